@@ -0,0 +1,404 @@
+//! Parameters changing over time.
+//!
+//! A [`Lane`] drives one float parameter with a [`Curve`], either breakpoints or an arbitrary
+//! wave. Before processing, [`Automated`] renders its lanes into a [`Schedule`] with a value
+//! for every sample, and blocks implementing [`Automatable`] read their parameters from it
+//! instead of their own fields.
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{ControlContext, ControlResult, MouseEvent};
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::layout::Layout;
+use crate::params::Parameters;
+use crate::vis::{self, VisualizeResult, BOX_SIZE, T};
+use crate::widgets::Range;
+
+/// How values go from one breakpoint to the next.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Constant ratio per second, e.g. for frequencies. Falls back to linear when the values
+    /// don't have the same sign.
+    Exponential,
+    /// Keeps the value until the next breakpoint.
+    Step,
+}
+
+impl Interpolation {
+    /// Value at `x` between 0 (`a`) and 1 (`b`).
+    pub fn interpolate(&self, a: f32, b: f32, x: f32) -> f32 {
+        match self {
+            Interpolation::Exponential if a * b > 0f32 => a * (b / a).powf(x),
+            Interpolation::Linear | Interpolation::Exponential => a + (b - a) * x,
+            Interpolation::Step => a,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Breakpoint {
+    #[serde(with = "crate::patch::millis")]
+    pub time: Duration,
+    pub value: f32,
+}
+
+impl Breakpoint {
+    pub fn new(time: Duration, value: f32) -> Self {
+        Self { time, value }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Curve {
+    /// Points sorted by time, the first and last values are held before and after them.
+    Breakpoints {
+        points: Vec<Breakpoint>,
+        #[serde(default)]
+        interpolation: Interpolation,
+    },
+    /// One value per sample, the last one is held when the wave is too short.
+    Wave(Wave),
+}
+
+impl Curve {
+    pub fn breakpoints(points: Vec<Breakpoint>, interpolation: Interpolation) -> Self {
+        Curve::Breakpoints {
+            points,
+            interpolation,
+        }
+    }
+
+    pub fn render(&self, len: usize) -> Wave {
+        match self {
+            Curve::Wave(wave) => (0..len)
+                .map(|n| wave.get(n).or(wave.last()).copied().unwrap_or_default())
+                .collect(),
+            Curve::Breakpoints {
+                points,
+                interpolation,
+            } => {
+                // index of the first breakpoint after the current sample
+                let mut next = 0;
+                (0..len)
+                    .map(|n| {
+                        let t = n as f32 / SR as f32;
+                        while next < points.len() && points[next].time.as_secs_f32() <= t {
+                            next += 1;
+                        }
+                        match (next.checked_sub(1).map(|i| points[i]), points.get(next)) {
+                            (None, None) => 0f32,
+                            (None, Some(b)) => b.value,
+                            (Some(a), None) => a.value,
+                            (Some(a), Some(b)) => {
+                                let x =
+                                    (t - a.time.as_secs_f32()) / (b.time - a.time).as_secs_f32();
+                                interpolation.interpolate(a.value, b.value, x)
+                            }
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Automation of one parameter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lane {
+    pub param: String,
+    pub curve: Curve,
+    /// Time constant of a one pole lowpass applied to the curve, avoids zipper noise when it
+    /// jumps (e.g. `Step` interpolation or a coarse wave).
+    #[serde(default, with = "crate::patch::millis")]
+    pub smoothing: Duration,
+}
+
+impl Lane {
+    pub fn new(param: &str, curve: Curve) -> Self {
+        Self {
+            param: param.to_string(),
+            curve,
+            smoothing: Duration::ZERO,
+        }
+    }
+
+    pub fn smoothing(mut self, smoothing: Duration) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Renders `len` samples of the curve, clamped to `range` and smoothed.
+    pub fn render(&self, len: usize, range: Range) -> Wave {
+        let mut wave = self.curve.render(len);
+        for x in wave.iter_mut() {
+            *x = x.clamp(range.min, range.max);
+        }
+        let samples = self.smoothing.as_secs_f32() * SR as f32;
+        if samples > 0f32 {
+            let a = (-1f32 / samples).exp();
+            let mut y = wave.first().copied().unwrap_or_default();
+            for x in wave.iter_mut() {
+                y = a * y + (1f32 - a) * *x;
+                *x = y;
+            }
+        }
+        wave
+    }
+}
+
+/// Values of the automated parameters for every sample of one processing pass.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    lanes: Vec<(String, Wave)>,
+}
+
+impl Schedule {
+    /// `None` when the parameter isn't automated.
+    pub fn value(&self, param: &str, n: usize) -> Option<f32> {
+        let (_, wave) = self.lanes.iter().find(|(x, _)| x == param)?;
+        wave.get(n).or(wave.last()).copied()
+    }
+}
+
+/// Blocks whose float parameters can change from one sample to the next.
+pub trait Automatable<I>: Block<I> + Parameters {
+    /// Number of samples processed for `input`, the lanes are rendered to that length.
+    fn num_samples(&self, input: &I) -> usize;
+
+    /// Like `process`, with the values in `schedule` taking the place of the block's own.
+    fn process_automated(&mut self, input: I, schedule: &Schedule) -> Self::Output;
+
+    /// Lays the block out with its own values around `out`, which the last call to `process`
+    /// or `process_automated` returned, so it isn't processed again.
+    fn visualize(&mut self, out: Self::Output) -> (Self::Output, VisualizeResult);
+}
+
+/// Runs a block with some of its parameters automated, and draws the lanes over it.
+///
+/// The block keeps its own parameter values, so the ones that aren't automated can still be
+/// changed from the diagram.
+#[derive(Debug)]
+pub struct Automated<B> {
+    block: B,
+    lanes: Vec<Lane>,
+    /// Of the parameter of every lane.
+    ranges: Vec<Range>,
+    /// Rendered by the last run, for the overlay.
+    schedule: Schedule,
+}
+
+impl<B: Parameters + std::fmt::Debug> Automated<B> {
+    pub fn new(block: B, lanes: Vec<Lane>) -> anyhow::Result<Self> {
+        let mut ranges = vec![];
+        for lane in lanes.iter() {
+            let param = block
+                .param(&lane.param)
+                .ok_or_else(|| anyhow!("{block:?} has no parameter {:?}", lane.param))?;
+            let range = param.range().ok_or_else(|| {
+                anyhow!(
+                    "only float parameters can be automated, {} is {:?}",
+                    param.name,
+                    param.kind
+                )
+            })?;
+            ranges.push(range);
+            if let Curve::Breakpoints { points, .. } = &lane.curve {
+                if points.windows(2).any(|x| x[0].time > x[1].time) {
+                    bail!("breakpoints of {:?} are not sorted by time", lane.param);
+                }
+            }
+        }
+        Ok(Self {
+            block,
+            lanes,
+            ranges,
+            schedule: Schedule::default(),
+        })
+    }
+
+    fn render(&self, len: usize) -> Schedule {
+        Schedule {
+            lanes: self
+                .lanes
+                .iter()
+                .zip(self.ranges.iter())
+                .map(|(x, range)| (x.param.clone(), x.render(len, *range)))
+                .collect(),
+        }
+    }
+
+    /// Draws every lane across the box of the block, scaled to the range of its parameter.
+    fn overlay(&self, layout: Layout) -> Layout {
+        let width = layout.width;
+        let lanes: Vec<_> = self
+            .schedule
+            .lanes
+            .iter()
+            .zip(self.ranges.iter())
+            .map(|((param, wave), range)| {
+                let points = (0..=width as usize)
+                    .filter_map(|x| {
+                        let n = (x as f32 / width * wave.len() as f32) as usize;
+                        let value = wave.get(n).or(wave.last())?;
+                        let y = BOX_SIZE - T - range.normalize(*value) * (BOX_SIZE - T * 2f32);
+                        Some(Vector2::new(x as f32, y.trunc()))
+                    })
+                    .collect::<Vec<_>>();
+                (param.clone(), points)
+            })
+            .collect();
+        layout.with_overlay(move |d| {
+            for (i, (param, points)) in lanes.into_iter().enumerate() {
+                let color = vis::LINE_COLORS[(i + 2) % vis::LINE_COLORS.len()];
+                d.draw_text(&param, T as _, (T + T * 3f32 * i as f32) as _, 1, color);
+                d.draw_line_strip(points, 1f32, color);
+            }
+        })
+    }
+}
+
+impl<I: Clone, B: Automatable<I>> Block<I> for Automated<B> {
+    type Output = B::Output;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(&mut self.block)
+    }
+
+    fn process(&mut self, input: I) -> Self::Output {
+        self.schedule = self.render(self.block.num_samples(&input));
+        self.block.process_automated(input, &self.schedule)
+    }
+
+    fn process_and_visualize(&mut self, input: I) -> (Self::Output, VisualizeResult) {
+        // the layout of the block with its own values, the output with the automated ones
+        let out = self.process(input);
+        let (out, result) = self.block.visualize(out);
+        let result = match result {
+            VisualizeResult::Block(layout) => VisualizeResult::Block(self.overlay(layout)),
+            VisualizeResult::None => VisualizeResult::None,
+        };
+        (out, result)
+    }
+
+    fn on_hover(&mut self, pos: Vector2, context: &mut ControlContext) -> ControlResult {
+        self.block.on_hover(pos, context)
+    }
+
+    fn on_unhover(&mut self, context: &mut ControlContext) -> ControlResult {
+        self.block.on_unhover(context)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        self.block.on_mouse(pos, event, context)
+    }
+
+    fn add_metadata(&mut self, key: &str, value: &str) {
+        self.block.add_metadata(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::{synths::Oscillator, ConstMultiplier};
+
+    fn ramp(from: f32, to: f32, interpolation: Interpolation) -> Curve {
+        Curve::breakpoints(
+            vec![
+                Breakpoint::new(Duration::from_secs(1), from),
+                Breakpoint::new(Duration::from_secs(2), to),
+            ],
+            interpolation,
+        )
+    }
+
+    #[test]
+    fn curves_interpolate_between_breakpoints() {
+        let at = |curve: &Curve, secs: f32| curve.render(SR * 3)[(secs * SR as f32) as usize];
+        let linear = ramp(1f32, 3f32, Interpolation::Linear);
+        // the first and last values are held
+        assert_eq!(at(&linear, 0f32), 1f32);
+        assert_eq!(at(&linear, 2.5), 3f32);
+        assert!((at(&linear, 1.5) - 2f32).abs() < 1e-3);
+        assert!((at(&linear, 1.25) - 1.5).abs() < 1e-3);
+
+        // halfway between 100 and 400 is their geometric mean
+        let exponential = ramp(100f32, 400f32, Interpolation::Exponential);
+        assert!((at(&exponential, 1.5) - 200f32).abs() < 0.1);
+        assert!((at(&exponential, 1.25) - 100f32 * 2f32.sqrt()).abs() < 0.1);
+        // falls back to linear through zero
+        let signs = ramp(-1f32, 1f32, Interpolation::Exponential);
+        assert!(at(&signs, 1.5).abs() < 1e-3);
+
+        let step = ramp(1f32, 3f32, Interpolation::Step);
+        assert_eq!(at(&step, 1.99), 1f32);
+        assert_eq!(at(&step, 2f32), 3f32);
+
+        let wave = Curve::Wave(vec![1f32, 2f32]);
+        assert_eq!(wave.render(4), vec![1f32, 2f32, 2f32, 2f32]);
+    }
+
+    #[test]
+    fn lanes_are_clamped_and_smoothed() {
+        let range = Range::linear(0f32, 1f32);
+        let clamped = Lane::new("gain", Curve::Wave(vec![-1f32, 0.5, 2f32])).render(3, range);
+        assert_eq!(clamped, vec![0f32, 0.5, 1f32]);
+
+        // a step reaches 1 - 1/e after one time constant
+        let wave = [vec![0f32; 100], vec![1f32; 5000]].concat();
+        let lane = Lane::new("gain", Curve::Wave(wave)).smoothing(Duration::from_millis(10));
+        let smooth = lane.render(5100, range);
+        let tau = SR / 100;
+        assert_eq!(smooth[99], 0f32);
+        assert!((smooth[100 + tau] - (1f32 - (-1f32).exp())).abs() < 0.01);
+        assert!(smooth.windows(2).all(|x| x[0] <= x[1]));
+        assert!(smooth[5099] > 0.99);
+    }
+
+    #[test]
+    fn lanes_drive_the_block() -> anyhow::Result<()> {
+        let lane = Lane::new("gain", ramp(0f32, 1f32, Interpolation::Linear));
+        let mut gain = Automated::new(ConstMultiplier(0.5), vec![lane])?;
+        let out = gain.process(vec![1f32; SR * 3]);
+        assert_eq!(out[SR / 2], 0f32);
+        assert!((out[SR * 3 / 2] - 0.5).abs() < 1e-3);
+        assert_eq!(out[SR * 5 / 2], 1f32);
+        // the block keeps its own value
+        assert_eq!(gain.block.0, 0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn lanes_are_validated() {
+        let unsorted = Curve::breakpoints(
+            vec![
+                Breakpoint::new(Duration::from_secs(2), 1f32),
+                Breakpoint::new(Duration::from_secs(1), 0f32),
+            ],
+            Interpolation::Linear,
+        );
+        let lane = Lane::new("gain", unsorted);
+        assert!(Automated::new(ConstMultiplier(1f32), vec![lane]).is_err());
+
+        let sorted = ramp(0f32, 1f32, Interpolation::Linear);
+        let missing = Lane::new("volume", sorted.clone());
+        assert!(Automated::new(ConstMultiplier(1f32), vec![missing]).is_err());
+
+        // the waveform is an enum
+        let wave = Lane::new("wave", sorted.clone());
+        assert!(Automated::new(Oscillator::default(), vec![wave]).is_err());
+        let freq = Lane::new("freq", sorted);
+        assert!(Automated::new(Oscillator::default(), vec![freq]).is_ok());
+    }
+}
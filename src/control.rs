@@ -1,3 +1,4 @@
+use raylib::math::Vector2;
 use raylib::{RaylibHandle, RaylibThread};
 
 pub enum ControlResult {
@@ -11,3 +12,31 @@ pub struct ControlContext<'a, 'b> {
 
     pub is_dirty: bool,
 }
+
+impl ControlResult {
+    pub fn is_handled(&self) -> bool {
+        matches!(self, ControlResult::Block)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MouseEvent {
+    /// Left button pressed.
+    Click,
+    /// Left button held while moving, by this much. Sent to the position where the drag
+    /// started so a control keeps following the mouse once it leaves it.
+    Drag(Vector2),
+    /// Mouse wheel moved.
+    Scroll(f32),
+}
+
+/// Marks the system dirty when a control changed, and stops the event from doing
+/// anything else (e.g. zooming when scrolling over a knob).
+pub fn handled(context: &mut ControlContext, changed: bool) -> ControlResult {
+    if changed {
+        context.is_dirty = true;
+        ControlResult::Block
+    } else {
+        ControlResult::Passthrough
+    }
+}
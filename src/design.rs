@@ -0,0 +1,744 @@
+//! Classical IIR filter design.
+//!
+//! Same steps as `scipy.signal.iirfilter`: an analog prototype with a cutoff of 1 rad/s, as
+//! zeros, poles and gain; moved to the band with its edges prewarped; turned digital with the
+//! bilinear transform; then split into second order sections, which unlike a single high
+//! order transfer function don't lose precision.
+use std::f64::consts::PI;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::blocks::{filter_section_from, Coefficients};
+use crate::dsp::{Wave, SR};
+use crate::graph::{Block, Streaming};
+use crate::spectrum::Complex;
+use crate::transfer::{self, Lti, TransferFunction, ZeroPoleGain};
+use crate::vis::{self, VisualizeResult};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Family {
+    /// Maximally flat passband, -3dB at the cutoff.
+    Butterworth,
+    /// `ripple` dB of ripple in the passband, the cutoff is where the response drops below it.
+    Chebyshev1 { ripple: f32 },
+    /// At least `attenuation` dB in the stopband, the cutoff is where the stopband starts.
+    Chebyshev2 { attenuation: f32 },
+    /// Ripple in both bands, the steepest transition for an order. The cutoff is the edge of
+    /// the passband like for `Chebyshev1`.
+    Elliptic { ripple: f32, attenuation: f32 },
+    /// Maximally flat group delay, -3dB at the cutoff.
+    Bessel,
+}
+
+/// Cutoffs in Hz.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Band {
+    LowPass(f32),
+    HighPass(f32),
+    BandPass(f32, f32),
+    BandStop(f32, f32),
+}
+
+/// Band-pass and band-stop filters end up with twice the order of the prototype.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Design {
+    pub family: Family,
+    pub band: Band,
+    pub order: usize,
+}
+
+impl Design {
+    pub fn new(family: Family, band: Band, order: usize) -> Self {
+        Self {
+            family,
+            band,
+            order,
+        }
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        let nyquist = SR as f32 / 2f32;
+        let valid = |f: f32| f > 0f32 && f < nyquist;
+        match self.band {
+            Band::LowPass(f) | Band::HighPass(f) if !valid(f) => {
+                bail!("cutoff has to be between 0 and {nyquist}Hz, got {f}")
+            }
+            Band::BandPass(low, high) | Band::BandStop(low, high)
+                if !valid(low) || !valid(high) || low >= high =>
+            {
+                bail!("band edges have to be increasing and between 0 and {nyquist}Hz, got {low} and {high}")
+            }
+            _ => {}
+        }
+        match self.family {
+            Family::Chebyshev1 { ripple } | Family::Elliptic { ripple, .. } if ripple <= 0f32 => {
+                bail!("ripple has to be positive, got {ripple}dB")
+            }
+            Family::Chebyshev2 { attenuation } | Family::Elliptic { attenuation, .. }
+                if attenuation <= 0f32 =>
+            {
+                bail!("attenuation has to be positive, got {attenuation}dB")
+            }
+            // the roots of higher order Bessel polynomials are out of reach of f32
+            Family::Bessel if self.order > 10 => {
+                bail!("Bessel filters go up to order 10, got {}", self.order)
+            }
+            _ => {}
+        }
+        if self.order == 0 {
+            bail!("order has to be at least 1");
+        }
+        Ok(())
+    }
+
+    /// The digital filter as zeros, poles and gain.
+    pub fn zpk(&self) -> anyhow::Result<ZeroPoleGain> {
+        self.check()?;
+        let n = self.order;
+        let prototype = match self.family {
+            Family::Butterworth => butterworth(n),
+            Family::Chebyshev1 { ripple } => chebyshev1(n, ripple as f64),
+            Family::Chebyshev2 { attenuation } => chebyshev2(n, attenuation as f64),
+            Family::Elliptic {
+                ripple,
+                attenuation,
+            } => elliptic(n, ripple as f64, attenuation as f64),
+            Family::Bessel => bessel(n),
+        };
+        // the prototype's DC gain is where its passband maps to: DC for low-pass and
+        // band-stop, Nyquist for high-pass and the center frequency for band-pass
+        let dc = prototype.gain as f64 * ratio(&prototype, Complex::ZERO);
+        let (analog, reference) = match self.band {
+            Band::LowPass(f) => (lowpass(prototype, prewarp(f)), Complex::ONE),
+            Band::HighPass(f) => (highpass(prototype, prewarp(f)), -Complex::ONE),
+            Band::BandPass(low, high) => {
+                let center = (prewarp(low) * prewarp(high)).sqrt();
+                let w = 2f32 * (center / (2f32 * SR as f32)).atan();
+                let analog = bandpass(prototype, prewarp(low), prewarp(high));
+                (analog, Complex::from_polar(1f32, w))
+            }
+            Band::BandStop(low, high) => {
+                let analog = bandstop(prototype, prewarp(low), prewarp(high));
+                (analog, Complex::ONE)
+            }
+        };
+        let digital = bilinear(analog);
+        Ok(ZeroPoleGain {
+            gain: (dc / ratio(&digital, reference)) as f32,
+            ..digital
+        })
+    }
+
+    pub fn sos(&self) -> anyhow::Result<Sos> {
+        Ok(Sos::from_zpk(&self.zpk()?))
+    }
+}
+
+/// Analog frequency in rad/s that the bilinear transform maps to `freq` Hz.
+fn prewarp(freq: f32) -> f32 {
+    (2f64 * SR as f64 * (PI * freq as f64 / SR as f64).tan()) as f32
+}
+
+fn product(x: &[Complex]) -> Complex {
+    x.iter().fold(Complex::ONE, |acc, x| acc * *x)
+}
+
+fn negated(x: &[Complex]) -> Vec<Complex> {
+    x.iter().map(|x| -*x).collect()
+}
+
+/// `-N + 1, -N + 3, ..., N - 1`
+fn symmetric(n: usize) -> impl Iterator<Item = f64> {
+    (0..n).map(move |i| (2 * i) as f64 - n as f64 + 1f64)
+}
+
+fn butterworth(n: usize) -> ZeroPoleGain {
+    ZeroPoleGain {
+        zeros: vec![],
+        poles: symmetric(n)
+            .map(|m| -Complex::from_polar(1f32, (PI * m / (2 * n) as f64) as f32))
+            .collect(),
+        gain: 1f32,
+    }
+}
+
+fn chebyshev1(n: usize, ripple: f64) -> ZeroPoleGain {
+    let eps = (10f64.powf(0.1 * ripple) - 1f64).sqrt();
+    let mu = (1f64 / eps).asinh() / n as f64;
+    // -sinh(mu + jθ)
+    let poles: Vec<Complex> = symmetric(n)
+        .map(|m| {
+            let theta = PI * m / (2 * n) as f64;
+            Complex::new(
+                (-mu.sinh() * theta.cos()) as f32,
+                (-mu.cosh() * theta.sin()) as f32,
+            )
+        })
+        .collect();
+    let mut gain = product(&negated(&poles)).re;
+    if n.is_multiple_of(2) {
+        gain /= (1f64 + eps * eps).sqrt() as f32;
+    }
+    ZeroPoleGain {
+        zeros: vec![],
+        poles,
+        gain,
+    }
+}
+
+fn chebyshev2(n: usize, attenuation: f64) -> ZeroPoleGain {
+    let de = 1f64 / (10f64.powf(0.1 * attenuation) - 1f64).sqrt();
+    let mu = (1f64 / de).asinh() / n as f64;
+    let zeros: Vec<Complex> = symmetric(n)
+        .filter(|m| *m != 0f64)
+        .map(|m| Complex::new(0f32, (1f64 / (m * PI / (2 * n) as f64).sin()) as f32))
+        .collect();
+    let poles: Vec<Complex> = symmetric(n)
+        .map(|m| {
+            let theta = PI * m / (2 * n) as f64;
+            let p = Complex::new(
+                (-mu.sinh() * theta.cos()) as f32,
+                (-mu.cosh() * theta.sin()) as f32,
+            );
+            Complex::ONE / p
+        })
+        .collect();
+    let gain = (product(&negated(&poles)) / product(&negated(&zeros))).re;
+    ZeroPoleGain { zeros, poles, gain }
+}
+
+/// https://github.com/scipy/scipy/blob/v1.5.4/scipy/signal/filter_design.py#L4224
+fn elliptic(n: usize, ripple: f64, attenuation: f64) -> ZeroPoleGain {
+    let eps_sq = 10f64.powf(0.1 * ripple) - 1f64;
+    if n == 1 {
+        let p = -(1f64 / eps_sq).sqrt() as f32;
+        return ZeroPoleGain {
+            zeros: vec![],
+            poles: vec![Complex::from(p)],
+            gain: -p,
+        };
+    }
+
+    let ck1_sq = eps_sq / (10f64.powf(0.1 * attenuation) - 1f64);
+    let m = elliptic_parameter(n, ck1_sq);
+    let capk = ellipk(m);
+
+    let sections: Vec<(f64, f64, f64)> = (0..n / 2 + n % 2)
+        .map(|i| ellipj((2 * i + 1 - n % 2) as f64 * capk / n as f64, m))
+        .collect();
+
+    let mut zeros = vec![];
+    for (s, _, _) in sections.iter().filter(|(s, _, _)| s.abs() > 1e-12) {
+        let z = (1f64 / (m.sqrt() * s)) as f32;
+        zeros.push(Complex::new(0f32, z));
+        zeros.push(Complex::new(0f32, -z));
+    }
+
+    // inverse Jacobi sc of 1 / sqrt(eps_sq) with the complementary modulus of ck1_sq
+    let r = ellipf((1f64 / eps_sq.sqrt()).atan(), 1f64 - ck1_sq);
+    let v0 = capk * r / (n as f64 * ellipk(ck1_sq));
+    let (sv, cv, dv) = ellipj(v0, 1f64 - m);
+
+    let mut poles = vec![];
+    for (s, c, d) in sections.iter() {
+        let denominator = 1f64 - (d * sv).powi(2);
+        let p = Complex::new(
+            (-(c * d * sv * cv) / denominator) as f32,
+            (-(s * dv) / denominator) as f32,
+        );
+        poles.push(p);
+        if p.im.abs() > 1e-7 * p.norm() {
+            poles.push(p.conj());
+        }
+    }
+
+    let mut gain = (product(&negated(&poles)) / product(&negated(&zeros))).re;
+    if n.is_multiple_of(2) {
+        gain /= (1f64 + eps_sq).sqrt() as f32;
+    }
+    ZeroPoleGain { zeros, poles, gain }
+}
+
+/// Parameter `m` of an elliptic filter of order `n`, its stopband starts at `1 / sqrt(m)` rad/s.
+fn elliptic_parameter(n: usize, ck1_sq: f64) -> f64 {
+    modulus_from_ratio(n as f64 * ellipk(ck1_sq) / ellipk(1f64 - ck1_sq))
+}
+
+/// Roots of the reverse Bessel polynomial, scaled so the response is -3dB at 1 rad/s.
+fn bessel(n: usize) -> ZeroPoleGain {
+    // a[k] = (2n - k)! / (2^(n - k) k! (n - k)!), the coefficient of s^k
+    let factorial = |x: usize| (1..=x).fold(1f64, |acc, i| acc * i as f64);
+    let a: Vec<f64> = (0..=n)
+        .map(|k| {
+            factorial(2 * n - k) / (2f64.powi((n - k) as i32) * factorial(k) * factorial(n - k))
+        })
+        .collect();
+    // with s = c x the coefficients of x stay close to 1, which keeps the root finding precise
+    let c = a[0].powf(1f64 / n as f64);
+    let scaled: Vec<f32> = (0..=n)
+        .rev()
+        .map(|k| (a[k] * c.powi(k as i32) / a[0]) as f32)
+        .collect();
+    let poles: Vec<Complex> = transfer::roots(&scaled)
+        .into_iter()
+        .map(|x| x * c as f32)
+        .collect();
+
+    // |H(jw)| with H(0) = 1 decreases monotonically, bisect the -3dB point
+    let magnitude = |w: f64| {
+        poles.iter().fold(1f64, |acc, p| {
+            acc * p.norm() as f64 / (p.re as f64).hypot(w - p.im as f64)
+        })
+    };
+    let (mut low, mut high) = (1e-3f64, 1e3f64);
+    for _ in 0..100 {
+        let mid = (low * high).sqrt();
+        if magnitude(mid) > 0.5f64.sqrt() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let w3 = (low * high).sqrt() as f32;
+
+    let poles: Vec<Complex> = poles.into_iter().map(|p| p / w3).collect();
+    ZeroPoleGain {
+        zeros: vec![],
+        gain: product(&negated(&poles)).re,
+        poles,
+    }
+}
+
+fn degree(zpk: &ZeroPoleGain) -> usize {
+    zpk.poles.len() - zpk.zeros.len()
+}
+
+/// Real `Π(x - zeros) / Π(x - poles)`, summing logarithms so high orders don't overflow.
+fn ratio(zpk: &ZeroPoleGain, x: Complex) -> f64 {
+    let sum = |roots: &[Complex]| {
+        roots.iter().fold((0f64, 0f64), |(magnitude, phase), root| {
+            let d = x - *root;
+            (magnitude + (d.norm() as f64).ln(), phase + d.arg() as f64)
+        })
+    };
+    let ((zeros, zeros_phase), (poles, poles_phase)) = (sum(&zpk.zeros), sum(&zpk.poles));
+    (zeros - poles).exp() * (zeros_phase - poles_phase).cos()
+}
+
+// The transforms below only move the roots, the gain is set once the filter is digital.
+
+fn lowpass(zpk: ZeroPoleGain, w: f32) -> ZeroPoleGain {
+    ZeroPoleGain {
+        zeros: zpk.zeros.iter().map(|x| *x * w).collect(),
+        poles: zpk.poles.iter().map(|x| *x * w).collect(),
+        ..zpk
+    }
+}
+
+fn highpass(zpk: ZeroPoleGain, w: f32) -> ZeroPoleGain {
+    let invert =
+        |x: &[Complex]| -> Vec<Complex> { x.iter().map(|x| Complex::from(w) / *x).collect() };
+    let mut zeros = invert(&zpk.zeros);
+    zeros.extend(std::iter::repeat_n(Complex::ZERO, degree(&zpk)));
+    ZeroPoleGain {
+        poles: invert(&zpk.poles),
+        zeros,
+        ..zpk
+    }
+}
+
+/// Every root `x` becomes the two roots of `s^2 - 2 x s + w0^2`.
+fn split(roots: &[Complex], w0: f32) -> Vec<Complex> {
+    let w0_sq = Complex::from(w0 * w0);
+    roots
+        .iter()
+        .flat_map(|x| {
+            let d = (*x * *x - w0_sq).sqrt();
+            [*x + d, *x - d]
+        })
+        .collect()
+}
+
+fn bandpass(zpk: ZeroPoleGain, low: f32, high: f32) -> ZeroPoleGain {
+    let (bw, w0) = (high - low, (low * high).sqrt());
+    let scale = |x: &[Complex]| -> Vec<Complex> { x.iter().map(|x| *x * (bw / 2f32)).collect() };
+    let mut zeros = split(&scale(&zpk.zeros), w0);
+    zeros.extend(std::iter::repeat_n(Complex::ZERO, degree(&zpk)));
+    ZeroPoleGain {
+        poles: split(&scale(&zpk.poles), w0),
+        zeros,
+        ..zpk
+    }
+}
+
+fn bandstop(zpk: ZeroPoleGain, low: f32, high: f32) -> ZeroPoleGain {
+    let (bw, w0) = (high - low, (low * high).sqrt());
+    let invert = |x: &[Complex]| -> Vec<Complex> {
+        x.iter().map(|x| Complex::from(bw / 2f32) / *x).collect()
+    };
+    let mut zeros = split(&invert(&zpk.zeros), w0);
+    for _ in 0..degree(&zpk) {
+        zeros.push(Complex::new(0f32, w0));
+        zeros.push(Complex::new(0f32, -w0));
+    }
+    ZeroPoleGain {
+        poles: split(&invert(&zpk.poles), w0),
+        zeros,
+        ..zpk
+    }
+}
+
+/// `s = 2 fs (z - 1) / (z + 1)`, zeros at infinity end up at Nyquist.
+fn bilinear(zpk: ZeroPoleGain) -> ZeroPoleGain {
+    let fs2 = Complex::from(2f32 * SR as f32);
+    let map =
+        |x: &[Complex]| -> Vec<Complex> { x.iter().map(|x| (fs2 + *x) / (fs2 - *x)).collect() };
+    let mut zeros = map(&zpk.zeros);
+    zeros.extend(std::iter::repeat_n(-Complex::ONE, degree(&zpk)));
+    ZeroPoleGain {
+        poles: map(&zpk.poles),
+        zeros,
+        ..zpk
+    }
+}
+
+/// Arithmetic-geometric mean.
+fn agm(mut a: f64, mut b: f64) -> f64 {
+    while (a - b).abs() > 1e-15 * a {
+        (a, b) = ((a + b) / 2f64, (a * b).sqrt());
+    }
+    a
+}
+
+/// Complete elliptic integral of the first kind `K(m)`, `m` being the parameter (the squared
+/// modulus).
+fn ellipk(m: f64) -> f64 {
+    PI / 2f64 / agm(1f64, (1f64 - m).sqrt())
+}
+
+/// Incomplete elliptic integral of the first kind `F(φ|m)`, with Carlson's `RF`.
+///
+/// https://en.wikipedia.org/wiki/Carlson_symmetric_form
+fn ellipf(phi: f64, m: f64) -> f64 {
+    let (sin, cos) = phi.sin_cos();
+    let (mut x, mut y, mut z) = (cos * cos, 1f64 - m * sin * sin, 1f64);
+    loop {
+        let mean = (x + y + z) / 3f64;
+        if [x, y, z].iter().all(|v| (v - mean).abs() < 1e-10 * mean) {
+            let (dx, dy, dz) = (1f64 - x / mean, 1f64 - y / mean, 1f64 - z / mean);
+            let e2 = dx * dy - dz * dz;
+            let e3 = dx * dy * dz;
+            let rf = (1f64 - e2 / 10f64 + e3 / 14f64 + e2 * e2 / 24f64 - 3f64 * e2 * e3 / 44f64)
+                / mean.sqrt();
+            return sin * rf;
+        }
+        let lambda = x.sqrt() * y.sqrt() + y.sqrt() * z.sqrt() + z.sqrt() * x.sqrt();
+        (x, y, z) = (
+            (x + lambda) / 4f64,
+            (y + lambda) / 4f64,
+            (z + lambda) / 4f64,
+        );
+    }
+}
+
+/// Jacobi elliptic functions `(sn, cn, dn)` of `u` with parameter `m`, by descending Landen
+/// transformations (Abramowitz and Stegun 16.4).
+fn ellipj(u: f64, m: f64) -> (f64, f64, f64) {
+    let mut a = vec![1f64];
+    let mut c = vec![m.sqrt()];
+    let mut b = (1f64 - m).sqrt();
+    while c.last().unwrap().abs() > 1e-15 && a.len() < 32 {
+        let an = *a.last().unwrap();
+        a.push((an + b) / 2f64);
+        c.push((an - b) / 2f64);
+        b = (an * b).sqrt();
+    }
+    let last = a.len() - 1;
+    let mut phi = 2f64.powi(last as i32) * a[last] * u;
+    for i in (1..=last).rev() {
+        phi = ((c[i] / a[i] * phi.sin()).asin() + phi) / 2f64;
+    }
+    let (sn, cn) = phi.sin_cos();
+    (sn, cn, (1f64 - m * sn * sn).sqrt())
+}
+
+/// Parameter `m` such that `K(m) / K(1 - m) == ratio`, from the nome with theta functions.
+fn modulus_from_ratio(ratio: f64) -> f64 {
+    let q = (-PI / ratio).exp();
+    let theta2 = 2f64 * q.powf(0.25) * (0..32).map(|n| q.powi(n * (n + 1))).sum::<f64>();
+    let theta3 = 1f64 + 2f64 * (1..32).map(|n| q.powi(n * n)).sum::<f64>();
+    (theta2 / theta3).powi(4)
+}
+
+/// Cascade of second order sections.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Sos {
+    pub sections: Vec<Coefficients>,
+}
+
+impl Sos {
+    /// Pairs every pole (or conjugate pair) with the closest zeros, starting with the poles
+    /// closest to the unit circle, which end up in the last sections. The gain goes in the
+    /// first section.
+    pub fn from_zpk(zpk: &ZeroPoleGain) -> Self {
+        let mut poles = groups(&zpk.poles);
+        let mut zeros = groups(&zpk.zeros);
+        poles.sort_by(|a, b| b[0].norm().total_cmp(&a[0].norm()));
+
+        let mut sections = vec![];
+        for pole in poles {
+            let closest = zeros
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| {
+                    x.len() == pole.len() || zeros.iter().all(|x| x.len() != pole.len())
+                })
+                .min_by(|(_, a), (_, b)| {
+                    (a[0] - pole[0]).norm().total_cmp(&(b[0] - pole[0]).norm())
+                })
+                .map(|(i, _)| i);
+            let zero = closest.map(|i| zeros.remove(i)).unwrap_or_default();
+            sections.push(section(&zero, &pole));
+        }
+        sections.reverse();
+
+        if let Some((b, _)) = sections.first_mut() {
+            for x in b.iter_mut() {
+                *x *= zpk.gain;
+            }
+        } else {
+            sections.push(([zpk.gain, 0f32, 0f32], [0f32, 0f32]));
+        }
+        Self { sections }
+    }
+
+    pub fn response(&self, freq: f32) -> Complex {
+        self.sections
+            .iter()
+            .map(|x| section_transfer_function(x).response(freq))
+            .fold(Complex::ONE, |acc, x| acc * x)
+    }
+}
+
+/// Conjugate pairs, the real roots two by two and the last one alone if there's an odd
+/// number of them.
+fn groups(roots: &[Complex]) -> Vec<Vec<Complex>> {
+    // rounding leaves some real roots slightly complex
+    let is_real = |x: &Complex| x.im.abs() <= 1e-6 * x.norm();
+    let mut groups: Vec<Vec<Complex>> = roots
+        .iter()
+        .filter(|x| x.im > 0f32 && !is_real(x))
+        .map(|x| vec![*x, x.conj()])
+        .collect();
+    let mut reals: Vec<Complex> = roots
+        .iter()
+        .filter(|x| is_real(x))
+        .map(|x| Complex::from(x.re))
+        .collect();
+    reals.sort_by(|a, b| b.norm().total_cmp(&a.norm()));
+    groups.extend(reals.chunks(2).map(|x| x.to_vec()));
+    groups
+}
+
+fn section(zeros: &[Complex], poles: &[Complex]) -> Coefficients {
+    // z^-2 (z - z0)(z - z1) / ((z - p0)(z - p1)), delayed when there are fewer zeros
+    let mut b = vec![0f32; poles.len().saturating_sub(zeros.len())];
+    b.extend(transfer::poly_from_roots(zeros));
+    b.resize(3, 0f32);
+    let mut a = transfer::poly_from_roots(poles);
+    a.resize(3, 0f32);
+    ([b[0], b[1], b[2]], [a[1], a[2]])
+}
+
+fn section_transfer_function((b, a): &Coefficients) -> TransferFunction {
+    TransferFunction {
+        b: b.to_vec(),
+        a: vec![1f32, a[0], a[1]],
+    }
+}
+
+impl Block<Wave> for Sos {
+    type Output = Wave;
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.process_chunk(&input, &mut vec![])
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("IIR\n{} sections", self.sections.len());
+        vis::visualize_simple_box(&text, out)
+    }
+}
+
+impl Streaming for Sos {
+    /// `[z1, z2]` of every section.
+    type State = Vec<[f32; 2]>;
+
+    fn process_chunk(&mut self, chunk: &[f32], state: &mut Self::State) -> Wave {
+        state.resize(self.sections.len(), Default::default());
+        self.sections
+            .iter()
+            .zip(state.iter_mut())
+            .fold(chunk.to_vec(), |wave, (x, state)| {
+                filter_section_from(&wave, *x, state)
+            })
+    }
+}
+
+impl Lti for Sos {
+    fn transfer_function(&self) -> TransferFunction {
+        self.sections
+            .iter()
+            .fold(TransferFunction::identity(), |acc, x| {
+                acc.cascade(&section_transfer_function(x))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANDS: [Band; 4] = [
+        Band::LowPass(1000f32),
+        Band::HighPass(1000f32),
+        Band::BandPass(500f32, 2000f32),
+        Band::BandStop(500f32, 2000f32),
+    ];
+
+    /// Frequencies in Hz that `omega` rad/s of the prototype end up at, 0 being the middle of
+    /// the passband.
+    fn edges(band: Band, omega: f64) -> Vec<f32> {
+        let warp = |f: f32| prewarp(f) as f64;
+        let unwarp = |w: f64| (SR as f64 / PI * (w / (2f64 * SR as f64)).atan()) as f32;
+        // the positive roots of w^2 - b w - w0^2 and w^2 + b w - w0^2
+        let around = |low: f32, high: f32, b: f64| {
+            let w0_sq = warp(low) * warp(high);
+            let w = (b + (b * b + 4f64 * w0_sq).sqrt()) / 2f64;
+            vec![unwarp(w0_sq / w), unwarp(w)]
+        };
+        match band {
+            Band::LowPass(f) => vec![unwarp(warp(f) * omega)],
+            Band::HighPass(f) => vec![unwarp(warp(f) / omega)],
+            Band::BandPass(low, high) => around(low, high, omega * (warp(high) - warp(low))),
+            Band::BandStop(low, high) => around(low, high, (warp(high) - warp(low)) / omega),
+        }
+    }
+
+    /// dB at every frequency `omega` ends up at.
+    fn gains(sos: &Sos, band: Band, omega: f64) -> Vec<f32> {
+        edges(band, omega)
+            .into_iter()
+            .map(|f| 20f32 * sos.response(f).norm().log10())
+            .collect()
+    }
+
+    /// Highest dB from `omega` to a hundred times higher.
+    fn max_gain(sos: &Sos, band: Band, omega: f64) -> f32 {
+        (0..=2000)
+            .flat_map(|i| gains(sos, band, omega * 100f64.powf(i as f64 / 2000f64)))
+            .fold(f32::MIN, f32::max)
+    }
+
+    fn assert_close(gains: &[f32], expected: f32, tolerance: f32, what: &str) {
+        for x in gains {
+            assert!(
+                (x - expected).abs() < tolerance,
+                "{what}: {x}dB != {expected}dB"
+            );
+        }
+    }
+
+    #[test]
+    fn butterworth_and_bessel_are_3db_down_at_the_cutoff() {
+        for family in [Family::Butterworth, Family::Bessel] {
+            for band in BANDS {
+                for order in [1, 2, 5] {
+                    let what = format!("{family:?} {band:?} {order}");
+                    let sos = Design::new(family, band, order).sos().unwrap();
+                    assert_close(&gains(&sos, band, 0f64), 0f32, 0.01, &what);
+                    assert_close(&gains(&sos, band, 1f64), -3.0103, 0.05, &what);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chebyshev1_ripples_down_to_the_cutoff() {
+        let ripple = 1f32;
+        for band in BANDS {
+            for order in [3, 4] {
+                let what = format!("{band:?} {order}");
+                let sos = Design::new(Family::Chebyshev1 { ripple }, band, order)
+                    .sos()
+                    .unwrap();
+                let passband = if order % 2 == 0 { -ripple } else { 0f32 };
+                assert_close(&gains(&sos, band, 0f64), passband, 0.01, &what);
+                assert_close(&gains(&sos, band, 1f64), -ripple, 0.05, &what);
+                let ripples: Vec<f32> = (0..=200)
+                    .flat_map(|i| gains(&sos, band, i as f64 / 200f64))
+                    .collect();
+                let highest = ripples.iter().copied().fold(f32::MIN, f32::max);
+                let lowest = ripples.iter().copied().fold(f32::MAX, f32::min);
+                assert_close(&[highest], 0f32, 0.01, &what);
+                assert_close(&[lowest], -ripple, 0.05, &what);
+            }
+        }
+    }
+
+    #[test]
+    fn chebyshev2_and_elliptic_attenuate_the_stopband() {
+        let (ripple, attenuation) = (1f32, 40f32);
+        for band in BANDS {
+            for order in [3, 4] {
+                let what = format!("Chebyshev2 {band:?} {order}");
+                let sos = Design::new(Family::Chebyshev2 { attenuation }, band, order)
+                    .sos()
+                    .unwrap();
+                assert_close(&gains(&sos, band, 0f64), 0f32, 0.01, &what);
+                assert_close(&gains(&sos, band, 1f64), -attenuation, 0.2, &what);
+                assert_close(&[max_gain(&sos, band, 1f64)], -attenuation, 0.2, &what);
+
+                let what = format!("Elliptic {band:?} {order}");
+                let family = Family::Elliptic {
+                    ripple,
+                    attenuation,
+                };
+                let sos = Design::new(family, band, order).sos().unwrap();
+                let passband = if order % 2 == 0 { -ripple } else { 0f32 };
+                assert_close(&gains(&sos, band, 0f64), passband, 0.01, &what);
+                assert_close(&gains(&sos, band, 1f64), -ripple, 0.05, &what);
+                let eps_sq = 10f64.powf(0.1 * ripple as f64) - 1f64;
+                let ck1_sq = eps_sq / (10f64.powf(0.1 * attenuation as f64) - 1f64);
+                let stopband = 1f64 / elliptic_parameter(order, ck1_sq).sqrt();
+                assert_close(&gains(&sos, band, stopband), -attenuation, 0.2, &what);
+                assert_close(&[max_gain(&sos, band, stopband)], -attenuation, 0.2, &what);
+            }
+        }
+    }
+
+    #[test]
+    fn elliptic_functions_match_their_identities() {
+        for m in [0.1f64, 0.5, 0.9, 0.999] {
+            let k = ellipk(m);
+            let (sn, cn, dn) = ellipj(k, m);
+            assert!(
+                (sn - 1f64).abs() < 1e-9 && cn.abs() < 1e-6,
+                "{m}: {sn} {cn}"
+            );
+            assert!((dn - (1f64 - m).sqrt()).abs() < 1e-9, "{m}: {dn}");
+            assert!((ellipf(PI / 2f64, m) - k).abs() < 1e-9, "{m}");
+            for phi in [0.1f64, 0.7, 1.3] {
+                let (sn, cn, _) = ellipj(ellipf(phi, m), m);
+                assert!((sn - phi.sin()).abs() < 1e-9 && (cn - phi.cos()).abs() < 1e-9);
+            }
+            let ratio = k / ellipk(1f64 - m);
+            assert!((modulus_from_ratio(ratio) - m).abs() < 1e-9, "{m}");
+        }
+        let (sn, cn, dn) = ellipj(0.6, 0f64);
+        assert!((sn - 0.6f64.sin()).abs() < 1e-12 && (cn - 0.6f64.cos()).abs() < 1e-12);
+        assert_eq!(dn, 1f64);
+    }
+}
@@ -1,11 +1,24 @@
-use crate::vis::{DrawContext, VisualizeResult};
+use crate::vis::VisualizeResult;
 
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 use std::fmt::Debug;
 pub const SR: usize = 44100;
 
 pub type Wave = Vec<f32>;
 
+/// Uniform numbers in (0, 1] from a xorshift generator, the same for a given `seed`. Never 0 so
+/// their logarithm is finite.
+pub fn uniform(seed: u32) -> impl FnMut() -> f32 {
+    let mut state = seed.max(1);
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        ((state >> 8) as f32 + 1f32) / (1u32 << 24) as f32
+    }
+}
+
 pub mod signals {
     pub use super::*;
 
@@ -24,15 +37,16 @@ pub mod signals {
 
 pub mod blocks {
 
-    use raylib::{
-        math::Vector2,
-        prelude::{RaylibDraw, RaylibTextureModeExt},
-        texture::RaylibTexture2D,
-    };
+    use raylib::math::{Rectangle, Vector2};
 
     use crate::{
-        graph::{Block, DInto},
+        automation::{Automatable, Schedule},
+        control::{self, ControlContext, ControlResult, MouseEvent},
+        graph::{Block, DInto, Streaming},
+        layout::Layout,
+        params::{Param, ParamValue, Parameters},
         vis,
+        widgets::{self, Knob, Range, Slider, Toggle},
     };
 
     pub use super::*;
@@ -41,12 +55,22 @@ pub mod blocks {
 
         use std::time::Duration;
 
+        use raylib::math::Vector2;
+
+        use crate::automation::{Automatable, Schedule};
+        use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+        use crate::params::{Param, ParamValue, Parameters};
+        use crate::widgets::{self, Knob, Range, Toggle};
+
+        use crate::graph::CanFeedback;
+        use crate::spectrum::Complex;
+        use crate::transfer::TransferFunction;
         use crate::{graph::Block, vis};
 
         pub use super::*;
 
         /// https://tttapa.github.io/Pages/Mathematics/Systems-and-Control-Theory/Digital-filters/DTLTI-Systems,-Transfer-Functions,-and-the-Z-transform/Impulse-and-Step-Response.html#the-kronecker-delta-function
-        #[derive(Debug)]
+        #[derive(Debug, Clone, Serialize, Deserialize)]
         pub enum KroneckerDelta {
             Start,
             Center,
@@ -56,6 +80,10 @@ pub mod blocks {
         impl Block<Duration> for KroneckerDelta {
             type Output = Wave;
 
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
             fn process(&mut self, duration: Duration) -> Self::Output {
                 let num_samples = (SR as f32 * duration.as_secs_f32()) as usize;
                 let mut wave = vec![0f32; num_samples];
@@ -67,13 +95,41 @@ pub mod blocks {
                 wave
             }
 
-            fn process_and_visualize(
-                &mut self,
-                dur: Duration,
-                context: &mut DrawContext,
-            ) -> (Self::Output, VisualizeResult) {
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
                 let out = self.process(dur.clone());
-                vis::visualize_simple_box(context, &format!("Delta\n{self:?}"), out)
+                vis::visualize_simple_box(&format!("Delta\n{self:?}"), out)
+            }
+        }
+
+        impl KroneckerDelta {
+            const ALL: [KroneckerDelta; 3] = [
+                KroneckerDelta::Start,
+                KroneckerDelta::Center,
+                KroneckerDelta::End,
+            ];
+        }
+
+        impl Parameters for KroneckerDelta {
+            fn params(&self) -> &'static [Param] {
+                const PARAMS: &[Param] = &[Param::choice("position", &["Start", "Center", "End"])];
+                PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "position" => Some(ParamValue::Enum(match self {
+                        KroneckerDelta::Start => 0,
+                        KroneckerDelta::Center => 1,
+                        KroneckerDelta::End => 2,
+                    })),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                if let ("position", Some(x)) = (name, value.as_enum()) {
+                    *self = Self::ALL[x].clone();
+                }
             }
         }
 
@@ -84,26 +140,523 @@ pub mod blocks {
         impl Block<Duration> for HeavisideStep {
             type Output = Wave;
 
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
             fn process(&mut self, duration: Duration) -> Self::Output {
                 let num_samples = (SR as f32 * duration.as_secs_f32()) as usize;
                 let wave = vec![1f32; num_samples];
                 wave
             }
 
-            fn process_and_visualize(
-                &mut self,
-                dur: Duration,
-                context: &mut DrawContext,
-            ) -> (Self::Output, VisualizeResult) {
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
                 let out = self.process(dur.clone());
-                vis::visualize_simple_box(context, &format!("Step"), out)
+                vis::visualize_simple_box(&format!("Step"), out)
             }
         }
 
-        #[derive(Debug)]
-        pub struct Oscillator;
+        impl Parameters for HeavisideStep {
+            fn params(&self) -> &'static [Param] {
+                &[]
+            }
+
+            fn get(&self, _name: &str) -> Option<ParamValue> {
+                None
+            }
+
+            fn set_checked(&mut self, _name: &str, _value: ParamValue) {}
+        }
+
+        #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+        pub enum SweepType {
+            Linear,
+            /// Same time per octave, the usual sweep for impulse response measurements.
+            #[default]
+            Exponential,
+        }
+
+        /// Sine sweep from `start` to `end` Hz over the input duration.
+        ///
+        /// https://www.researchgate.net/publication/2456363_Simultaneous_Measurement_of_Impulse_Response_and_Distortion_With_a_Swept-Sine_Technique
+        #[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct Sweep {
+            #[builder(value = default)]
+            pub t: SweepType,
+            /// Hz
+            #[builder(value = 20f32)]
+            pub start: f32,
+            /// Hz
+            #[builder(value = 20000f32)]
+            pub end: f32,
+        }
+
+        impl Default for Sweep {
+            fn default() -> Self {
+                Self::builder().build()
+            }
+        }
+
+        impl Sweep {
+            const PARAMS: &'static [Param] = &[
+                Param::choice("type", &["Linear", "Exponential"]),
+                Param::float("start", Range::log(1f32, 20000f32), "Hz"),
+                Param::float("end", Range::log(1f32, 20000f32), "Hz"),
+            ];
+
+            /// `start` and `end`, at least 1 Hz for exponential sweeps (like the range of the
+            /// parameters), which can't reach 0 Hz or go below.
+            fn frequencies(&self) -> (f32, f32) {
+                match self.t {
+                    SweepType::Linear => (self.start, self.end),
+                    SweepType::Exponential => (self.start.max(1f32), self.end.max(1f32)),
+                }
+            }
+
+            /// Seconds for the frequency of an exponential sweep to grow by a factor of e.
+            ///
+            /// `None` for linear sweeps and for constant tones (`start == end`), which don't
+            /// grow at all.
+            fn time_constant(&self, duration: f32) -> Option<f32> {
+                let (f1, f2) = self.frequencies();
+                match self.t {
+                    SweepType::Exponential if f1 != f2 => Some(duration / (f2 / f1).ln()),
+                    _ => None,
+                }
+            }
+
+            /// Phase in radians `t` seconds into a sweep lasting `duration` seconds.
+            fn phase(&self, t: f32, duration: f32) -> f32 {
+                let (f1, f2) = self.frequencies();
+                match self.time_constant(duration) {
+                    Some(l) => 2f32 * PI * f1 * l * ((t / l).exp() - 1f32),
+                    None => 2f32 * PI * (f1 * t + (f2 - f1) * t * t / (2f32 * duration)),
+                }
+            }
+
+            /// Filter turning the response to the sweep into an impulse response when
+            /// convolved with it: the sweep reversed in time, with a decay of 6dB per octave
+            /// for exponential sweeps to undo their pink spectrum (none for constant tones).
+            ///
+            /// Scaled so the sweep convolved with its inverse has a gain of 1 in the middle of
+            /// the band, the impulse response it gives is delayed by `len - 1` samples.
+            pub fn inverse_filter(&self, duration: Duration) -> Wave {
+                let sweep = self.clone().process(duration);
+                let len = sweep.len();
+                let l = self.time_constant(duration.as_secs_f32());
+                let mut inverse: Wave = (0..len)
+                    .map(|n| {
+                        let x = sweep[len - 1 - n];
+                        match l {
+                            Some(l) => x * (-(n as f32) / SR as f32 / l).exp(),
+                            None => x,
+                        }
+                    })
+                    .collect();
+                let (f1, f2) = self.frequencies();
+                let center = match self.t {
+                    SweepType::Linear => (f1 + f2) / 2f32,
+                    SweepType::Exponential => (f1 * f2).sqrt(),
+                };
+                let w = 2f32 * PI * center / SR as f32;
+                let dtft = |x: &[f32]| {
+                    x.iter().enumerate().fold(Complex::ZERO, |acc, (n, x)| {
+                        acc + Complex::from_polar(*x, -w * n as f32)
+                    })
+                };
+                let gain = (dtft(&sweep) * dtft(&inverse)).norm();
+                if gain > 0f32 && gain.is_finite() {
+                    for x in inverse.iter_mut() {
+                        *x /= gain;
+                    }
+                }
+                inverse
+            }
+        }
+
+        impl Block<Duration> for Sweep {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, duration: Duration) -> Self::Output {
+                let num_samples = (SR as f32 * duration.as_secs_f32()) as usize;
+                (0..num_samples)
+                    .map(|n| {
+                        self.phase(n as f32 / SR as f32, duration.as_secs_f32())
+                            .sin()
+                    })
+                    .collect()
+            }
+
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
+                let out = self.process(dur);
+                let text = format!("Sweep\n{:?}\n{:.0}-{:.0}Hz", self.t, self.start, self.end);
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for Sweep {
+            fn params(&self) -> &'static [Param] {
+                Self::PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "type" => Some(ParamValue::Enum(match self.t {
+                        SweepType::Linear => 0,
+                        SweepType::Exponential => 1,
+                    })),
+                    "start" => Some(ParamValue::Float(self.start)),
+                    "end" => Some(ParamValue::Float(self.end)),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                match (name, value) {
+                    ("type", ParamValue::Enum(0)) => self.t = SweepType::Linear,
+                    ("type", ParamValue::Enum(_)) => self.t = SweepType::Exponential,
+                    ("start", ParamValue::Float(x)) => self.start = x,
+                    ("end", ParamValue::Float(x)) => self.end = x,
+                    _ => {}
+                }
+            }
+        }
+
+        /// Sum of sines at `freqs` with Schroeder phases, which keep the crest factor low,
+        /// normalized to a peak of 1.
+        #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+        pub struct Multitone {
+            /// Hz
+            pub freqs: Vec<f32>,
+        }
+
+        impl Multitone {
+            /// `count` harmonics of `fundamental`, starting with the fundamental itself.
+            pub fn comb(fundamental: f32, count: usize) -> Self {
+                Self {
+                    freqs: (1..=count).map(|k| fundamental * k as f32).collect(),
+                }
+            }
+        }
+
+        impl Block<Duration> for Multitone {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, duration: Duration) -> Self::Output {
+                let num_samples = (SR as f32 * duration.as_secs_f32()) as usize;
+                let count = self.freqs.len() as f32;
+                let phases: Vec<f32> = (0..self.freqs.len())
+                    .map(|k| -PI * (k * (k + 1)) as f32 / count)
+                    .collect();
+                let mut wave: Wave = (0..num_samples)
+                    .map(|n| {
+                        let t = n as f32 / SR as f32;
+                        self.freqs
+                            .iter()
+                            .zip(phases.iter())
+                            .map(|(f, phase)| (2f32 * PI * f * t + phase).cos())
+                            .sum()
+                    })
+                    .collect();
+                let peak = wave.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+                if peak > 0f32 {
+                    for x in wave.iter_mut() {
+                        *x /= peak;
+                    }
+                }
+                wave
+            }
+
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
+                let out = self.process(dur);
+                let text = format!("Multitone\n{} tones", self.freqs.len());
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for Multitone {
+            fn params(&self) -> &'static [Param] {
+                &[]
+            }
+
+            fn get(&self, _name: &str) -> Option<ParamValue> {
+                None
+            }
+
+            fn set_checked(&mut self, _name: &str, _value: ParamValue) {}
+        }
+
+        /// Maximum length sequence of `±1`, repeated over the input duration. Its circular
+        /// autocorrelation is an impulse, so correlating a response with one period of it gives
+        /// the impulse response.
+        ///
+        /// https://en.wikipedia.org/wiki/Maximum_length_sequence
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct Mls {
+            /// Between 2 and 20, the period is `2^order - 1` samples.
+            pub order: u32,
+        }
+
+        impl Default for Mls {
+            fn default() -> Self {
+                Self { order: 12 }
+            }
+        }
+
+        impl Mls {
+            /// Feedback taps of a primitive polynomial for every order, from
+            /// https://en.wikipedia.org/wiki/Linear-feedback_shift_register#Example_polynomials_for_maximal_LFSRs
+            const TAPS: [&'static [u32]; 19] = [
+                &[2, 1],
+                &[3, 2],
+                &[4, 3],
+                &[5, 3],
+                &[6, 5],
+                &[7, 6],
+                &[8, 6, 5, 4],
+                &[9, 5],
+                &[10, 7],
+                &[11, 9],
+                &[12, 11, 10, 4],
+                &[13, 12, 11, 8],
+                &[14, 13, 12, 2],
+                &[15, 14],
+                &[16, 15, 13, 4],
+                &[17, 14],
+                &[18, 11],
+                &[19, 18, 17, 14],
+                &[20, 17],
+            ];
+
+            pub fn period(&self) -> usize {
+                (1 << self.order()) - 1
+            }
+
+            fn order(&self) -> u32 {
+                self.order.clamp(2, 20)
+            }
+
+            /// One period of the sequence, from a Galois LFSR.
+            pub fn sequence(&self) -> Wave {
+                let mask = Self::TAPS[self.order() as usize - 2]
+                    .iter()
+                    .fold(0u32, |acc, tap| acc | (1 << (tap - 1)));
+                let mut state = 1u32;
+                (0..self.period())
+                    .map(|_| {
+                        let bit = state & 1;
+                        state >>= 1;
+                        if bit == 1 {
+                            state ^= mask;
+                            1f32
+                        } else {
+                            -1f32
+                        }
+                    })
+                    .collect()
+            }
+        }
+
+        impl Block<Duration> for Mls {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, duration: Duration) -> Self::Output {
+                let num_samples = (SR as f32 * duration.as_secs_f32()) as usize;
+                self.sequence()
+                    .into_iter()
+                    .cycle()
+                    .take(num_samples)
+                    .collect()
+            }
+
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
+                let out = self.process(dur);
+                let text = format!("MLS\norder {}", self.order());
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for Mls {
+            fn params(&self) -> &'static [Param] {
+                const PARAMS: &[Param] = &[Param::float("order", Range::linear(2f32, 20f32), "")];
+                PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "order" => Some(ParamValue::Float(self.order as f32)),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                if let ("order", Some(x)) = (name, value.as_float()) {
+                    self.order = x.round() as u32;
+                }
+            }
+        }
+
+        /// Unit impulses `rate` times per second, the first one on the first sample. Silence
+        /// unless the rate is positive.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct ImpulseTrain {
+            /// Hz
+            pub rate: f32,
+        }
+
+        impl Default for ImpulseTrain {
+            fn default() -> Self {
+                Self { rate: 10f32 }
+            }
+        }
+
+        impl Block<Duration> for ImpulseTrain {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, duration: Duration) -> Self::Output {
+                let num_samples = (SR as f32 * duration.as_secs_f32()) as usize;
+                let mut wave = vec![0f32; num_samples];
+                if !(self.rate.is_finite() && self.rate > 0f32) {
+                    return wave;
+                }
+                let period = SR as f32 / self.rate;
+                let mut k = 0;
+                while let Some(x) = wave.get_mut((k as f32 * period).round() as usize) {
+                    *x = 1f32;
+                    k += 1;
+                }
+                wave
+            }
+
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
+                let out = self.process(dur);
+                let text = format!("Impulses\n{:.1}Hz", self.rate);
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for ImpulseTrain {
+            fn params(&self) -> &'static [Param] {
+                const PARAMS: &[Param] =
+                    &[Param::float("rate", Range::log(0.1f32, 20000f32), "Hz")];
+                PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "rate" => Some(ParamValue::Float(self.rate)),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                if let ("rate", Some(x)) = (name, value.as_float()) {
+                    self.rate = x;
+                }
+            }
+        }
+
+        /// Gaussian white noise with a standard deviation of `amplitude`, the same for a given
+        /// `seed`.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct Noise {
+            pub amplitude: f32,
+            pub seed: u32,
+        }
+
+        impl Default for Noise {
+            fn default() -> Self {
+                Self {
+                    amplitude: 0.1f32,
+                    seed: 1,
+                }
+            }
+        }
+
+        impl Noise {
+            /// `n` samples from [`uniform`], made Gaussian with the Box-Muller transform.
+            pub fn samples(&self, n: usize) -> Wave {
+                let mut uniform = uniform(self.seed);
+                (0..n)
+                    .map(|_| {
+                        let (u1, u2) = (uniform(), uniform());
+                        self.amplitude
+                            * (-2f32 * u1.ln()).sqrt()
+                            * (2f32 * std::f32::consts::PI * u2).cos()
+                    })
+                    .collect()
+            }
+        }
+
+        impl Block<Duration> for Noise {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, duration: Duration) -> Self::Output {
+                self.samples((SR as f32 * duration.as_secs_f32()) as usize)
+            }
+
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
+                let out = self.process(dur);
+                let text = format!("Noise\n{:.3}", self.amplitude);
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for Noise {
+            fn params(&self) -> &'static [Param] {
+                const PARAMS: &[Param] =
+                    &[Param::float("amplitude", Range::linear(0f32, 1f32), "")];
+                PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "amplitude" => Some(ParamValue::Float(self.amplitude)),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                if let ("amplitude", Some(x)) = (name, value.as_float()) {
+                    self.amplitude = x;
+                }
+            }
+        }
+
+        /// Parameters changed from the knobs in the diagram override the ones coming from
+        /// the input.
+        #[derive(Debug, Default)]
+        pub struct Oscillator {
+            pub freq: Option<f32>,
+            pub phase: Option<f32>,
+            pub wave: Option<WaveType>,
+
+            /// Controls of the last processed wave, with the overrides applied.
+            controls: Option<OscillatorControls>,
+        }
 
-        #[derive(Clone, Debug)]
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
         pub enum WaveType {
             Sinusoid,
             Square,
@@ -111,51 +664,467 @@ pub mod blocks {
             Sawtooth,
         }
 
-        #[derive(Clone)]
+        impl WaveType {
+            pub const ALL: [WaveType; 4] = [
+                WaveType::Sinusoid,
+                WaveType::Square,
+                WaveType::Triangle,
+                WaveType::Sawtooth,
+            ];
+
+            fn index(&self) -> usize {
+                Self::ALL.iter().position(|x| x == self).unwrap_or_default()
+            }
+
+            fn symbol(&self) -> &'static str {
+                match self {
+                    WaveType::Sinusoid => "~",
+                    WaveType::Square => "n",
+                    WaveType::Triangle => "^",
+                    WaveType::Sawtooth => "/",
+                }
+            }
+
+            /// Value at `phase` radians, same shapes as `Oscillator::process`.
+            fn sample(&self, phase: f32) -> f32 {
+                match self {
+                    WaveType::Sinusoid => phase.sin(),
+                    WaveType::Square => phase.sin().signum(),
+                    WaveType::Triangle => (2.0 / PI) * phase.sin().asin(),
+                    WaveType::Sawtooth => 2.0 * (((phase / (2.0 * PI)) % 1.0) - 0.5),
+                }
+            }
+        }
+
+        #[derive(Clone, Debug, Serialize, Deserialize)]
         pub struct OscillatorControls {
             pub freq: f32,
             pub phase: f32,
+            #[serde(with = "crate::patch::millis")]
             pub duration: std::time::Duration,
             pub wave: WaveType,
         }
 
-        impl Block<OscillatorControls> for Oscillator {
-            type Output = Wave;
+        impl Oscillator {
+            const FREQ: Range = Range::log(1f32, 20000f32);
+            const PHASE: Range = Range::linear(0f32, 2f32 * PI);
+            const PARAMS: &'static [Param] = &[
+                Param::float("freq", Self::FREQ, "Hz"),
+                Param::float("phase", Self::PHASE, "rad"),
+                Param::choice("wave", &["Sinusoid", "Square", "Triangle", "Sawtooth"]),
+            ];
+
+            fn widgets() -> (Knob, Knob, Toggle) {
+                (
+                    Knob::new(widgets::cell(0, 0, 3), Self::FREQ),
+                    Knob::new(widgets::cell(0, 1, 3), Self::PHASE),
+                    Toggle::new(widgets::cell(0, 2, 3), WaveType::ALL.len()),
+                )
+            }
+
+            fn resolve(&mut self, mut controls: OscillatorControls) -> OscillatorControls {
+                if let Some(x) = self.freq {
+                    controls.freq = x;
+                }
+                if let Some(x) = self.phase {
+                    controls.phase = x;
+                }
+                if let Some(x) = &self.wave {
+                    controls.wave = x.clone();
+                }
+                self.controls = Some(controls.clone());
+                controls
+            }
+        }
+
+        impl Block<OscillatorControls> for Oscillator {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, controls: OscillatorControls) -> Self::Output {
+                let controls = self.resolve(controls);
+                match controls.wave {
+                    WaveType::Sinusoid => signals::create_periodic_wave(controls.duration, |n| {
+                        (2.0 * PI * controls.freq * (n as f32) + controls.phase).sin()
+                    }),
+                    WaveType::Square => signals::create_periodic_wave(controls.duration, |n| {
+                        (2.0 * PI * controls.freq * (n as f32) + controls.phase)
+                            .sin()
+                            .signum()
+                    }),
+                    WaveType::Triangle => signals::create_periodic_wave(controls.duration, |n| {
+                        (2.0 / PI)
+                            * (2.0 * PI * controls.freq * (n as f32) + controls.phase)
+                                .sin()
+                                .asin()
+                    }),
+                    WaveType::Sawtooth => signals::create_periodic_wave(controls.duration, |n| {
+                        let phase_offset = controls.phase / (2.0 * PI);
+                        2.0 * (((n * controls.freq + phase_offset) % 1.0) - 0.5)
+                    }),
+                }
+            }
+
+            fn process_and_visualize(
+                &mut self,
+                controls: OscillatorControls,
+            ) -> (Self::Output, VisualizeResult) {
+                let out = self.process(controls);
+                self.visualize(out)
+            }
+
+            fn on_mouse(
+                &mut self,
+                pos: Vector2,
+                event: MouseEvent,
+                context: &mut ControlContext,
+            ) -> ControlResult {
+                let Some(controls) = &self.controls else {
+                    return ControlResult::Passthrough;
+                };
+                let (freq_knob, phase_knob, wave_toggle) = Self::widgets();
+                let (mut freq, mut phase) = (controls.freq, controls.phase);
+                let mut wave = controls.wave.index();
+                let changed = if freq_knob.update(&mut freq, pos, event) {
+                    self.freq = Some(freq);
+                    true
+                } else if phase_knob.update(&mut phase, pos, event) {
+                    self.phase = Some(phase);
+                    true
+                } else if wave_toggle.update(&mut wave, pos, event) {
+                    self.wave = Some(WaveType::ALL[wave].clone());
+                    true
+                } else {
+                    false
+                };
+                control::handled(context, changed)
+            }
+        }
+
+        impl Parameters for Oscillator {
+            fn params(&self) -> &'static [Param] {
+                Self::PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                let controls = self.controls.as_ref();
+                match name {
+                    "freq" => Some(ParamValue::Float(self.freq.or(controls.map(|x| x.freq))?)),
+                    "phase" => Some(ParamValue::Float(self.phase.or(controls.map(|x| x.phase))?)),
+                    "wave" => Some(ParamValue::Enum(
+                        self.wave.as_ref().or(controls.map(|x| &x.wave))?.index(),
+                    )),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                match (name, value) {
+                    ("freq", ParamValue::Float(x)) => self.freq = Some(x),
+                    ("phase", ParamValue::Float(x)) => self.phase = Some(x),
+                    ("wave", ParamValue::Enum(x)) => self.wave = Some(WaveType::ALL[x].clone()),
+                    _ => {}
+                }
+            }
+        }
+
+        /// Integrates the frequency so sweeps don't jump in phase.
+        impl Automatable<OscillatorControls> for Oscillator {
+            fn num_samples(&self, controls: &OscillatorControls) -> usize {
+                (SR as f32 * controls.duration.as_secs_f32()) as usize
+            }
+
+            fn process_automated(
+                &mut self,
+                controls: OscillatorControls,
+                schedule: &Schedule,
+            ) -> Self::Output {
+                let n_samples = self.num_samples(&controls);
+                let controls = self.resolve(controls);
+                let mut phase = 0f32;
+                (0..n_samples)
+                    .map(|n| {
+                        let freq = schedule.value("freq", n).unwrap_or(controls.freq);
+                        let offset = schedule.value("phase", n).unwrap_or(controls.phase);
+                        let x = controls.wave.sample(phase + offset);
+                        phase = (phase + 2.0 * PI * freq / SR as f32) % (2.0 * PI);
+                        x
+                    })
+                    .collect()
+            }
+
+            fn visualize(&mut self, out: Self::Output) -> (Self::Output, VisualizeResult) {
+                // set by `resolve` while processing
+                let Some(controls) = self.controls.clone() else {
+                    return vis::visualize_simple_box("Oscillator", out);
+                };
+                let text = format!("{:?}\n{:.1}Hz", controls.wave, controls.freq);
+                widgets::visualize_with_widgets(&text, out, 1, move |d| {
+                    let (freq, phase, wave) = Self::widgets();
+                    freq.draw(d, controls.freq);
+                    phase.draw(d, controls.phase);
+                    wave.draw(d, controls.wave.symbol());
+                })
+            }
+        }
+
+        /// Plucked string with the extended Karplus-Strong algorithm: a burst of noise going
+        /// round a delay line one period long through a lowpass filter.
+        ///
+        /// https://www.jstor.org/stable/3680063 (Jaffe and Smith, Extensions of the
+        /// Karplus-Strong Plucked-String Algorithm)
+        #[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct KarplusStrong {
+            /// Hz
+            #[builder(value = 110f32)]
+            pub freq: f32,
+            /// Fraction of the level lost every period, shortens the decay of all the
+            /// harmonics.
+            #[builder(value = 0.002f32)]
+            pub damping: f32,
+            /// Weight of the previous sample in the lowpass of the loop, 0.5 damps the high
+            /// harmonics the most and values towards 0 or 1 make them ring longer.
+            #[builder(value = 0.5f32)]
+            pub stretch: f32,
+            /// Between 0 (the bridge) and 0.5 (the middle of the string), removes the
+            /// harmonics with a node there.
+            #[builder(value = 0.13f32)]
+            pub pick_position: f32,
+            #[builder(value = 1)]
+            pub seed: u32,
+        }
+
+        impl Default for KarplusStrong {
+            fn default() -> Self {
+                Self::builder().build()
+            }
+        }
+
+        impl KarplusStrong {
+            const PARAMS: &'static [Param] = &[
+                Param::float("freq", Range::log(20f32, 5000f32), "Hz"),
+                Param::float("damping", Range::log(0.0001f32, 0.5f32), ""),
+                Param::float("stretch", Range::linear(0.01f32, 0.99f32), ""),
+                Param::float("pick_position", Range::linear(0f32, 0.5f32), ""),
+            ];
+
+            pub fn pluck(&self, n_samples: usize) -> Wave {
+                let stretch = self.stretch.clamp(0.01f32, 0.99f32);
+                // the lowpass and the tuning allpass make up the period with the delay line,
+                // the allpass delaying between 0.1 and 1.1 samples to keep its coefficient
+                // away from -1
+                let period = SR as f32 / self.freq.max(1f32);
+                let omega = 2f32 * PI / period;
+                let lowpass_delay =
+                    (stretch * omega.sin()).atan2(1f32 - stretch + stretch * omega.cos()) / omega;
+                let len = (period - lowpass_delay - 0.1f32).floor().max(1f32) as usize;
+                let delay = period - lowpass_delay - len as f32;
+                let coefficient =
+                    ((1f32 - delay) * omega / 2f32).sin() / ((1f32 + delay) * omega / 2f32).sin();
+
+                let noise = Noise {
+                    amplitude: 1f32,
+                    seed: self.seed,
+                }
+                .samples(len);
+                let pick = (self.pick_position.clamp(0f32, 0.5f32) * len as f32).round() as usize;
+                let picked: Wave = (0..len)
+                    .map(|i| {
+                        let comb = i.checked_sub(pick).filter(|_| pick > 0);
+                        noise[i] - comb.map_or(0f32, |j| noise[j])
+                    })
+                    .collect();
+                // without DC, which would go round the loop forever
+                let mean = picked.iter().sum::<f32>() / len as f32;
+                let mut excitation: Wave = picked.iter().map(|x| x - mean).collect();
+                excitation.resize(n_samples, 0f32);
+
+                // the string: both filters in a loop through the delay line
+                let gain = 1f32 - self.damping.clamp(0f32, 1f32);
+                let lowpass = TransferFunction {
+                    b: vec![(1f32 - stretch) * gain, stretch * gain],
+                    a: vec![1f32],
+                };
+                let allpass = TransferFunction {
+                    b: vec![coefficient, 1f32],
+                    a: vec![1f32, coefficient],
+                };
+                let mut string = lowpass
+                    .cascade(&allpass)
+                    .feedback(len)
+                    .expect("the delay line is at least a sample long");
+                string.process(excitation)
+            }
+        }
+
+        impl Block<Duration> for KarplusStrong {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, duration: Duration) -> Self::Output {
+                self.pluck((SR as f32 * duration.as_secs_f32()) as usize)
+            }
+
+            fn process_and_visualize(&mut self, dur: Duration) -> (Self::Output, VisualizeResult) {
+                let out = self.process(dur);
+                let text = format!("String\n{:.1}Hz", self.freq);
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for KarplusStrong {
+            fn params(&self) -> &'static [Param] {
+                Self::PARAMS
+            }
+
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "freq" => Some(ParamValue::Float(self.freq)),
+                    "damping" => Some(ParamValue::Float(self.damping)),
+                    "stretch" => Some(ParamValue::Float(self.stretch)),
+                    "pick_position" => Some(ParamValue::Float(self.pick_position)),
+                    _ => None,
+                }
+            }
+
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                match (name, value.as_float()) {
+                    ("freq", Some(x)) => self.freq = x,
+                    ("damping", Some(x)) => self.damping = x,
+                    ("stretch", Some(x)) => self.stretch = x,
+                    ("pick_position", Some(x)) => self.pick_position = x,
+                    _ => {}
+                }
+            }
+        }
+
+        /// A partial of a [`ModalBank`].
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct Mode {
+            /// Of the frequency of the bank.
+            pub ratio: f32,
+            pub gain: f32,
+            /// Seconds to decay by 60 dB.
+            pub decay: f32,
+        }
+
+        /// Modal synthesis: a two-pole resonator for every mode of an object, all driven by
+        /// the input, e.g. a `KroneckerDelta` to strike it or `Noise` to rub it.
+        #[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct ModalBank {
+            /// Hz
+            #[builder(value = 440f32)]
+            pub freq: f32,
+            /// Scales the decay of every mode.
+            #[builder(value = 1f32)]
+            pub decay: f32,
+            #[builder(value = default)]
+            pub modes: Vec<Mode>,
+        }
+
+        impl Default for ModalBank {
+            fn default() -> Self {
+                Self::builder().modes(Self::bell()).build()
+            }
+        }
+
+        impl ModalBank {
+            const PARAMS: &'static [Param] = &[
+                Param::float("freq", Range::log(20f32, 5000f32), "Hz"),
+                Param::float("decay", Range::log(0.01f32, 10f32), ""),
+            ];
+
+            /// Partials of Risset's bell, its hum an octave and a bit under `freq`.
+            pub fn bell() -> Vec<Mode> {
+                [
+                    (0.56f32, 1f32, 4f32),
+                    (0.92f32, 0.67f32, 3.6f32),
+                    (1.19f32, 1f32, 2.6f32),
+                    (1.7f32, 1.8f32, 2.2f32),
+                    (2f32, 2.67f32, 1.3f32),
+                    (2.74f32, 1.67f32, 1.4f32),
+                    (3f32, 1.46f32, 1f32),
+                    (3.76f32, 1.33f32, 0.8f32),
+                    (4.07f32, 1.33f32, 0.6f32),
+                ]
+                .into_iter()
+                .map(|(ratio, gain, decay)| Mode { ratio, gain, decay })
+                .collect()
+            }
+
+            pub fn resonate(&self, excitation: &[f32]) -> Wave {
+                let mut out = vec![0f32; excitation.len()];
+                for mode in &self.modes {
+                    let freq = self.freq * mode.ratio;
+                    if freq <= 0f32 || freq >= SR as f32 / 2f32 {
+                        continue;
+                    }
+                    // poles at `r e^{±jθ}`, the impulse response `gain r^n sin((n + 1) θ)`
+                    let theta = 2f32 * PI * freq / SR as f32;
+                    let t60 = (mode.decay * self.decay).max(1e-4);
+                    let r = 10f32.powf(-3f32 / (t60 * SR as f32));
+                    let mut resonator = TransferFunction {
+                        b: vec![mode.gain * theta.sin()],
+                        a: vec![1f32, -2f32 * r * theta.cos(), r * r],
+                    };
+                    for (x, y) in resonator
+                        .process(excitation.to_vec())
+                        .iter()
+                        .zip(out.iter_mut())
+                    {
+                        *y += x;
+                    }
+                }
+                let total: f32 = self.modes.iter().map(|x| x.gain.abs()).sum();
+                out.iter().map(|x| x / total.max(1e-6)).collect()
+            }
+        }
+
+        impl Block<Wave> for ModalBank {
+            type Output = Wave;
+
+            fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+                Some(self)
+            }
+
+            fn process(&mut self, input: Wave) -> Self::Output {
+                self.resonate(&input)
+            }
+
+            fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+                let out = self.process(input);
+                let text = format!("Modes\n{}\n{:.1}Hz", self.modes.len(), self.freq);
+                vis::visualize_simple_box(&text, out)
+            }
+        }
+
+        impl Parameters for ModalBank {
+            fn params(&self) -> &'static [Param] {
+                Self::PARAMS
+            }
 
-            fn process(&mut self, controls: OscillatorControls) -> Self::Output {
-                match controls.wave {
-                    WaveType::Sinusoid => signals::create_periodic_wave(controls.duration, |n| {
-                        (2.0 * PI * controls.freq * (n as f32) + controls.phase).sin()
-                    }),
-                    WaveType::Square => signals::create_periodic_wave(controls.duration, |n| {
-                        (2.0 * PI * controls.freq * (n as f32) + controls.phase)
-                            .sin()
-                            .signum()
-                    }),
-                    WaveType::Triangle => signals::create_periodic_wave(controls.duration, |n| {
-                        (2.0 / PI)
-                            * (2.0 * PI * controls.freq * (n as f32) + controls.phase)
-                                .sin()
-                                .asin()
-                    }),
-                    WaveType::Sawtooth => signals::create_periodic_wave(controls.duration, |n| {
-                        let phase_offset = controls.phase / (2.0 * PI);
-                        2.0 * (((n * controls.freq + phase_offset) % 1.0) - 0.5)
-                    }),
+            fn get(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    "freq" => Some(ParamValue::Float(self.freq)),
+                    "decay" => Some(ParamValue::Float(self.decay)),
+                    _ => None,
                 }
             }
 
-            fn process_and_visualize(
-                &mut self,
-                controls: OscillatorControls,
-                context: &mut DrawContext,
-            ) -> (Self::Output, VisualizeResult) {
-                let out = self.process(controls.clone());
-                vis::visualize_simple_box(
-                    context,
-                    &format!("{:?}\n{}Hz", controls.wave, controls.freq),
-                    out,
-                )
+            fn set_checked(&mut self, name: &str, value: ParamValue) {
+                match (name, value.as_float()) {
+                    ("freq", Some(x)) => self.freq = x,
+                    ("decay", Some(x)) => self.decay = x,
+                    _ => {}
+                }
             }
         }
     }
@@ -169,6 +1138,10 @@ pub mod blocks {
     impl<I: DInto<[Wave; N]>, const N: usize> Block<I> for AutoPad<N> {
         type Output = I;
 
+        fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+            Some(self)
+        }
+
         fn process(&mut self, input: I) -> Self::Output {
             let mut waves: [Wave; N] = input.into();
 
@@ -194,13 +1167,34 @@ pub mod blocks {
 
             DInto::from(out)
         }
-        fn process_and_visualize(
-            &mut self,
-            input: I,
-            context: &mut DrawContext,
-        ) -> (Self::Output, VisualizeResult) {
+        fn process_and_visualize(&mut self, input: I) -> (Self::Output, VisualizeResult) {
             let out = self.process(input);
-            vis::visualize_simple_box(context, &format!("Autopad\n{self:?}"), out)
+            vis::visualize_simple_box(&format!("Autopad\n{self:?}"), out)
+        }
+    }
+
+    impl<const N: usize> Parameters for AutoPad<N> {
+        fn params(&self) -> &'static [Param] {
+            const PARAMS: &[Param] = &[Param::choice("side", &["Start", "End"])];
+            PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "side" => Some(ParamValue::Enum(match self {
+                    AutoPad::Start => 0,
+                    AutoPad::End => 1,
+                })),
+                _ => None,
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            match (name, value.as_enum()) {
+                ("side", Some(0)) => *self = AutoPad::Start,
+                ("side", Some(_)) => *self = AutoPad::End,
+                _ => {}
+            }
         }
     }
 
@@ -214,6 +1208,10 @@ pub mod blocks {
     impl<T: DInto<[Wave; N]>, const N: usize> Block<T> for Basic<N> {
         type Output = Wave;
 
+        fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+            Some(self)
+        }
+
         fn process(&mut self, input: T) -> Self::Output {
             let input = input.into();
             let len = input[0].len();
@@ -257,33 +1255,52 @@ pub mod blocks {
             }
         }
 
-        fn process_and_visualize(
-            &mut self,
-            input: T,
-            context: &mut DrawContext,
-        ) -> (Self::Output, VisualizeResult) {
+        fn process_and_visualize(&mut self, input: T) -> (Self::Output, VisualizeResult) {
             let out = self.process(input);
-            let (out, vis_result) = vis::visualize_simple_box(context, &format!("{:?}", self), out);
+            let (out, vis_result) = vis::visualize_simple_box(&format!("{:?}", self), out);
 
             (
                 out,
                 match vis_result {
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    } => VisualizeResult::Block {
-                        texture,
-                        input_connections: input_connections.repeat(N),
-                        output_connections,
-                    },
+                    VisualizeResult::Block(mut layout) => {
+                        layout.input_connections = layout.input_connections.repeat(N);
+                        VisualizeResult::Block(layout)
+                    }
                     x => x,
                 },
             )
         }
     }
 
-    #[derive(Debug, tidy_builder::Builder)]
+    impl<const N: usize> Parameters for Basic<N> {
+        fn params(&self) -> &'static [Param] {
+            const PARAMS: &[Param] = &[Param::choice("op", &["Mix", "Amp", "Diff"])];
+            PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "op" => Some(ParamValue::Enum(match self {
+                    Basic::Mix => 0,
+                    Basic::Amp => 1,
+                    Basic::Diff => 2,
+                })),
+                _ => None,
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            match (name, value.as_enum()) {
+                ("op", Some(0)) => *self = Basic::Mix,
+                ("op", Some(1)) => *self = Basic::Amp,
+                ("op", Some(_)) => *self = Basic::Diff,
+                _ => {}
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+    #[serde(default)]
     pub struct WindowSetting {
         #[builder(value = 1024)]
         frame_size: usize,
@@ -297,13 +1314,100 @@ pub mod blocks {
         }
     }
 
-    #[derive(Debug, Default)]
+    impl WindowSetting {
+        const FRAME_SIZE_RANGE: Range = Range::log(16f32, 8192f32);
+        const HOP_LENGTH_RANGE: Range = Range::log(1f32, 4096f32);
+        const FRAME_SIZE: Param = Param::float("frame_size", Self::FRAME_SIZE_RANGE, "samples");
+        const HOP_LENGTH: Param = Param::float("hop_length", Self::HOP_LENGTH_RANGE, "samples");
+
+        /// At least one sample, whatever a patch file says.
+        pub fn frame_size(&self) -> usize {
+            self.frame_size.max(1)
+        }
+
+        /// At least one sample, a hop of 0 would never move to the next frame.
+        pub fn hop_length(&self) -> usize {
+            self.hop_length.max(1)
+        }
+
+        /// One frame every `hop_length` samples, the last ones zero padded to `frame_size`.
+        pub fn frames<'a>(&self, wave: &'a [f32]) -> impl Iterator<Item = Wave> + 'a {
+            let (frame_size, hop_length) = (self.frame_size(), self.hop_length());
+            (0..wave.len()).step_by(hop_length).map(move |start| {
+                let mut frame = wave[start..(start + frame_size).min(wave.len())].to_vec();
+                frame.resize(frame_size, 0f32);
+                frame
+            })
+        }
+
+        /// One frame centered on every `hop_length`th sample, zero padded past both ends.
+        pub fn centered_frames<'a>(&self, wave: &'a [f32]) -> impl Iterator<Item = Wave> + 'a {
+            let (frame_size, hop_length) = (self.frame_size(), self.hop_length());
+            (0..wave.len()).step_by(hop_length).map(move |center| {
+                (0..frame_size)
+                    .map(|i| {
+                        let j = (center + i).checked_sub(frame_size / 2);
+                        j.and_then(|j| wave.get(j)).copied().unwrap_or_default()
+                    })
+                    .collect()
+            })
+        }
+
+        pub(crate) fn widgets() -> (Slider, Slider) {
+            (
+                Slider::new(widgets::cell(0, 0, 1), Self::FRAME_SIZE_RANGE),
+                Slider::new(widgets::cell(1, 0, 1), Self::HOP_LENGTH_RANGE),
+            )
+        }
+
+        /// Routes a mouse event to the frame size and hop length sliders.
+        pub(crate) fn update(&mut self, pos: Vector2, event: MouseEvent) -> bool {
+            let (frame_size_slider, hop_length_slider) = Self::widgets();
+            let mut frame_size = self.frame_size as f32;
+            let mut hop_length = self.hop_length as f32;
+            if frame_size_slider.update(&mut frame_size, pos, event) {
+                self.set_checked("frame_size", ParamValue::Float(frame_size));
+                true
+            } else if hop_length_slider.update(&mut hop_length, pos, event) {
+                self.set_checked("hop_length", ParamValue::Float(hop_length));
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    impl Parameters for WindowSetting {
+        fn params(&self) -> &'static [Param] {
+            const PARAMS: &[Param] = &[WindowSetting::FRAME_SIZE, WindowSetting::HOP_LENGTH];
+            PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "frame_size" => Some(ParamValue::Float(self.frame_size as f32)),
+                "hop_length" => Some(ParamValue::Float(self.hop_length as f32)),
+                _ => None,
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            match (name, value.as_float()) {
+                ("frame_size", Some(x)) => self.frame_size = (x.round() as usize).max(1),
+                ("hop_length", Some(x)) => self.hop_length = (x.round() as usize).max(1),
+                _ => {}
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
     pub enum EnvelopeType {
         #[default]
         Amp,
     }
 
-    #[derive(Debug, tidy_builder::Builder)]
+    #[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+    #[serde(default)]
     pub struct EnvelopeBlock {
         #[builder(value = default)]
         pub t: EnvelopeType,
@@ -320,40 +1424,145 @@ pub mod blocks {
     impl Block<Wave> for EnvelopeBlock {
         type Output = Wave;
 
+        fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+            Some(self)
+        }
+
         fn process(&mut self, input: Wave) -> Self::Output {
             // shape of output depands on the hop_length
             let mut out =
-                vec![0f32; (input.len() as f32 / self.window.hop_length as f32).ceil() as usize];
+                vec![0f32; (input.len() as f32 / self.window.hop_length() as f32).ceil() as usize];
 
             let mut w_start = 0;
             for slot in out.iter_mut() {
                 let frame =
-                    &input[w_start..(self.window.frame_size + w_start).min(input.len() - 1)];
+                    &input[w_start..(self.window.frame_size() + w_start).min(input.len() - 1)];
                 match self.t {
                     EnvelopeType::Amp => {
                         *slot = frame.iter().copied().reduce(f32::max).unwrap_or_default();
                     }
                 }
-                w_start += self.window.hop_length;
+                w_start += self.window.hop_length();
             }
             out
         }
 
-        fn process_and_visualize(
-            &mut self,
-            input: Wave,
-            context: &mut DrawContext,
-        ) -> (Self::Output, VisualizeResult) {
+        fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
             let out = self.process(input);
-            vis::visualize_simple_box(
-                context,
+            let window = self.window.clone();
+            widgets::visualize_with_widgets(
                 &format!(
                     "Envelope\n{:?}\n{},{}",
                     self.t, self.window.frame_size, self.window.hop_length
                 ),
                 out,
+                2,
+                move |d| {
+                    let (frame_size, hop_length) = WindowSetting::widgets();
+                    frame_size.draw(d, window.frame_size as f32);
+                    hop_length.draw(d, window.hop_length as f32);
+                },
             )
         }
+
+        fn on_mouse(
+            &mut self,
+            pos: Vector2,
+            event: MouseEvent,
+            context: &mut ControlContext,
+        ) -> ControlResult {
+            let changed = self.window.update(pos, event);
+            control::handled(context, changed)
+        }
+    }
+
+    impl Parameters for EnvelopeBlock {
+        fn params(&self) -> &'static [Param] {
+            const PARAMS: &[Param] = &[
+                Param::choice("type", &["Amp"]),
+                WindowSetting::FRAME_SIZE,
+                WindowSetting::HOP_LENGTH,
+            ];
+            PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "type" => Some(ParamValue::Enum(match self.t {
+                    EnvelopeType::Amp => 0,
+                })),
+                _ => self.window.get(name),
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            match name {
+                "type" => self.t = EnvelopeType::Amp,
+                _ => self.window.set_checked(name, value),
+            }
+        }
+    }
+
+    /// Delays the wave by `N` samples while keeping its length; the samples shifted
+    /// past the end are dropped.
+    #[derive(Debug, Default)]
+    pub struct Delay(pub usize);
+
+    impl Block<Wave> for Delay {
+        type Output = Wave;
+
+        fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+            Some(self)
+        }
+
+        fn process(&mut self, input: Wave) -> Self::Output {
+            let len = input.len();
+            let delay = self.0.min(len);
+            let mut wave = vec![0f32; len];
+            wave[delay..].copy_from_slice(&input[..len - delay]);
+            wave
+        }
+
+        fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+            let out = self.process(input);
+            vis::visualize_simple_box(&format!("Delay\nz^-{}", self.0), out)
+        }
+    }
+
+    impl Streaming for Delay {
+        /// The last samples of the previous chunks, still to be output.
+        type State = Wave;
+
+        fn process_chunk(&mut self, chunk: &[f32], state: &mut Self::State) -> Wave {
+            state.resize(self.0, 0f32);
+            state.extend_from_slice(chunk);
+            let rest = state.split_off(chunk.len());
+            std::mem::replace(state, rest)
+        }
+    }
+
+    impl Parameters for Delay {
+        fn params(&self) -> &'static [Param] {
+            const PARAMS: &[Param] = &[Param::float(
+                "delay",
+                Range::linear(0f32, SR as f32),
+                "samples",
+            )];
+            PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "delay" => Some(ParamValue::Float(self.0 as f32)),
+                _ => None,
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            if let ("delay", Some(x)) = (name, value.as_float()) {
+                self.0 = x.round() as usize;
+            }
+        }
     }
 
     #[derive(Debug, Default)]
@@ -362,6 +1571,10 @@ pub mod blocks {
     impl Block<Wave> for ConstMultiplier {
         type Output = Wave;
 
+        fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+            Some(self)
+        }
+
         fn process(&mut self, mut input: Wave) -> Self::Output {
             for sample in input.iter_mut() {
                 *sample *= self.0;
@@ -369,44 +1582,434 @@ pub mod blocks {
             input
         }
 
-        fn process_and_visualize(
-            &mut self,
-            input: Wave,
-            context: &mut DrawContext,
-        ) -> (Self::Output, VisualizeResult) {
+        fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
             let out = self.process(input);
-            let mut tx = context.get_texture(vis::BOX_SIZE as _, vis::BOX_SIZE as _);
-            tx.set_texture_filter(
-                context.thread,
-                raylib::ffi::TextureFilter::TEXTURE_FILTER_ANISOTROPIC_16X,
-            );
-            let mut d = context.rl.begin_drawing(context.thread);
-            let mut d = d.begin_texture_mode(context.thread, &mut tx);
+            self.visualize(out)
+        }
+
+        fn on_mouse(
+            &mut self,
+            pos: Vector2,
+            event: MouseEvent,
+            context: &mut ControlContext,
+        ) -> ControlResult {
+            let changed = ConstMultiplier::knob().update(&mut self.0, pos, event);
+            control::handled(context, changed)
+        }
+    }
+
+    impl Streaming for ConstMultiplier {
+        type State = ();
+
+        fn process_chunk(&mut self, chunk: &[f32], _state: &mut Self::State) -> Wave {
+            chunk.iter().map(|x| x * self.0).collect()
+        }
+    }
+
+    impl ConstMultiplier {
+        const GAIN: Range = Range::linear(-2f32, 2f32);
+        const PARAMS: &'static [Param] = &[Param::float("gain", Self::GAIN, "")];
+
+        fn knob() -> Knob {
+            Knob::new(widgets::cell(0, 0, 1), Self::GAIN)
+        }
+    }
+
+    impl Parameters for ConstMultiplier {
+        fn params(&self) -> &'static [Param] {
+            Self::PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "gain" => Some(ParamValue::Float(self.0)),
+                _ => None,
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            if let ("gain", Some(x)) = (name, value.as_float()) {
+                self.0 = x;
+            }
+        }
+    }
+
+    impl Automatable<Wave> for ConstMultiplier {
+        fn num_samples(&self, input: &Wave) -> usize {
+            input.len()
+        }
+
+        fn process_automated(&mut self, mut input: Wave, schedule: &Schedule) -> Self::Output {
+            for (n, sample) in input.iter_mut().enumerate() {
+                *sample *= schedule.value("gain", n).unwrap_or(self.0);
+            }
+            input
+        }
+
+        fn visualize(&mut self, out: Self::Output) -> (Self::Output, VisualizeResult) {
             let pad = vis::T * 3f32;
 
             let center = ((vis::BOX_SIZE - pad * 2f32) / 2f32) + pad;
-            d.draw_triangle_lines(
-                Vector2::new(0f32 + pad, 0f32 + pad),
-                Vector2::new(0f32 + pad, vis::BOX_SIZE - pad),
-                Vector2::new(vis::BOX_SIZE - pad, center),
-                vis::BORDER_COLOR,
-            );
-            d.draw_text(
-                &format!("* {}", self.0),
-                center as _,
-                (pad as i32) / 2,
-                1,
-                vis::TEXT_COLOR,
-            );
-            drop(d);
+            let gain = self.0;
+            let height = vis::BOX_SIZE + widgets::ROW_HEIGHT;
+            let layout = Layout::new(vis::BOX_SIZE, height, move |d| {
+                d.draw_triangle_lines(
+                    Vector2::new(0f32 + pad, 0f32 + pad),
+                    Vector2::new(0f32 + pad, vis::BOX_SIZE - pad),
+                    Vector2::new(vis::BOX_SIZE - pad, center),
+                    vis::BORDER_COLOR,
+                );
+                d.draw_text(
+                    &format!("* {:.2}", gain),
+                    center as _,
+                    (pad as i32) / 2,
+                    1,
+                    vis::TEXT_COLOR,
+                );
+                vis::draw_border(
+                    d,
+                    Rectangle {
+                        x: 0f32,
+                        y: vis::BOX_SIZE,
+                        width: vis::BOX_SIZE,
+                        height: widgets::ROW_HEIGHT,
+                    },
+                );
+                ConstMultiplier::knob().draw(d, gain);
+            });
+            (
+                out,
+                VisualizeResult::Block(layout.with_ports(
+                    vec![Vector2::new(0f32 + pad, center)],
+                    vec![Vector2::new(vis::BOX_SIZE - pad, center)],
+                )),
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    pub enum BiquadType {
+        #[default]
+        LowPass,
+        HighPass,
+        BandPass,
+        Notch,
+    }
+
+    impl BiquadType {
+        pub const ALL: [BiquadType; 4] = [
+            BiquadType::LowPass,
+            BiquadType::HighPass,
+            BiquadType::BandPass,
+            BiquadType::Notch,
+        ];
+
+        fn symbol(&self) -> &'static str {
+            match self {
+                BiquadType::LowPass => "L",
+                BiquadType::HighPass => "H",
+                BiquadType::BandPass => "B",
+                BiquadType::Notch => "N",
+            }
+        }
+    }
+
+    /// Second order IIR filter, coefficients from the
+    /// [Audio EQ Cookbook](https://www.w3.org/TR/audio-eq-cookbook/).
+    #[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct Biquad {
+        #[builder(value = default)]
+        pub t: BiquadType,
+        /// Hz
+        #[builder(value = 1000f32)]
+        pub cutoff: f32,
+        #[builder(value = 0.707f32)]
+        pub q: f32,
+    }
+
+    impl Default for Biquad {
+        fn default() -> Self {
+            Self::builder().build()
+        }
+    }
+
+    /// Normalized `([b0, b1, b2], [a1, a2])` of a second order section, `a0` is 1.
+    pub type Coefficients = ([f32; 3], [f32; 2]);
+
+    /// Filters with one second order section, in transposed direct form II.
+    pub fn filter_section(input: Wave, coefficients: Coefficients) -> Wave {
+        filter_section_from(&input, coefficients, &mut Default::default())
+    }
+
+    /// [`filter_section`] carrying on from the state `[z1, z2]` the previous samples left.
+    pub fn filter_section_from(
+        input: &[f32],
+        ([b0, b1, b2], [a1, a2]): Coefficients,
+        [z1, z2]: &mut [f32; 2],
+    ) -> Wave {
+        input
+            .iter()
+            .map(|&x| {
+                let y = b0 * x + *z1;
+                *z1 = b1 * x - a1 * y + *z2;
+                *z2 = b2 * x - a2 * y;
+                y
+            })
+            .collect()
+    }
+
+    impl Biquad {
+        pub fn coefficients(&self) -> Coefficients {
+            let cutoff = self.cutoff.clamp(1f32, SR as f32 / 2f32 - 1f32);
+            let w0 = 2f32 * PI * cutoff / SR as f32;
+            let (sin, cos) = w0.sin_cos();
+            let alpha = sin / (2f32 * self.q.max(0.01f32));
+
+            let b = match self.t {
+                BiquadType::LowPass => [(1f32 - cos) / 2f32, 1f32 - cos, (1f32 - cos) / 2f32],
+                BiquadType::HighPass => [(1f32 + cos) / 2f32, -(1f32 + cos), (1f32 + cos) / 2f32],
+                BiquadType::BandPass => [alpha, 0f32, -alpha],
+                BiquadType::Notch => [1f32, -2f32 * cos, 1f32],
+            };
+            let a0 = 1f32 + alpha;
+            (
+                [b[0] / a0, b[1] / a0, b[2] / a0],
+                [-2f32 * cos / a0, (1f32 - alpha) / a0],
+            )
+        }
+
+        const CUTOFF: Range = Range::log(20f32, 20000f32);
+        const Q: Range = Range::log(0.1f32, 20f32);
+        const PARAMS: &'static [Param] = &[
+            Param::choice("type", &["LowPass", "HighPass", "BandPass", "Notch"]),
+            Param::float("cutoff", Self::CUTOFF, "Hz"),
+            Param::float("q", Self::Q, ""),
+        ];
+
+        fn widgets() -> (Knob, Knob, Toggle) {
             (
+                Knob::new(widgets::cell(0, 0, 3), Self::CUTOFF),
+                Knob::new(widgets::cell(0, 1, 3), Self::Q),
+                Toggle::new(widgets::cell(0, 2, 3), BiquadType::ALL.len()),
+            )
+        }
+    }
+
+    impl Parameters for Biquad {
+        fn params(&self) -> &'static [Param] {
+            Self::PARAMS
+        }
+
+        fn get(&self, name: &str) -> Option<ParamValue> {
+            match name {
+                "type" => Some(ParamValue::Enum(
+                    BiquadType::ALL
+                        .iter()
+                        .position(|x| *x == self.t)
+                        .unwrap_or_default(),
+                )),
+                "cutoff" => Some(ParamValue::Float(self.cutoff)),
+                "q" => Some(ParamValue::Float(self.q)),
+                _ => None,
+            }
+        }
+
+        fn set_checked(&mut self, name: &str, value: ParamValue) {
+            match (name, value) {
+                ("type", ParamValue::Enum(x)) => self.t = BiquadType::ALL[x].clone(),
+                ("cutoff", ParamValue::Float(x)) => self.cutoff = x,
+                ("q", ParamValue::Float(x)) => self.q = x,
+                _ => {}
+            }
+        }
+    }
+
+    /// Recomputes the coefficients whenever the cutoff or q change, keeping the state.
+    impl Automatable<Wave> for Biquad {
+        fn num_samples(&self, input: &Wave) -> usize {
+            input.len()
+        }
+
+        fn process_automated(&mut self, input: Wave, schedule: &Schedule) -> Self::Output {
+            let mut filter = self.clone();
+            let ([mut b0, mut b1, mut b2], [mut a1, mut a2]) = filter.coefficients();
+
+            let (mut z1, mut z2) = (0f32, 0f32);
+            input
+                .into_iter()
+                .enumerate()
+                .map(|(n, x)| {
+                    let cutoff = schedule.value("cutoff", n).unwrap_or(self.cutoff);
+                    let q = schedule.value("q", n).unwrap_or(self.q);
+                    if cutoff != filter.cutoff || q != filter.q {
+                        (filter.cutoff, filter.q) = (cutoff, q);
+                        ([b0, b1, b2], [a1, a2]) = filter.coefficients();
+                    }
+                    let y = b0 * x + z1;
+                    z1 = b1 * x - a1 * y + z2;
+                    z2 = b2 * x - a2 * y;
+                    y
+                })
+                .collect()
+        }
+
+        fn visualize(&mut self, out: Self::Output) -> (Self::Output, VisualizeResult) {
+            let filter = self.clone();
+            widgets::visualize_with_widgets(
+                &format!("{:?}\n{:.0}Hz\nQ={:.2}", self.t, self.cutoff, self.q),
                 out,
-                VisualizeResult::Block {
-                    texture: tx,
-                    input_connections: vec![Vector2::new(0f32 + pad, center)],
-                    output_connections: vec![Vector2::new(vis::BOX_SIZE - pad, center)],
+                1,
+                move |d| {
+                    let (cutoff, q, t) = Self::widgets();
+                    cutoff.draw(d, filter.cutoff);
+                    q.draw(d, filter.q);
+                    t.draw(d, filter.t.symbol());
                 },
             )
         }
     }
+
+    impl Streaming for Biquad {
+        type State = [f32; 2];
+
+        fn process_chunk(&mut self, chunk: &[f32], state: &mut Self::State) -> Wave {
+            filter_section_from(chunk, self.coefficients(), state)
+        }
+    }
+
+    impl Block<Wave> for Biquad {
+        type Output = Wave;
+
+        fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+            Some(self)
+        }
+
+        fn process(&mut self, input: Wave) -> Self::Output {
+            filter_section(input, self.coefficients())
+        }
+
+        fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+            let out = self.process(input);
+            self.visualize(out)
+        }
+
+        fn on_mouse(
+            &mut self,
+            pos: Vector2,
+            event: MouseEvent,
+            context: &mut ControlContext,
+        ) -> ControlResult {
+            let (cutoff, q, t) = Self::widgets();
+            let mut index = BiquadType::ALL
+                .iter()
+                .position(|x| *x == self.t)
+                .unwrap_or_default();
+            let changed = cutoff.update(&mut self.cutoff, pos, event)
+                || q.update(&mut self.q, pos, event)
+                || t.update(&mut index, pos, event);
+            self.t = BiquadType::ALL[index].clone();
+            control::handled(context, changed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::time::Duration;
+
+    use super::blocks::synths::{ImpulseTrain, KarplusStrong, Sweep, SweepType};
+    use super::blocks::WindowSetting;
+    use super::SR;
+    use crate::graph::Block;
+    use crate::spectrum::Complex;
+
+    #[test]
+    fn exponential_sweep_between_equal_frequencies_is_a_tone() {
+        let duration = Duration::from_millis(100);
+        let mut sweep = Sweep::builder().start(440f32).end(440f32).build();
+        let tone = sweep.process(duration);
+        for (n, x) in tone.iter().enumerate() {
+            let expected = (2f32 * PI * 440f32 * n as f32 / SR as f32).sin();
+            assert!((x - expected).abs() < 1e-3, "{n}: {x} != {expected}");
+        }
+        let inverse = sweep.inverse_filter(duration);
+        assert!(inverse.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn impulse_trains_without_a_positive_rate_are_silent() {
+        let duration = Duration::from_millis(100);
+        for rate in [0f32, -10f32, f32::NAN, f32::INFINITY] {
+            let out = ImpulseTrain { rate }.process(duration);
+            assert_eq!(out.len(), SR / 10);
+            assert!(out.iter().all(|x| *x == 0f32), "{rate}");
+        }
+        let out = ImpulseTrain { rate: 100f32 }.process(duration);
+        assert_eq!(out.iter().sum::<f32>(), 10f32);
+    }
+
+    #[test]
+    fn exponential_sweeps_from_or_to_0hz_are_finite() {
+        let duration = Duration::from_millis(100);
+        for (start, end) in [(0f32, 1000f32), (-20f32, 1000f32), (1000f32, 0f32)] {
+            let mut sweep = Sweep::builder()
+                .t(SweepType::Exponential)
+                .start(start)
+                .end(end)
+                .build();
+            assert!(sweep.process(duration).iter().all(|x| x.is_finite()));
+            assert!(sweep.inverse_filter(duration).iter().all(|x| x.is_finite()));
+        }
+    }
+
+    /// Hz, the loudest frequency within a semitone of `guess`, from the Hann windowed DTFT.
+    fn peak_frequency(wave: &[f32], guess: f32) -> f32 {
+        let magnitude = |freq: f32| {
+            let omega = 2f32 * PI * freq / SR as f32;
+            let len = wave.len() as f32;
+            let sum = wave.iter().enumerate().fold(Complex::ZERO, |sum, (n, x)| {
+                let w = 0.5f32 - 0.5f32 * (2f32 * PI * n as f32 / len).cos();
+                sum + Complex::from_polar(x * w, -omega * n as f32)
+            });
+            sum.norm()
+        };
+        // golden section search
+        let ratio = (5f32.sqrt() - 1f32) / 2f32;
+        let semitone = 2f32.powf(1f32 / 12f32);
+        let (mut low, mut high) = (guess / semitone, guess * semitone);
+        for _ in 0..40 {
+            let (a, b) = (high - ratio * (high - low), low + ratio * (high - low));
+            if magnitude(a) > magnitude(b) {
+                high = b;
+            } else {
+                low = a;
+            }
+        }
+        (low + high) / 2f32
+    }
+
+    #[test]
+    fn plucked_strings_are_in_tune() {
+        for freq in [55f32, 110f32, 440f32, 1500f32] {
+            for stretch in [0.1f32, 0.5f32, 0.9f32] {
+                let string = KarplusStrong::builder().freq(freq).stretch(stretch).build();
+                let measured = peak_frequency(&string.pluck(SR), freq);
+                let cents = 1200f32 * (measured / freq).log2();
+                assert!(
+                    cents.abs() < 1f32,
+                    "{freq}Hz, stretch {stretch}: {cents} cents"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn windows_from_patches_never_hop_by_0() {
+        let window: WindowSetting = ron::from_str("(frame_size: 0, hop_length: 0)").unwrap();
+        assert_eq!((window.frame_size(), window.hop_length()), (1, 1));
+        assert_eq!(window.frames(&[1f32; 5]).count(), 5);
+        assert_eq!(window.centered_frames(&[1f32; 5]).count(), 5);
+    }
 }
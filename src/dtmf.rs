@@ -0,0 +1,252 @@
+//! Dual-tone multi-frequency signalling, the tones of telephone keypads: every key is the sum
+//! of a row and a column frequency.
+use std::time::Duration;
+
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::synths::{Oscillator, OscillatorControls, WaveType};
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::{Wave, SR};
+use crate::goertzel::goertzel;
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::vis::{self, VisualizeResult};
+use crate::widgets::{self, Range};
+
+/// Hz
+pub const ROWS: [f32; 4] = [697f32, 770f32, 852f32, 941f32];
+/// Hz
+pub const COLUMNS: [f32; 4] = [1209f32, 1336f32, 1477f32, 1633f32];
+pub const KEYS: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+/// Row and column frequencies of a key.
+pub fn tones(key: char) -> Option<(f32, f32)> {
+    let key = key.to_ascii_uppercase();
+    KEYS.iter().enumerate().find_map(|(row, keys)| {
+        let column = keys.iter().position(|x| *x == key)?;
+        Some((ROWS[row], COLUMNS[column]))
+    })
+}
+
+/// Plays every key of `digits` for the input duration followed by as much silence, other
+/// characters are pauses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtmfEncoder {
+    pub digits: String,
+}
+
+impl Default for DtmfEncoder {
+    fn default() -> Self {
+        Self {
+            digits: "0123456789*#".to_string(),
+        }
+    }
+}
+
+impl Block<Duration> for DtmfEncoder {
+    type Output = Wave;
+
+    fn process(&mut self, duration: Duration) -> Self::Output {
+        let mut oscillator = Oscillator::default();
+        let mut tone = |freq: f32| {
+            oscillator.process(OscillatorControls {
+                freq,
+                phase: 0f32,
+                duration,
+                wave: WaveType::Sinusoid,
+            })
+        };
+
+        let silence = vec![0f32; (SR as f32 * duration.as_secs_f32()) as usize];
+        let mut out = vec![];
+        for key in self.digits.chars() {
+            match tones(key) {
+                Some((row, column)) => out.extend(
+                    tone(row)
+                        .into_iter()
+                        .zip(tone(column))
+                        .map(|(a, b)| 0.5f32 * (a + b)),
+                ),
+                None => out.extend(silence.iter()),
+            }
+            out.extend(silence.iter());
+        }
+        out
+    }
+
+    fn process_and_visualize(&mut self, duration: Duration) -> (Self::Output, VisualizeResult) {
+        let out = self.process(duration);
+        vis::visualize_simple_box(&format!("DTMF\n{}", lines(&self.digits)), out)
+    }
+}
+
+/// Splits long strings in lines that fit in a box.
+fn lines(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .chunks(8)
+        .map(|x| x.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Finds the key pressed in every frame with the Goertzel algorithm, outputs one value per
+/// frame: the index of the key in [`KEYS`] plus one, or 0 when no key is pressed.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DtmfDecoder {
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    /// Smallest fraction of the power of a frame in the two tones.
+    #[builder(value = 0.25f32)]
+    pub threshold: f32,
+}
+
+impl Default for DtmfDecoder {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// How many times stronger than the other rows (or columns) the tone must be.
+const DOMINANCE: f32 = 4f32;
+/// Largest power ratio between the row and column tones, about 8 dB.
+const TWIST: f32 = 6.3f32;
+
+impl DtmfDecoder {
+    const PARAMS: &'static [Param] = &[
+        Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+        Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+        Param::float("threshold", Range::linear(0f32, 1f32), ""),
+    ];
+
+    /// Key of a frame, if its power is mostly in one row and one column tone.
+    fn key(&self, frame: &[f32]) -> Option<char> {
+        let strongest = |freqs: &[f32; 4]| {
+            let mut powers: Vec<(usize, f32)> = freqs
+                .iter()
+                .map(|f| goertzel(frame, *f))
+                .enumerate()
+                .collect();
+            powers.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let (best, power) = powers[0];
+            (power > powers[1].1 * DOMINANCE).then_some((best, power))
+        };
+        let (row, row_power) = strongest(&ROWS)?;
+        let (column, column_power) = strongest(&COLUMNS)?;
+
+        let power = frame.iter().map(|x| x * x).sum::<f32>() / frame.len() as f32;
+        let twist = row_power / column_power;
+        (row_power + column_power >= self.threshold * power
+            && (1f32 / TWIST..=TWIST).contains(&twist))
+        .then_some(KEYS[row][column])
+    }
+
+    /// The key of every frame.
+    pub fn keys(&self, wave: &[f32]) -> Vec<Option<char>> {
+        self.window.frames(wave).map(|x| self.key(&x)).collect()
+    }
+
+    /// The keys held for at least two frames in a row.
+    pub fn decode(&self, wave: &[f32]) -> String {
+        let keys = self.keys(wave);
+        let mut digits = String::new();
+        let mut previous = None;
+        for pair in keys.windows(2) {
+            if pair[0].is_some() && pair[0] == pair[1] && pair[0] != previous {
+                digits.extend(pair[0]);
+            }
+            previous = pair[0];
+        }
+        digits
+    }
+}
+
+impl Block<Wave> for DtmfDecoder {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.keys(&input)
+            .into_iter()
+            .map(|key| {
+                key.and_then(|key| KEYS.iter().flatten().position(|x| *x == key))
+                    .map(|i| (i + 1) as f32)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let digits = self.decode(&input);
+        let out = self.process(input);
+        let window = self.window.clone();
+        widgets::visualize_with_widgets(&format!("Decoded\n{}", lines(&digits)), out, 2, move |d| {
+            let (frame_size, hop_length) = WindowSetting::widgets();
+            frame_size.draw(d, window.frame_size() as f32);
+            hop_length.draw(d, window.hop_length() as f32);
+        })
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for DtmfDecoder {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "threshold" => Some(ParamValue::Float(self.threshold)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("threshold", ParamValue::Float(x)) => self.threshold = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::synths::Noise;
+
+    #[test]
+    fn keys_played_in_noise_are_decoded() {
+        let digits = "0123456789*#ABCD";
+        let tones = DtmfEncoder {
+            digits: digits.to_string(),
+        }
+        .process(Duration::from_millis(60));
+        let noise = Noise {
+            amplitude: 0.2f32,
+            seed: 1,
+        }
+        .samples(tones.len());
+        let wave: Wave = tones.iter().zip(noise).map(|(x, n)| x + n).collect();
+        assert_eq!(DtmfDecoder::default().decode(&wave), digits);
+    }
+}
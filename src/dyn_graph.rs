@@ -0,0 +1,936 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use raylib::math::{Rectangle, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::control;
+use crate::dsp::blocks::synths::OscillatorControls;
+use crate::dsp::Wave;
+use crate::graph::Block;
+use crate::layout::Layout;
+use crate::params::Parameters;
+use crate::vis::{self, VisualizeResult};
+
+/// A value travelling on an edge of a [`Graph`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Value {
+    Wave(Wave),
+    Duration(#[serde(with = "crate::patch::millis")] Duration),
+    Oscillator(OscillatorControls),
+}
+
+impl Value {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Wave(_) => "wave",
+            Value::Duration(_) => "duration",
+            Value::Oscillator(_) => "oscillator controls",
+        }
+    }
+
+    pub fn as_wave(&self) -> Option<&Wave> {
+        match self {
+            Value::Wave(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// Types that can be split into (and rebuilt from) the values of a node's ports.
+///
+/// Nested tuples are flattened, so `((Wave, Wave), Wave)` has three ports.
+pub trait Ports: Sized {
+    fn arity() -> usize;
+    /// What every port carries, as in [`Value::kind`].
+    fn kinds(out: &mut Vec<&'static str>);
+    fn into_values(self, out: &mut Vec<Value>);
+    fn from_values(values: &mut std::vec::IntoIter<Value>) -> anyhow::Result<Self>;
+}
+
+macro_rules! impl_port {
+    ($t:ty, $variant:ident, $kind:literal) => {
+        impl Ports for $t {
+            fn arity() -> usize {
+                1
+            }
+
+            fn kinds(out: &mut Vec<&'static str>) {
+                out.push($kind);
+            }
+
+            fn into_values(self, out: &mut Vec<Value>) {
+                out.push(Value::$variant(self));
+            }
+
+            fn from_values(values: &mut std::vec::IntoIter<Value>) -> anyhow::Result<Self> {
+                match values.next() {
+                    Some(Value::$variant(x)) => Ok(x),
+                    Some(x) => bail!("expected {}, got {}", $kind, x.kind()),
+                    None => bail!("missing value"),
+                }
+            }
+        }
+
+        impl From<$t> for Value {
+            fn from(value: $t) -> Self {
+                Value::$variant(value)
+            }
+        }
+    };
+}
+
+impl_port!(Wave, Wave, "wave");
+impl_port!(Duration, Duration, "duration");
+impl_port!(OscillatorControls, Oscillator, "oscillator controls");
+
+impl Ports for () {
+    fn arity() -> usize {
+        0
+    }
+
+    fn kinds(_out: &mut Vec<&'static str>) {}
+
+    fn into_values(self, _out: &mut Vec<Value>) {}
+
+    fn from_values(_values: &mut std::vec::IntoIter<Value>) -> anyhow::Result<Self> {
+        Ok(())
+    }
+}
+
+impl<A: Ports, B: Ports> Ports for (A, B) {
+    fn arity() -> usize {
+        A::arity() + B::arity()
+    }
+
+    fn kinds(out: &mut Vec<&'static str>) {
+        A::kinds(out);
+        B::kinds(out);
+    }
+
+    fn into_values(self, out: &mut Vec<Value>) {
+        self.0.into_values(out);
+        self.1.into_values(out);
+    }
+
+    fn from_values(values: &mut std::vec::IntoIter<Value>) -> anyhow::Result<Self> {
+        let a = A::from_values(values)?;
+        let b = B::from_values(values)?;
+        Ok((a, b))
+    }
+}
+
+/// Object safe version of [`Block`] working on port values.
+pub trait DynBlock: Debug {
+    /// What every input port takes, as in [`Value::kind`].
+    fn input_kinds(&self) -> Vec<&'static str>;
+    fn output_kinds(&self) -> Vec<&'static str>;
+
+    fn input_arity(&self) -> usize {
+        self.input_kinds().len()
+    }
+
+    fn output_arity(&self) -> usize {
+        self.output_kinds().len()
+    }
+
+    fn process(&mut self, inputs: Vec<Value>) -> anyhow::Result<Vec<Value>>;
+
+    fn process_and_visualize(
+        &mut self,
+        inputs: Vec<Value>,
+    ) -> anyhow::Result<(Vec<Value>, VisualizeResult)> {
+        Ok((self.process(inputs)?, VisualizeResult::None))
+    }
+
+    fn on_hover(
+        &mut self,
+        pos: Vector2,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        let _ = context;
+        let _ = pos;
+        control::ControlResult::Passthrough
+    }
+
+    fn on_unhover(&mut self, context: &mut control::ControlContext) -> control::ControlResult {
+        let _ = context;
+        control::ControlResult::Passthrough
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        let _ = (pos, event, context);
+        control::ControlResult::Passthrough
+    }
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        None
+    }
+}
+
+/// Wraps a static [`Block`] (or a whole chain of them) so it can be used as a graph node.
+pub struct Embedded<B, I, O> {
+    block: B,
+    _marker: PhantomData<fn(I) -> O>,
+}
+
+impl<B, I, O> Embedded<B, I, O>
+where
+    B: Block<I, Output = O>,
+{
+    pub fn new(block: B) -> Self {
+        Self {
+            block,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<B: Debug, I, O> Debug for Embedded<B, I, O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.block.fmt(f)
+    }
+}
+
+impl<B, I, O> DynBlock for Embedded<B, I, O>
+where
+    B: Block<I, Output = O>,
+    I: Ports,
+    O: Ports,
+{
+    fn input_kinds(&self) -> Vec<&'static str> {
+        let mut kinds = vec![];
+        I::kinds(&mut kinds);
+        kinds
+    }
+
+    fn output_kinds(&self) -> Vec<&'static str> {
+        let mut kinds = vec![];
+        O::kinds(&mut kinds);
+        kinds
+    }
+
+    fn process(&mut self, inputs: Vec<Value>) -> anyhow::Result<Vec<Value>> {
+        let input = I::from_values(&mut inputs.into_iter())?;
+        let mut out = Vec::with_capacity(O::arity());
+        self.block.process(input).into_values(&mut out);
+        Ok(out)
+    }
+
+    fn process_and_visualize(
+        &mut self,
+        inputs: Vec<Value>,
+    ) -> anyhow::Result<(Vec<Value>, VisualizeResult)> {
+        let input = I::from_values(&mut inputs.into_iter())?;
+        let mut out = Vec::with_capacity(O::arity());
+        let (output, vis_result) = self.block.process_and_visualize(input);
+        output.into_values(&mut out);
+        Ok((out, vis_result))
+    }
+
+    fn on_hover(
+        &mut self,
+        pos: Vector2,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        self.block.on_hover(pos, context)
+    }
+
+    fn on_unhover(&mut self, context: &mut control::ControlContext) -> control::ControlResult {
+        self.block.on_unhover(context)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        self.block.on_mouse(pos, event, context)
+    }
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        self.block.parameters()
+    }
+}
+
+/// A source node that always outputs the same value.
+#[derive(Debug)]
+pub struct Constant(pub Value);
+
+impl DynBlock for Constant {
+    fn input_kinds(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn output_kinds(&self) -> Vec<&'static str> {
+        vec![self.0.kind()]
+    }
+
+    fn process(&mut self, _inputs: Vec<Value>) -> anyhow::Result<Vec<Value>> {
+        Ok(vec![self.0.clone()])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortRef {
+    pub node: NodeId,
+    pub port: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: PortRef,
+    pub to: PortRef,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    block: Box<dyn DynBlock>,
+
+    tx_rec: Rectangle,
+}
+
+/// A graph built at runtime out of boxed blocks.
+///
+/// Nodes have named input and output ports (`in`/`out` for single port blocks,
+/// `in0, in1, ...` otherwise) that are addressed as `"node.port"` when connecting. An
+/// output can feed any number of inputs but every input takes exactly one edge; fan-in
+/// goes through a mixing node such as `Basic::Mix`.
+#[derive(Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    values: HashMap<PortRef, Value>,
+    /// Why the last run failed, drawn in place of the graph.
+    error: Option<String>,
+
+    colored: bool,
+}
+
+impl Debug for Graph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Graph")
+            .field(
+                "nodes",
+                &self.nodes.iter().map(|x| &x.name).collect::<Vec<_>>(),
+            )
+            .field("edges", &self.edges.len())
+            .finish()
+    }
+}
+
+pub(crate) fn default_port_names(prefix: &str, n: usize) -> Vec<String> {
+    if n == 1 {
+        vec![prefix.to_string()]
+    } else {
+        (0..n).map(|i| format!("{prefix}{i}")).collect()
+    }
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn colored(mut self) -> Self {
+        self.colored = true;
+        self
+    }
+
+    /// Adds a static block as a node with the default port names.
+    pub fn add_node<I, O, B>(&mut self, name: &str, block: B) -> anyhow::Result<NodeId>
+    where
+        B: Block<I, Output = O> + 'static,
+        I: Ports + 'static,
+        O: Ports + 'static,
+    {
+        self.add_dyn_node(
+            name,
+            Box::new(Embedded::new(block)),
+            default_port_names("in", I::arity()),
+            default_port_names("out", O::arity()),
+        )
+    }
+
+    pub fn add_node_with_ports<I, O, B>(
+        &mut self,
+        name: &str,
+        block: B,
+        inputs: &[&str],
+        outputs: &[&str],
+    ) -> anyhow::Result<NodeId>
+    where
+        B: Block<I, Output = O> + 'static,
+        I: Ports + 'static,
+        O: Ports + 'static,
+    {
+        self.add_dyn_node(
+            name,
+            Box::new(Embedded::new(block)),
+            inputs.iter().map(|x| x.to_string()).collect(),
+            outputs.iter().map(|x| x.to_string()).collect(),
+        )
+    }
+
+    pub fn add_constant(&mut self, name: &str, value: impl Into<Value>) -> anyhow::Result<NodeId> {
+        self.add_dyn_node(
+            name,
+            Box::new(Constant(value.into())),
+            vec![],
+            default_port_names("out", 1),
+        )
+    }
+
+    pub fn add_dyn_node(
+        &mut self,
+        name: &str,
+        block: Box<dyn DynBlock>,
+        inputs: Vec<String>,
+        outputs: Vec<String>,
+    ) -> anyhow::Result<NodeId> {
+        if name.is_empty() || name.contains('.') {
+            bail!("invalid node name {name:?}");
+        }
+        if self.node(name).is_some() {
+            bail!("node {name:?} already exists");
+        }
+        if inputs.len() != block.input_arity() || outputs.len() != block.output_arity() {
+            bail!(
+                "node {name:?} has {} inputs and {} outputs but {} and {} port names were given",
+                block.input_arity(),
+                block.output_arity(),
+                inputs.len(),
+                outputs.len()
+            );
+        }
+
+        self.nodes.push(Node {
+            name: name.to_string(),
+            inputs,
+            outputs,
+            block,
+            tx_rec: Default::default(),
+        });
+        Ok(NodeId(self.nodes.len() - 1))
+    }
+
+    pub fn node(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|x| x.name == name).map(NodeId)
+    }
+
+    fn port(&self, path: &str, output: bool) -> anyhow::Result<PortRef> {
+        let (node_name, port_name) = path
+            .split_once('.')
+            .ok_or_else(|| anyhow!("expected \"node.port\", got {path:?}"))?;
+        let node = self
+            .node(node_name)
+            .ok_or_else(|| anyhow!("unknown node {node_name:?}"))?;
+        let ports = if output {
+            &self.nodes[node.0].outputs
+        } else {
+            &self.nodes[node.0].inputs
+        };
+        let port = ports
+            .iter()
+            .position(|x| x == port_name)
+            .ok_or_else(|| anyhow!("node {node_name:?} has no port {port_name:?}"))?;
+        Ok(PortRef { node, port })
+    }
+
+    /// Connects an output port to an input port, e.g. `graph.connect("osc.out", "mix.in0")`.
+    /// Fails if the input is already connected or takes another kind of value.
+    pub fn connect(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        let from_port = self.port(from, true)?;
+        let to_port = self.port(to, false)?;
+        if self.edges.iter().any(|x| x.to == to_port) {
+            bail!("input {to} is already connected");
+        }
+        let output_kind = self.nodes[from_port.node.0].block.output_kinds()[from_port.port];
+        let input_kind = self.nodes[to_port.node.0].block.input_kinds()[to_port.port];
+        if output_kind != input_kind {
+            bail!("can't connect {from} ({output_kind}) to {to} ({input_kind})");
+        }
+        self.edges.push(Edge {
+            from: from_port,
+            to: to_port,
+        });
+        Ok(())
+    }
+
+    /// Checks that every input is connected and that the graph has no cycle, so it can run.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (n, node) in self.nodes.iter().enumerate() {
+            for (port, name) in node.inputs.iter().enumerate() {
+                let to = PortRef {
+                    node: NodeId(n),
+                    port,
+                };
+                if !self.edges.iter().any(|x| x.to == to) {
+                    bail!("input {}.{name} is not connected", node.name);
+                }
+            }
+        }
+        self.topological_order()?;
+        Ok(())
+    }
+
+    /// Why the last run failed, if it did.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Parameters of a node, e.g. `graph.parameters("osc")?.set("freq", ParamValue::Float(220f32))`.
+    pub fn parameters(&mut self, name: &str) -> anyhow::Result<&mut dyn Parameters> {
+        let node = self
+            .node(name)
+            .ok_or_else(|| anyhow!("unknown node {name:?}"))?;
+        self.nodes[node.0]
+            .block
+            .parameters()
+            .ok_or_else(|| anyhow!("node {name:?} has no parameters"))
+    }
+
+    /// Value produced on an output port by the last run.
+    pub fn value(&self, path: &str) -> Option<&Value> {
+        let port = self.port(path, true).ok()?;
+        self.values.get(&port)
+    }
+
+    /// Kahn's algorithm; fails if the graph has a cycle. Edges can't make loops, a loop goes
+    /// inside a single node, e.g. a block closed with [`crate::graph::CanFeedback::feedback`].
+    pub fn topological_order(&self) -> anyhow::Result<Vec<NodeId>> {
+        let mut in_degree = vec![0usize; self.nodes.len()];
+        for edge in self.edges.iter() {
+            in_degree[edge.to.node.0] += 1;
+        }
+
+        let mut ready: Vec<usize> = (0..self.nodes.len())
+            .filter(|x| in_degree[*x] == 0)
+            .rev()
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(n) = ready.pop() {
+            order.push(NodeId(n));
+            for edge in self.edges.iter().filter(|x| x.from.node.0 == n) {
+                in_degree[edge.to.node.0] -= 1;
+                if in_degree[edge.to.node.0] == 0 {
+                    ready.push(edge.to.node.0);
+                }
+            }
+        }
+
+        if order.len() != self.nodes.len() {
+            bail!("the graph has a cycle");
+        }
+        Ok(order)
+    }
+
+    fn gather_inputs(&self, id: NodeId) -> anyhow::Result<Vec<Value>> {
+        let node = &self.nodes[id.0];
+        (0..node.inputs.len())
+            .map(|port| {
+                let to = PortRef { node: id, port };
+                let edge = self.edges.iter().find(|x| x.to == to).ok_or_else(|| {
+                    anyhow!("input {}.{} is not connected", node.name, node.inputs[port])
+                })?;
+                self.values.get(&edge.from).cloned().ok_or_else(|| {
+                    anyhow!(
+                        "no value on the edge to {}.{}",
+                        node.name,
+                        node.inputs[port]
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn store_outputs(&mut self, id: NodeId, outputs: Vec<Value>) -> anyhow::Result<()> {
+        let node = &self.nodes[id.0];
+        if outputs.len() != node.outputs.len() {
+            bail!(
+                "node {:?} produced {} values for {} outputs",
+                node.name,
+                outputs.len(),
+                node.outputs.len()
+            );
+        }
+        for (port, value) in outputs.into_iter().enumerate() {
+            self.values.insert(PortRef { node: id, port }, value);
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        self.values.clear();
+        for id in self.topological_order()? {
+            let inputs = self.gather_inputs(id)?;
+            let outputs = self.nodes[id.0]
+                .block
+                .process(inputs)
+                .with_context(|| format!("while processing {:?}", self.nodes[id.0].name))?;
+            self.store_outputs(id, outputs)?;
+        }
+        Ok(())
+    }
+
+    pub fn run_and_visualize(&mut self) -> anyhow::Result<VisualizeResult> {
+        self.values.clear();
+        let order = self.topological_order()?;
+
+        let mut visuals: Vec<Option<Layout>> = (0..self.nodes.len()).map(|_| None).collect();
+        for id in order.iter().copied() {
+            let inputs = self.gather_inputs(id)?;
+            let (outputs, vis_result) = self.nodes[id.0]
+                .block
+                .process_and_visualize(inputs)
+                .with_context(|| format!("while processing {:?}", self.nodes[id.0].name))?;
+            self.store_outputs(id, outputs)?;
+            visuals[id.0] = match vis_result {
+                VisualizeResult::None => None,
+                VisualizeResult::Block(x) => Some(x),
+            };
+        }
+
+        // column of a node is the longest path from a source to it
+        let mut depth = vec![0usize; self.nodes.len()];
+        for id in order.iter() {
+            for edge in self.edges.iter().filter(|x| x.to.node == *id) {
+                depth[id.0] = depth[id.0].max(depth[edge.from.node.0] + 1);
+            }
+        }
+        let n_columns = depth
+            .iter()
+            .copied()
+            .max()
+            .map(|x| x + 1)
+            .unwrap_or_default();
+
+        let pad_x = vis::T * 10f32;
+        let pad_y = vis::T * 3f32;
+        let mut column_w = vec![0f32; n_columns];
+        let mut column_h = vec![0f32; n_columns];
+        for (n, visual) in visuals.iter().enumerate() {
+            let Some(layout) = visual else {
+                continue;
+            };
+            let c = depth[n];
+            column_w[c] = column_w[c].max(layout.width);
+            if column_h[c] > 0f32 {
+                column_h[c] += pad_y;
+            }
+            column_h[c] += layout.height;
+        }
+
+        let max_w = column_w.iter().sum::<f32>()
+            + pad_x
+                * column_w
+                    .iter()
+                    .filter(|x| **x > 0f32)
+                    .count()
+                    .saturating_sub(1) as f32;
+        let max_h = column_h.iter().copied().fold(0f32, f32::max);
+        if max_w <= 0f32 || max_h <= 0f32 {
+            for node in self.nodes.iter_mut() {
+                node.tx_rec = Default::default();
+            }
+            return Ok(VisualizeResult::None);
+        }
+
+        let mut column_x = vec![0f32; n_columns];
+        let mut x = 0f32;
+        for c in 0..n_columns {
+            column_x[c] = x;
+            if column_w[c] > 0f32 {
+                x += column_w[c] + pad_x;
+            }
+        }
+        let mut column_y: Vec<f32> = column_h
+            .iter()
+            .map(|h| ((max_h - h) / 2f32).trunc())
+            .collect();
+
+        for (n, visual) in visuals.iter().enumerate() {
+            let node = &mut self.nodes[n];
+            let Some(layout) = visual else {
+                node.tx_rec = Default::default();
+                continue;
+            };
+            let c = depth[n];
+            let pos = Vector2::new(
+                column_x[c] + ((column_w[c] - layout.width) / 2f32).trunc(),
+                column_y[c],
+            );
+            column_y[c] += layout.height + pad_y;
+            node.tx_rec = layout.rec(pos);
+        }
+
+        // connections
+        let mut lines = vec![];
+        for (i, edge) in self.edges.iter().enumerate() {
+            let (Some(from), Some(to)) = (&visuals[edge.from.node.0], &visuals[edge.to.node.0])
+            else {
+                continue;
+            };
+            let from_node = &self.nodes[edge.from.node.0];
+            let to_node = &self.nodes[edge.to.node.0];
+            let a = port_position(
+                &from.output_connections,
+                edge.from.port,
+                from_node.outputs.len(),
+                &from_node.tx_rec,
+                true,
+            ) + Vector2::new(from_node.tx_rec.x, from_node.tx_rec.y);
+            let b = port_position(
+                &to.input_connections,
+                edge.to.port,
+                to_node.inputs.len(),
+                &to_node.tx_rec,
+                false,
+            ) + Vector2::new(to_node.tx_rec.x, to_node.tx_rec.y);
+            lines.push((
+                a,
+                b,
+                if self.colored {
+                    vis::LINE_COLORS[i % vis::LINE_COLORS.len()]
+                } else {
+                    vis::BORDER_COLOR
+                },
+            ));
+        }
+
+        let placed: Vec<_> = visuals
+            .into_iter()
+            .zip(self.nodes.iter())
+            .filter_map(|(layout, node)| {
+                Some((layout?, Vector2::new(node.tx_rec.x, node.tx_rec.y)))
+            })
+            .collect();
+        Ok(VisualizeResult::Block(Layout::new(
+            max_w,
+            max_h,
+            move |d| {
+                for (layout, pos) in placed {
+                    layout.paint(d, pos);
+                }
+                for (a, b, color) in lines {
+                    d.draw_line_bezier(a, b, 1f32, color);
+                }
+            },
+        )))
+    }
+}
+
+/// Position of a port inside a node's rectangle. Falls back to spreading the ports over the
+/// side of the rectangle when the block didn't report one connection per port.
+fn port_position(
+    connections: &[Vector2],
+    port: usize,
+    n_ports: usize,
+    rec: &Rectangle,
+    output: bool,
+) -> Vector2 {
+    if let Some(x) = connections.get(port) {
+        return *x;
+    }
+    if let Some(x) = connections.last() {
+        return *x;
+    }
+    Vector2::new(
+        if output { rec.width } else { 0f32 },
+        rec.height * (port + 1) as f32 / (n_ports + 1) as f32,
+    )
+}
+
+impl Block<()> for Graph {
+    type Output = ();
+
+    fn process(&mut self, _input: ()) -> Self::Output {
+        self.error = self.run().err().map(|e| format!("{e:#}"));
+    }
+
+    fn process_and_visualize(&mut self, _input: ()) -> (Self::Output, VisualizeResult) {
+        match self.run_and_visualize() {
+            Ok(x) => {
+                self.error = None;
+                ((), x)
+            }
+            Err(e) => {
+                let error = format!("{e:#}");
+                for node in self.nodes.iter_mut() {
+                    node.tx_rec = Default::default();
+                }
+                self.error = Some(error.clone());
+                ((), vis::visualize_error(&error))
+            }
+        }
+    }
+
+    fn on_hover(
+        &mut self,
+        pos: Vector2,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        for node in self.nodes.iter_mut() {
+            if node.tx_rec.check_collision_point_rec(pos) {
+                node.block
+                    .on_hover(pos - Vector2::new(node.tx_rec.x, node.tx_rec.y), context);
+            } else {
+                node.block.on_unhover(context);
+            }
+        }
+
+        control::ControlResult::Passthrough
+    }
+
+    fn on_unhover(&mut self, context: &mut control::ControlContext) -> control::ControlResult {
+        for node in self.nodes.iter_mut() {
+            node.block.on_unhover(context);
+        }
+        control::ControlResult::Passthrough
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        for node in self.nodes.iter_mut() {
+            if node.tx_rec.check_collision_point_rec(pos) {
+                return node.block.on_mouse(
+                    pos - Vector2::new(node.tx_rec.x, node.tx_rec.y),
+                    event,
+                    context,
+                );
+            }
+        }
+        control::ControlResult::Passthrough
+    }
+
+    fn add_metadata(&mut self, key: &str, value: &str) {
+        match (key, value) {
+            ("colored", "true") => self.colored = true,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::{Basic, ConstMultiplier};
+
+    /// `x` through two gains mixed back together, added in reverse order.
+    fn diamond() -> Graph {
+        let mut graph = Graph::new();
+        graph
+            .add_node_with_ports::<(Wave, Wave), Wave, _>(
+                "mix",
+                Basic::<2>::Mix,
+                &["a", "b"],
+                &["out"],
+            )
+            .unwrap();
+        graph
+            .add_node::<Wave, Wave, _>("b", ConstMultiplier(3f32))
+            .unwrap();
+        graph
+            .add_node::<Wave, Wave, _>("a", ConstMultiplier(2f32))
+            .unwrap();
+        graph.add_constant("x", vec![1f32; 4]).unwrap();
+        graph.connect("x.out", "a.in").unwrap();
+        graph.connect("x.out", "b.in").unwrap();
+        graph.connect("a.out", "mix.a").unwrap();
+        graph.connect("b.out", "mix.b").unwrap();
+        graph
+    }
+
+    #[test]
+    fn nodes_run_after_their_inputs() {
+        let mut graph = diamond();
+        graph.validate().unwrap();
+        let order = graph.topological_order().unwrap();
+        assert_eq!(order.len(), graph.nodes().len());
+        let position = |id: NodeId| order.iter().position(|x| *x == id).unwrap();
+        for edge in graph.edges() {
+            assert!(
+                position(edge.from.node) < position(edge.to.node),
+                "{edge:?}"
+            );
+        }
+
+        graph.run().unwrap();
+        let out = graph.value("mix.out").and_then(Value::as_wave).unwrap();
+        assert_eq!(out, &vec![2.5f32; 4]);
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut graph = Graph::new();
+        graph.add_constant("x", vec![1f32; 4]).unwrap();
+        graph
+            .add_node_with_ports::<(Wave, Wave), Wave, _>(
+                "mix",
+                Basic::<2>::Mix,
+                &["x", "loop"],
+                &["out"],
+            )
+            .unwrap();
+        graph
+            .add_node::<Wave, Wave, _>("gain", ConstMultiplier(0.5f32))
+            .unwrap();
+        graph.connect("x.out", "mix.x").unwrap();
+        graph.connect("mix.out", "gain.in").unwrap();
+        graph.connect("gain.out", "mix.loop").unwrap();
+
+        assert!(graph.topological_order().is_err());
+        assert!(graph.validate().is_err());
+        assert!(graph.run().is_err());
+
+        // drawn as an error instead of panicking
+        graph.process(());
+        assert!(graph.error().is_some());
+    }
+
+    #[test]
+    fn inputs_must_be_connected_to_the_same_kind() {
+        let mut graph = Graph::new();
+        graph
+            .add_constant("duration", Duration::from_secs(1))
+            .unwrap();
+        graph
+            .add_node::<Wave, Wave, _>("gain", ConstMultiplier(1f32))
+            .unwrap();
+        assert!(graph.validate().is_err());
+        assert!(graph.connect("duration.out", "gain.in").is_err());
+        assert!(graph.edges().is_empty());
+
+        graph.add_constant("x", vec![1f32; 4]).unwrap();
+        graph.connect("x.out", "gain.in").unwrap();
+        assert!(graph.connect("x.out", "gain.in").is_err());
+        graph.validate().unwrap();
+    }
+}
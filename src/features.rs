@@ -0,0 +1,642 @@
+//! Frame-wise audio features: mel spectrogram, MFCC, chroma and spectral shape descriptors.
+//!
+//! Every feature is computed on the same frames as [`spectrum::stft`], centered on every
+//! `hop_length`th sample, and gives a column of values per frame. Columns can be written to
+//! CSV or NPY files to be compared with other tools, and are drawn as a heatmap.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::{self, bin_freq};
+use crate::vis::{self, OutputConfig, VisualizeResult, BOX_SIZE, LINE_COLORS};
+use crate::widgets::{self, Button, Range};
+
+/// One column of values per frame.
+pub type Columns = Vec<Vec<f32>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Feature {
+    /// Power in `n_mels` triangular bands evenly spaced on the mel scale.
+    MelSpectrogram,
+    /// The mel spectrogram in dB.
+    LogMel,
+    /// DCT of the log-mel spectrogram, liftered.
+    #[default]
+    Mfcc,
+    /// Power of the 12 pitch classes starting at C, scaled so the strongest is 1.
+    Chroma,
+    /// Magnitude weighted mean frequency in Hz.
+    Centroid,
+    /// Magnitude weighted standard deviation of the frequency around the centroid, in Hz.
+    Bandwidth,
+    /// Frequency in Hz under which `rolloff` of the magnitude is.
+    Rolloff,
+    /// Geometric over arithmetic mean of the power, high for noise and close to 0 for a sine.
+    Flatness,
+    /// Fraction of consecutive samples changing sign.
+    ZeroCrossingRate,
+    /// Root mean square of the samples.
+    Rms,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 10] = [
+        Feature::MelSpectrogram,
+        Feature::LogMel,
+        Feature::Mfcc,
+        Feature::Chroma,
+        Feature::Centroid,
+        Feature::Bandwidth,
+        Feature::Rolloff,
+        Feature::Flatness,
+        Feature::ZeroCrossingRate,
+        Feature::Rms,
+    ];
+
+    /// Whether every frame has more than one value.
+    pub fn is_multidimensional(&self) -> bool {
+        matches!(
+            self,
+            Feature::MelSpectrogram | Feature::LogMel | Feature::Mfcc | Feature::Chroma
+        )
+    }
+}
+
+/// HTK mel scale.
+pub fn hz_to_mel(f: f32) -> f32 {
+    2595f32 * (1f32 + f / 700f32).log10()
+}
+
+pub fn mel_to_hz(m: f32) -> f32 {
+    700f32 * (10f32.powf(m / 2595f32) - 1f32)
+}
+
+/// `n_mels` triangular filters evenly spaced on the mel scale between `fmin` and `fmax`, over
+/// the `n / 2 + 1` bins of an `n` points transform. Every filter has the same area, so wide
+/// bands don't get louder.
+pub fn mel_filterbank(n_mels: usize, n: usize, fmin: f32, fmax: f32) -> Columns {
+    let (lo, hi) = (hz_to_mel(fmin), hz_to_mel(fmax));
+    let edges: Vec<f32> = (0..n_mels + 2)
+        .map(|i| mel_to_hz(lo + (hi - lo) * i as f32 / (n_mels + 1) as f32))
+        .collect();
+    edges
+        .windows(3)
+        .map(|band| {
+            let (left, center, right) = (band[0], band[1], band[2]);
+            (0..=n / 2)
+                .map(|k| {
+                    let f = bin_freq(k, n);
+                    let rising = (f - left) / (center - left);
+                    let falling = (right - f) / (right - center);
+                    rising.min(falling).max(0f32) * 2f32 / (right - left)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Maps the `n / 2 + 1` bins of an `n` points transform between `fmin` and `fmax` to the
+/// nearest of the 12 pitch classes, C first.
+pub fn chroma_filterbank(n: usize, fmin: f32, fmax: f32) -> Columns {
+    let mut filters = vec![vec![0f32; n / 2 + 1]; 12];
+    let freqs = (1..=n / 2).map(|k| (k, bin_freq(k, n)));
+    for (k, f) in freqs.filter(|(_, f)| *f >= fmin && *f <= fmax) {
+        let midi = 69f32 + 12f32 * (f / 440f32).log2();
+        filters[(midi.round() as i32).rem_euclid(12) as usize][k] = 1f32;
+    }
+    filters
+}
+
+/// Orthonormal DCT-II, the first `n_out` coefficients.
+pub fn dct(x: &[f32], n_out: usize) -> Wave {
+    let n = x.len() as f32;
+    (0..n_out)
+        .map(|k| {
+            let scale = if k == 0 {
+                (1f32 / n).sqrt()
+            } else {
+                (2f32 / n).sqrt()
+            };
+            let sum: f32 = x
+                .iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    x * (std::f32::consts::PI * k as f32 * (2 * i + 1) as f32 / (2f32 * n)).cos()
+                })
+                .sum();
+            scale * sum
+        })
+        .collect()
+}
+
+/// Sinusoidal liftering, raises the higher cepstral coefficients which are otherwise much
+/// smaller than the first ones. Does nothing when `lifter` is 0.
+pub fn lifter(coefficients: &mut [f32], lifter: f32) {
+    if lifter <= 0f32 {
+        return;
+    }
+    for (k, c) in coefficients.iter_mut().enumerate() {
+        *c *= 1f32 + lifter / 2f32 * (std::f32::consts::PI * k as f32 / lifter).sin();
+    }
+}
+
+/// Power in dB, floored at -100 dB.
+fn power_to_db(x: f32) -> f32 {
+    10f32 * x.max(1e-10).log10()
+}
+
+/// Applies every filter of `bank` to a power spectrum.
+fn apply(bank: &[Vec<f32>], power: &[f32]) -> Wave {
+    bank.iter()
+        .map(|filter| filter.iter().zip(power.iter()).map(|(w, p)| w * p).sum())
+        .collect()
+}
+
+/// Writes one line of comma separated values per column.
+pub fn write_csv<T: AsRef<Path>>(path: T, columns: &[Vec<f32>]) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for column in columns {
+        let line: Vec<String> = column.iter().map(|x| x.to_string()).collect();
+        writeln!(file, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes a `(frames, values)` float32 array in NumPy's `.npy` format.
+pub fn write_npy<T: AsRef<Path>>(path: T, columns: &[Vec<f32>]) -> anyhow::Result<()> {
+    let values = columns.first().map(|x| x.len()).unwrap_or_default();
+    if columns.iter().any(|x| x.len() != values) {
+        bail!("all frames must have the same number of values");
+    }
+
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {values}), }}",
+        columns.len()
+    );
+    // magic, version and header length take 10 bytes, the whole header is aligned to 64
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x93NUMPY\x01\x00")?;
+    file.write_all(&(header.len() as u16).to_le_bytes())?;
+    file.write_all(header.as_bytes())?;
+    for x in columns.iter().flatten() {
+        file.write_all(&x.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Outputs the values of every frame one after the other, a single value per frame unless
+/// the feature [is multidimensional](Feature::is_multidimensional). When `export` is set, the
+/// `render` command and the export button under the block write the features to that file
+/// (see [`OutputConfig::path`]), as NPY if it ends with `.npy` and CSV otherwise.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureExtractor {
+    #[builder(value = default)]
+    pub feature: Feature,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    #[builder(value = 40)]
+    pub n_mels: usize,
+    #[builder(value = 13)]
+    pub n_mfcc: usize,
+    /// Liftering of the MFCCs, 0 to disable.
+    #[builder(value = 22f32)]
+    pub lifter: f32,
+    /// Fraction of the magnitude under the rolloff frequency.
+    #[builder(value = 0.85f32)]
+    pub rolloff: f32,
+    /// Hz, lowest frequency of the mel bands and chroma.
+    #[builder(value = 0f32)]
+    pub fmin: f32,
+    /// Hz, highest frequency of the mel bands and chroma.
+    #[builder(value = 22050f32)]
+    pub fmax: f32,
+    #[builder(value = default)]
+    pub export: Option<String>,
+    /// Set by the export button, the next process exports.
+    #[serde(skip)]
+    #[builder(value = default)]
+    export_requested: bool,
+    /// Where the last export went, or why it failed.
+    #[serde(skip)]
+    #[builder(value = default)]
+    exported: Option<Result<PathBuf, String>>,
+    #[serde(skip)]
+    #[builder(value = default)]
+    output: OutputConfig,
+}
+
+impl Default for FeatureExtractor {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+const HEATMAP_WIDTH: f32 = BOX_SIZE * 4f32;
+const HEATMAP_HEIGHT: f32 = 80f32;
+
+impl FeatureExtractor {
+    const PARAMS: &'static [Param] = &[
+        Param::choice(
+            "feature",
+            &[
+                "MelSpectrogram",
+                "LogMel",
+                "Mfcc",
+                "Chroma",
+                "Centroid",
+                "Bandwidth",
+                "Rolloff",
+                "Flatness",
+                "ZeroCrossingRate",
+                "Rms",
+            ],
+        ),
+        Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+        Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+        Param::float("n_mels", Range::log(4f32, 256f32), "bands"),
+        Param::float("n_mfcc", Range::log(1f32, 128f32), "coefficients"),
+        Param::float("lifter", Range::linear(0f32, 60f32), ""),
+        Param::float("rolloff", Range::linear(0.5f32, 1f32), ""),
+        Param::float("fmin", Range::linear(0f32, 22050f32), "Hz"),
+        Param::float("fmax", Range::linear(0f32, 22050f32), "Hz"),
+    ];
+
+    pub fn extract(&self, wave: &[f32]) -> Columns {
+        let fmax = self.fmax.min(SR as f32 / 2f32);
+        let stft = spectrum::stft(wave, &self.window);
+        let n = self.window.frame_size().next_power_of_two();
+        let mels = mel_filterbank(self.n_mels.max(1), n, self.fmin, fmax);
+        let chroma = chroma_filterbank(n, self.fmin, fmax);
+        let freqs: Wave = (0..=n / 2).map(|k| bin_freq(k, n)).collect();
+
+        self.window
+            .centered_frames(wave)
+            .zip(stft)
+            .map(|(frame, bins)| {
+                let magnitude: Wave = bins.iter().map(|x| x.norm()).collect();
+                let power: Wave = bins.iter().map(|x| x.norm_sqr()).collect();
+                let total = magnitude.iter().sum::<f32>().max(f32::MIN_POSITIVE);
+                let centroid = || {
+                    freqs
+                        .iter()
+                        .zip(magnitude.iter())
+                        .map(|(f, m)| f * m)
+                        .sum::<f32>()
+                        / total
+                };
+                match self.feature {
+                    Feature::MelSpectrogram => apply(&mels, &power),
+                    Feature::LogMel => apply(&mels, &power).into_iter().map(power_to_db).collect(),
+                    Feature::Mfcc => {
+                        let log_mel: Wave =
+                            apply(&mels, &power).into_iter().map(power_to_db).collect();
+                        let mut mfcc = dct(&log_mel, self.n_mfcc.min(log_mel.len()));
+                        lifter(&mut mfcc, self.lifter);
+                        mfcc
+                    }
+                    Feature::Chroma => {
+                        let chroma = apply(&chroma, &power);
+                        let max = chroma.iter().copied().fold(0f32, f32::max);
+                        if max > 0f32 {
+                            chroma.into_iter().map(|x| x / max).collect()
+                        } else {
+                            chroma
+                        }
+                    }
+                    Feature::Centroid => vec![centroid()],
+                    Feature::Bandwidth => {
+                        let c = centroid();
+                        let variance = freqs
+                            .iter()
+                            .zip(magnitude.iter())
+                            .map(|(f, m)| m * (f - c).powi(2))
+                            .sum::<f32>()
+                            / total;
+                        vec![variance.sqrt()]
+                    }
+                    Feature::Rolloff => {
+                        let threshold = self.rolloff * total;
+                        let mut sum = 0f32;
+                        let k = magnitude
+                            .iter()
+                            .position(|m| {
+                                sum += m;
+                                sum >= threshold
+                            })
+                            .unwrap_or(n / 2);
+                        vec![freqs[k]]
+                    }
+                    Feature::Flatness => {
+                        let power: Wave = power.iter().map(|x| x.max(1e-10)).collect();
+                        let len = power.len() as f32;
+                        let geometric = (power.iter().map(|x| x.ln()).sum::<f32>() / len).exp();
+                        let arithmetic = power.iter().sum::<f32>() / len;
+                        vec![geometric / arithmetic]
+                    }
+                    Feature::ZeroCrossingRate => {
+                        let crossings = frame
+                            .windows(2)
+                            .filter(|x| x[0].is_sign_negative() != x[1].is_sign_negative())
+                            .count();
+                        vec![crossings as f32 / frame.len() as f32]
+                    }
+                    Feature::Rms => {
+                        let power = frame.iter().map(|x| x * x).sum::<f32>() / frame.len() as f32;
+                        vec![power.sqrt()]
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Exports where `output` puts files, and every time when rendering.
+    pub fn with_output(mut self, output: &OutputConfig) -> Self {
+        self.output = output.clone();
+        self
+    }
+
+    /// Writes `columns` to the `export` file, returning its path.
+    pub fn export(&self, columns: &[Vec<f32>]) -> anyhow::Result<PathBuf> {
+        let Some(name) = &self.export else {
+            bail!("no file to export to");
+        };
+        let path = self.output.path(name);
+        let written = if name.ends_with(".npy") {
+            write_npy(&path, columns)
+        } else {
+            write_csv(&path, columns)
+        };
+        written.with_context(|| format!("exporting to {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Exports once after the button was clicked, and every time when rendering.
+    fn export_if_requested(&mut self, columns: &[Vec<f32>]) {
+        if self.export.is_some() && (self.export_requested || self.output.is_rendering()) {
+            self.export_requested = false;
+            self.exported = Some(self.export(columns).map_err(|e| format!("{e:#}")));
+        }
+    }
+
+    fn export_button() -> Button {
+        Button::new(widgets::cell(2, 0, 1))
+    }
+}
+
+impl Block<Wave> for FeatureExtractor {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        let columns = self.extract(&input);
+        self.export_if_requested(&columns);
+        columns.into_iter().flatten().collect()
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let columns = self.extract(&input);
+        self.export_if_requested(&columns);
+        let exported = match &self.exported {
+            Some(Ok(path)) => format!("\nexported {}", path.display()),
+            Some(Err(e)) => format!("\nexport failed: {e}"),
+            None => String::new(),
+        };
+        let out: Wave = columns.iter().flatten().copied().collect();
+
+        let window = self.window.clone();
+        let exports = self.export.is_some();
+        let (out, result) = widgets::visualize_with_widgets(
+            &format!(
+                "Features\n{:?}\n{} frames{exported}",
+                self.feature,
+                columns.len()
+            ),
+            out,
+            if exports { 3 } else { 2 },
+            move |d| {
+                let (frame_size, hop_length) = WindowSetting::widgets();
+                frame_size.draw(d, window.frame_size() as f32);
+                hop_length.draw(d, window.hop_length() as f32);
+                if exports {
+                    Self::export_button().draw(d, "export");
+                }
+            },
+        );
+        let multidimensional = self.feature.is_multidimensional();
+        let result = vis::with_plot(result, HEATMAP_WIDTH, HEATMAP_HEIGHT, move |d, rec| {
+            if multidimensional {
+                vis::draw_heatmap(d, rec, &columns);
+            } else {
+                let values: Wave = columns.into_iter().flatten().collect();
+                let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                vis::draw_curve(d, rec, &values, min, max, LINE_COLORS[0]);
+            }
+        });
+        (out, result)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        if self.export.is_some() && Self::export_button().update(pos, event) {
+            self.export_requested = true;
+            return control::handled(context, true);
+        }
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for FeatureExtractor {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "feature" => Some(ParamValue::Enum(
+                Feature::ALL
+                    .iter()
+                    .position(|x| *x == self.feature)
+                    .unwrap_or_default(),
+            )),
+            "n_mels" => Some(ParamValue::Float(self.n_mels as f32)),
+            "n_mfcc" => Some(ParamValue::Float(self.n_mfcc as f32)),
+            "lifter" => Some(ParamValue::Float(self.lifter)),
+            "rolloff" => Some(ParamValue::Float(self.rolloff)),
+            "fmin" => Some(ParamValue::Float(self.fmin)),
+            "fmax" => Some(ParamValue::Float(self.fmax)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("feature", ParamValue::Enum(x)) => self.feature = Feature::ALL[*x],
+            ("n_mels", ParamValue::Float(x)) => self.n_mels = x.round() as usize,
+            ("n_mfcc", ParamValue::Float(x)) => self.n_mfcc = x.round() as usize,
+            ("lifter", ParamValue::Float(x)) => self.lifter = *x,
+            ("rolloff", ParamValue::Float(x)) => self.rolloff = *x,
+            ("fmin", ParamValue::Float(x)) => self.fmin = *x,
+            ("fmax", ParamValue::Float(x)) => self.fmax = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::synths::Noise;
+
+    fn sine(freq: f32, len: usize) -> Wave {
+        (0..len)
+            .map(|i| (2f32 * std::f32::consts::PI * freq * i as f32 / SR as f32).sin())
+            .collect()
+    }
+
+    /// The single value of every frame away from both ends of `wave`.
+    fn middle(feature: Feature, wave: &[f32]) -> Wave {
+        let columns = FeatureExtractor::builder()
+            .feature(feature)
+            .build()
+            .extract(wave);
+        let margin = columns.len() / 4;
+        columns[margin..columns.len() - margin]
+            .iter()
+            .map(|x| x[0])
+            .collect()
+    }
+
+    #[test]
+    fn npy_headers_are_aligned_and_hold_the_shape() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("dsp-blocks-features.npy");
+        let columns = vec![vec![1f32, 2f32, 3f32], vec![4f32, 5f32, 6f32]];
+        write_npy(&path, &columns)?;
+        let bytes = std::fs::read(&path)?;
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + len])?;
+        assert!(header.contains("'descr': '<f4'"), "{header}");
+        assert!(header.contains("'shape': (2, 3)"), "{header}");
+        assert!(header.ends_with('\n'));
+        let values: Wave = bytes[10 + len..]
+            .chunks_exact(4)
+            .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+        assert_eq!(values, vec![1f32, 2f32, 3f32, 4f32, 5f32, 6f32]);
+
+        assert!(write_npy(&path, &[vec![1f32], vec![1f32, 2f32]]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dct_is_orthonormal() {
+        let n = 16;
+        let basis: Vec<Wave> = (0..n)
+            .map(|i| {
+                let mut x = vec![0f32; n];
+                x[i] = 1f32;
+                dct(&x, n)
+            })
+            .collect();
+        for j in 0..n {
+            for k in 0..n {
+                let dot: f32 = basis.iter().map(|x| x[j] * x[k]).sum();
+                let expected = if j == k { 1f32 } else { 0f32 };
+                assert!((dot - expected).abs() < 1e-5, "{j} {k}: {dot}");
+            }
+        }
+        // a constant only has the first coefficient
+        let constant = dct(&[1f32; 8], 4);
+        assert!((constant[0] - 8f32.sqrt()).abs() < 1e-5);
+        assert!(constant[1..].iter().all(|x| x.abs() < 1e-5));
+    }
+
+    #[test]
+    fn mel_bands_stay_between_their_edges() {
+        let (n, n_mels, fmin, fmax) = (2048, 10, 300f32, 8000f32);
+        let bank = mel_filterbank(n_mels, n, fmin, fmax);
+        assert_eq!(bank.len(), n_mels);
+        let step = (hz_to_mel(fmax) - hz_to_mel(fmin)) / (n_mels + 1) as f32;
+        for (i, filter) in bank.iter().enumerate() {
+            assert_eq!(filter.len(), n / 2 + 1);
+            let edge = |j: usize| mel_to_hz(hz_to_mel(fmin) + step * j as f32);
+            let (left, center, right) = (edge(i), edge(i + 1), edge(i + 2));
+            for (k, w) in filter.iter().enumerate() {
+                let f = bin_freq(k, n);
+                if f <= left || f >= right {
+                    assert_eq!(*w, 0f32, "band {i} at {f} Hz");
+                } else {
+                    assert!(*w > 0f32, "band {i} at {f} Hz");
+                }
+            }
+            // the peak is the bin nearest the center
+            let peak = (0..filter.len())
+                .max_by(|a, b| filter[*a].total_cmp(&filter[*b]))
+                .unwrap_or_default();
+            assert!(
+                (bin_freq(peak, n) - center).abs() <= bin_freq(1, n),
+                "band {i}"
+            );
+        }
+        assert!((mel_to_hz(hz_to_mel(1000f32)) - 1000f32).abs() < 0.1);
+    }
+
+    #[test]
+    fn sines_have_their_frequency_as_centroid_and_rolloff() {
+        let wave = sine(1000f32, SR);
+        for x in middle(Feature::Centroid, &wave) {
+            assert!((x - 1000f32).abs() < 50f32, "{x}");
+        }
+        let bin = bin_freq(1, 1024);
+        for x in middle(Feature::Rolloff, &wave) {
+            assert!((x - 1000f32).abs() <= bin, "{x}");
+        }
+    }
+
+    #[test]
+    fn sines_are_tonal_and_clicks_and_noise_are_flat() {
+        for x in middle(Feature::Flatness, &sine(1000f32, SR)) {
+            assert!(x < 0.01, "{x}");
+        }
+        // a click in the middle of a frame has a flat spectrum
+        let mut click = vec![0f32; SR];
+        click[512 * 10] = 1f32;
+        let columns = FeatureExtractor::builder()
+            .feature(Feature::Flatness)
+            .build()
+            .extract(&click);
+        assert!((columns[10][0] - 1f32).abs() < 1e-3, "{:?}", columns[10]);
+
+        // the power of every bin of white noise is exponentially distributed, whose geometric
+        // mean is e^-γ of the arithmetic one
+        let noise = Noise::default().samples(SR);
+        let flatness = middle(Feature::Flatness, &noise);
+        let mean = flatness.iter().sum::<f32>() / flatness.len() as f32;
+        assert!((mean - (-0.5772f32).exp()).abs() < 0.05, "{mean}");
+    }
+}
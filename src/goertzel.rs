@@ -0,0 +1,141 @@
+//! Power at a few frequencies with the Goertzel algorithm, a second order resonator per
+//! frequency instead of a whole FFT. Used for tone signalling, see [`crate::dtmf`].
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::vis::{self, VisualizeResult, BOX_SIZE};
+use crate::widgets::{self, Range};
+
+/// Mean power of the component of `frame` at `freq` Hz, `A² / 2` for a sine of amplitude `A`
+/// going through a whole number of periods in the frame.
+pub fn goertzel(frame: &[f32], freq: f32) -> f32 {
+    if frame.is_empty() {
+        return 0f32;
+    }
+    let coefficient = 2f32 * (2f32 * std::f32::consts::PI * freq / SR as f32).cos();
+    let (mut s1, mut s2) = (0f32, 0f32);
+    for x in frame {
+        let s = x + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+    2f32 * power / (frame.len() as f32).powi(2)
+}
+
+/// Outputs the power at every frequency of `freqs` for every frame, one frame after the other.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Goertzel {
+    /// Hz
+    #[builder(value = default)]
+    pub freqs: Vec<f32>,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+}
+
+impl Default for Goertzel {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl Goertzel {
+    const PARAMS: &'static [Param] = &[
+        Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+        Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+    ];
+
+    /// Power of every frequency, one column per frame.
+    pub fn powers(&self, wave: &[f32]) -> Vec<Wave> {
+        self.window
+            .frames(wave)
+            .map(|frame| self.freqs.iter().map(|f| goertzel(&frame, *f)).collect())
+            .collect()
+    }
+}
+
+impl Block<Wave> for Goertzel {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.powers(&input).into_iter().flatten().collect()
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let powers = self.powers(&input);
+        let out = powers.iter().flatten().copied().collect();
+        let window = self.window.clone();
+        let (out, result) = widgets::visualize_with_widgets(
+            &format!("Goertzel\n{} freqs", self.freqs.len()),
+            out,
+            2,
+            move |d| {
+                let (frame_size, hop_length) = WindowSetting::widgets();
+                frame_size.draw(d, window.frame_size() as f32);
+                hop_length.draw(d, window.hop_length() as f32);
+            },
+        );
+        let result = vis::with_plot(result, BOX_SIZE * 4f32, 80f32, move |d, rec| {
+            vis::draw_heatmap(d, rec, &powers)
+        });
+        (out, result)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for Goertzel {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        self.window.get(name)
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        self.window.set_checked(name, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn power_of_a_sine_is_half_its_squared_amplitude() {
+        // 441 Hz is 100 samples per period, the frame holds 50 of them
+        let (amplitude, freq) = (0.8f32, 441f32);
+        let sine: Wave = (0..5000)
+            .map(|n| amplitude * (2f32 * PI * freq * n as f32 / SR as f32 + 0.3f32).sin())
+            .collect();
+        let power = goertzel(&sine, freq);
+        let expected = amplitude * amplitude / 2f32;
+        assert!(
+            (power - expected).abs() < 1e-3 * expected,
+            "{power} != {expected}"
+        );
+        // another whole number of periods in the frame is orthogonal to it
+        assert!(goertzel(&sine, 2f32 * freq) < 1e-4 * expected);
+    }
+}
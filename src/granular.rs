@@ -0,0 +1,315 @@
+//! Granular synthesis: a cloud of short windowed grains read from a source wave.
+use std::time::Duration;
+
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{self, Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::render::Canvas;
+use crate::vis::{self, VisualizeResult, BOX_SIZE, LINE_COLORS};
+use crate::widgets::Range;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum GrainWindow {
+    #[default]
+    Hann,
+    Triangle,
+    /// Standard deviation of a sixth of the grain.
+    Gaussian,
+    /// Rises and falls over a quarter of the grain each.
+    Trapezoid,
+}
+
+impl GrainWindow {
+    pub const ALL: [GrainWindow; 4] = [
+        GrainWindow::Hann,
+        GrainWindow::Triangle,
+        GrainWindow::Gaussian,
+        GrainWindow::Trapezoid,
+    ];
+
+    /// Gain at `t` between 0 and 1 through the grain.
+    pub fn gain(&self, t: f32) -> f32 {
+        match self {
+            GrainWindow::Hann => 0.5f32 - 0.5f32 * (2f32 * std::f32::consts::PI * t).cos(),
+            GrainWindow::Triangle => 1f32 - (2f32 * t - 1f32).abs(),
+            GrainWindow::Gaussian => (-0.5f32 * ((t - 0.5f32) * 6f32).powi(2)).exp(),
+            GrainWindow::Trapezoid => (4f32 * t.min(1f32 - t)).min(1f32),
+        }
+    }
+}
+
+/// Where a grain is written in the output and read from in the source, in samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Grain {
+    pub onset: usize,
+    pub start: f32,
+}
+
+/// A cloud of grains read from the input around `position`, `density` grains per second
+/// starting at random times. The same for a given `seed`.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Granular {
+    #[builder(value = default)]
+    #[serde(with = "crate::patch::millis")]
+    pub duration: Duration,
+    #[builder(value = default)]
+    #[serde(with = "crate::patch::millis")]
+    pub grain_size: Duration,
+    /// Grains per second.
+    #[builder(value = 40f32)]
+    pub density: f32,
+    /// Between 0 and 1 through the source.
+    #[builder(value = 0.5f32)]
+    pub position: f32,
+    /// How far from `position` grains can start, as a fraction of the source.
+    #[builder(value = 0.1f32)]
+    pub jitter: f32,
+    /// Semitones
+    #[builder(value = 0f32)]
+    pub pitch: f32,
+    #[builder(value = default)]
+    pub window: GrainWindow,
+    #[builder(value = 1)]
+    pub seed: u32,
+}
+
+impl Default for Granular {
+    fn default() -> Self {
+        Self::builder()
+            .duration(Duration::from_secs(2))
+            .grain_size(Duration::from_millis(80))
+            .build()
+    }
+}
+
+impl Granular {
+    const PARAMS: &'static [Param] = &[
+        Param::duration("duration"),
+        Param::duration("grain_size"),
+        Param::float("density", Range::log(1f32, 1000f32), "grains/s"),
+        Param::float("position", Range::linear(0f32, 1f32), ""),
+        Param::float("jitter", Range::linear(0f32, 1f32), ""),
+        Param::float("pitch", Range::linear(-24f32, 24f32), "st"),
+        Param::choice("window", &["Hann", "Triangle", "Gaussian", "Trapezoid"]),
+    ];
+
+    fn grain_len(&self) -> usize {
+        (self.grain_size.as_secs_f32() * SR as f32)
+            .round()
+            .max(1f32) as usize
+    }
+
+    /// Source samples read per output sample.
+    fn rate(&self) -> f32 {
+        2f32.powf(self.pitch / 12f32)
+    }
+
+    /// The grains for a source of `source_len` samples, their onsets a Poisson process.
+    pub fn grains(&self, source_len: usize) -> Vec<Grain> {
+        let mut uniform = dsp::uniform(self.seed);
+
+        let len = (self.duration.as_secs_f32() * SR as f32) as usize;
+        let span = self.grain_len() as f32 * self.rate();
+        let latest = (source_len as f32 - span).max(0f32);
+        let mean_gap = SR as f32 / self.density.max(1e-3);
+        let mut grains = vec![];
+        let mut time = -mean_gap * uniform().ln();
+        while (time as usize) < len {
+            let offset = self.jitter * (2f32 * uniform() - 1f32);
+            let start = ((self.position + offset) * latest).clamp(0f32, latest);
+            grains.push(Grain {
+                onset: time as usize,
+                start,
+            });
+            time -= mean_gap * uniform().ln();
+        }
+        grains
+    }
+
+    pub fn synthesize(&self, source: &[f32], grains: &[Grain]) -> Wave {
+        let len = (self.duration.as_secs_f32() * SR as f32) as usize;
+        let mut out = vec![0f32; len];
+        if source.is_empty() {
+            return out;
+        }
+        let grain_len = self.grain_len();
+        let rate = self.rate();
+        let envelope: Wave = (0..grain_len)
+            .map(|i| self.window.gain((i as f32 + 0.5f32) / grain_len as f32))
+            .collect();
+        for grain in grains {
+            for (i, gain) in envelope.iter().enumerate() {
+                let Some(y) = out.get_mut(grain.onset + i) else {
+                    break;
+                };
+                let position = grain.start + i as f32 * rate;
+                let j = position.floor() as usize;
+                let frac = position - j as f32;
+                let a = source.get(j).copied().unwrap_or_default();
+                let b = source.get(j + 1).copied().unwrap_or_default();
+                *y += gain * (a + (b - a) * frac);
+            }
+        }
+
+        // grains are uncorrelated, their powers add up
+        let overlap = self.density * self.grain_size.as_secs_f32();
+        let gain = 1f32 / overlap.max(1f32).sqrt();
+        out.iter().map(|x| x * gain).collect()
+    }
+}
+
+impl Block<Wave> for Granular {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        let grains = self.grains(input.len());
+        self.synthesize(&input, &grains)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let grains = self.grains(input.len());
+        let out = self.synthesize(&input, &grains);
+        let text = format!("Granular\n{} grains", grains.len());
+        let (out, result) = vis::visualize_simple_box(&text, out);
+
+        let span = self.grain_len() as f32 * self.rate();
+        let result = vis::with_plot(result, BOX_SIZE * 4f32, 50f32, move |d, rec| {
+            draw_grains(d, rec, &input, &grains, span)
+        });
+        (out, result)
+    }
+}
+
+/// The source wave with a line where every grain starts and ends in it.
+fn draw_grains(d: &mut Canvas, rec: Rectangle, source: &[f32], grains: &[Grain], span: f32) {
+    if source.is_empty() {
+        return;
+    }
+    vis::draw_wave(d, rec, source, LINE_COLORS[0], 0f32);
+    let x = |sample: f32| (rec.x + rec.width * (sample / source.len() as f32).min(1f32)).trunc();
+    for grain in grains {
+        let (start, end) = (x(grain.start), x(grain.start + span));
+        let y = (rec.y + rec.height / 2f32).trunc();
+        d.draw_line_ex(
+            Vector2::new(start, y),
+            Vector2::new(end.max(start + 1f32), y),
+            3f32,
+            LINE_COLORS[2],
+        );
+        d.draw_line_ex(
+            Vector2::new(start, rec.y),
+            Vector2::new(start, rec.y + rec.height),
+            1f32,
+            LINE_COLORS[2],
+        );
+    }
+}
+
+impl Parameters for Granular {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "duration" => Some(ParamValue::Duration(self.duration)),
+            "grain_size" => Some(ParamValue::Duration(self.grain_size)),
+            "density" => Some(ParamValue::Float(self.density)),
+            "position" => Some(ParamValue::Float(self.position)),
+            "jitter" => Some(ParamValue::Float(self.jitter)),
+            "pitch" => Some(ParamValue::Float(self.pitch)),
+            "window" => Some(ParamValue::Enum(
+                GrainWindow::ALL
+                    .iter()
+                    .position(|x| *x == self.window)
+                    .unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, value) {
+            ("duration", ParamValue::Duration(x)) => self.duration = x,
+            ("grain_size", ParamValue::Duration(x)) => self.grain_size = x,
+            ("density", ParamValue::Float(x)) => self.density = x,
+            ("position", ParamValue::Float(x)) => self.position = x,
+            ("jitter", ParamValue::Float(x)) => self.jitter = x,
+            ("pitch", ParamValue::Float(x)) => self.pitch = x,
+            ("window", ParamValue::Enum(x)) => self.window = GrainWindow::ALL[x],
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::synths::Noise;
+
+    #[test]
+    fn seeds_make_clouds_repeatable() {
+        let source = Noise::default().samples(SR);
+        let mut a = Granular::default();
+        let mut b = Granular::default();
+        let (out_a, out_b) = (a.process(source.clone()), b.process(source.clone()));
+        assert_eq!(out_a, out_b);
+        let onsets =
+            |x: &Granular| -> Vec<usize> { x.grains(SR).iter().map(|g| g.onset).collect() };
+        assert_eq!(onsets(&a), onsets(&b));
+
+        let mut c = Granular::builder()
+            .duration(Duration::from_secs(2))
+            .grain_size(Duration::from_millis(80))
+            .seed(2)
+            .build();
+        assert_ne!(onsets(&a), onsets(&c));
+        assert_ne!(out_a, c.process(source));
+    }
+
+    #[test]
+    fn grains_are_read_within_the_source() {
+        let source_len = SR;
+        for (position, jitter, pitch) in [
+            (0f32, 1f32, 0f32),
+            (1f32, 1f32, 0f32),
+            (0.5, 0.1, 12f32),
+            (1f32, 0f32, 24f32),
+            (0f32, 0.5, -24f32),
+        ] {
+            let granular = Granular::builder()
+                .duration(Duration::from_secs(2))
+                .grain_size(Duration::from_millis(80))
+                .position(position)
+                .jitter(jitter)
+                .pitch(pitch)
+                .build();
+            let grains = granular.grains(source_len);
+            // about 40 grains per second
+            assert!((50..110).contains(&grains.len()), "{}", grains.len());
+            let span = granular.grain_len() as f32 * granular.rate();
+            let latest = source_len as f32 - span;
+            for grain in grains.iter() {
+                assert!(
+                    (0f32..=latest).contains(&grain.start),
+                    "{} > {latest}",
+                    grain.start
+                );
+                assert!(grain.onset < SR * 2);
+            }
+            assert!(grains.windows(2).all(|x| x[0].onset <= x[1].onset));
+        }
+
+        // sources shorter than a grain are read from the start
+        let grains = Granular::default().grains(100);
+        assert!(grains.iter().all(|x| x.start == 0f32));
+    }
+}
@@ -1,11 +1,11 @@
-use crate::vis::{DrawContext, VisualizeResult};
+use crate::dsp::Wave;
+use crate::layout::{self, Layout};
+use crate::params::Parameters;
+use crate::render::Canvas;
+use crate::vis::VisualizeResult;
 use crate::{control, vis};
-use raylib::texture::RaylibTexture2D;
-use raylib::{color::Color, ffi::BlendMode, math::Vector2, prelude::RaylibBlendModeExt};
-use raylib::{
-    math::Rectangle,
-    prelude::{RaylibDraw, RaylibTextureModeExt},
-};
+use anyhow::bail;
+use raylib::math::{Rectangle, Vector2};
 use std::fmt::Debug;
 
 #[derive(Debug)]
@@ -23,12 +23,7 @@ pub trait Block<Input>: Debug {
 
     fn process(&mut self, input: Input) -> Self::Output;
 
-    fn process_and_visualize(
-        &mut self,
-        input: Input,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
-        let _ = context;
+    fn process_and_visualize(&mut self, input: Input) -> (Self::Output, VisualizeResult) {
         let x = self.process(input);
         (x, VisualizeResult::None)
     }
@@ -48,6 +43,22 @@ pub trait Block<Input>: Debug {
         control::ControlResult::Passthrough
     }
 
+    /// Clicks, drags and scrolls, `pos` is relative to the block like in `on_hover`.
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        let _ = (pos, event, context);
+        control::ControlResult::Passthrough
+    }
+
+    /// Named parameters of the block, `None` for blocks without any (e.g. combinators).
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        None
+    }
+
     fn add_metadata(&mut self, key: &str, value: &str) {
         let _ = key;
         let _ = value;
@@ -101,195 +112,51 @@ where
         (a, b)
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: (I1, I2),
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
-        let (a, a_txt) = self.a.process_and_visualize(input.0, context);
-        let (b, b_txt) = self.b.process_and_visualize(input.1, context);
-
-        let ((a_texture, mut a_inputs, mut a_outputs), (b_texture, mut b_inputs, mut b_output)) =
-            match (a_txt, b_txt) {
-                (VisualizeResult::None, VisualizeResult::None) => {
-                    self.a_tx_rec = Default::default();
-                    self.b_tx_rec = Default::default();
-                    return (((a, b)), VisualizeResult::None);
-                }
-                (VisualizeResult::None, VisualizeResult::SimpleTexture(x)) => {
-                    self.a_tx_rec = Default::default();
-                    self.b_tx_rec.width = x.width().abs() as _;
-                    self.b_tx_rec.height = x.height().abs() as _;
-                    self.b_tx_rec.x = 0f32;
-                    self.b_tx_rec.y = 0f32;
-                    return (((a, b)), VisualizeResult::SimpleTexture(x));
-                }
-                (VisualizeResult::SimpleTexture(x), VisualizeResult::None) => {
-                    self.b_tx_rec = Default::default();
-                    self.a_tx_rec.width = x.width().abs() as _;
-                    self.a_tx_rec.height = x.height().abs() as _;
-                    self.a_tx_rec.x = 0f32;
-                    self.a_tx_rec.y = 0f32;
-                    return (((a, b)), VisualizeResult::SimpleTexture(x));
-                }
-                (
-                    VisualizeResult::None,
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                ) => {
-                    self.a_tx_rec = Default::default();
-                    self.b_tx_rec.width = texture.width().abs() as _;
-                    self.b_tx_rec.height = texture.height().abs() as _;
-                    self.b_tx_rec.x = 0f32;
-                    self.b_tx_rec.y = 0f32;
-                    return (
-                        ((a, b)),
-                        VisualizeResult::Block {
-                            texture,
-                            input_connections,
-                            output_connections,
-                        },
-                    );
-                }
-                (
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                    VisualizeResult::None,
-                ) => {
-                    self.b_tx_rec = Default::default();
-                    self.a_tx_rec.width = texture.width().abs() as _;
-                    self.a_tx_rec.height = texture.height().abs() as _;
-                    self.a_tx_rec.x = 0f32;
-                    self.a_tx_rec.y = 0f32;
-                    return (
-                        ((a, b)),
-                        VisualizeResult::Block {
-                            texture,
-                            input_connections,
-                            output_connections,
-                        },
-                    );
-                }
-                (VisualizeResult::SimpleTexture(a), VisualizeResult::SimpleTexture(b)) => {
-                    ((a, vec![], vec![]), (b, vec![], vec![]))
-                }
-                (
-                    VisualizeResult::SimpleTexture(a),
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                ) => (
-                    (a, vec![], vec![]),
-                    (texture, input_connections, output_connections),
-                ),
-                (
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                    VisualizeResult::SimpleTexture(b),
-                ) => (
-                    (texture, input_connections, output_connections),
-                    (b, vec![], vec![]),
-                ),
-                (
-                    VisualizeResult::Block {
-                        texture: a,
-                        input_connections: a_input,
-                        output_connections: a_output,
-                    },
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                ) => (
-                    (a, a_input, a_output),
-                    (texture, input_connections, output_connections),
-                ),
-            };
-
-        let max_w = a_texture.width().max(b_texture.width());
-        let max_h = a_texture.height() + b_texture.height();
+    fn process_and_visualize(&mut self, input: (I1, I2)) -> (Self::Output, VisualizeResult) {
+        let (a, a_vis) = self.a.process_and_visualize(input.0);
+        let (b, b_vis) = self.b.process_and_visualize(input.1);
+
+        self.a_tx_rec = Default::default();
+        self.b_tx_rec = Default::default();
+        let (a_layout, b_layout) = match (a_vis, b_vis) {
+            (VisualizeResult::None, VisualizeResult::None) => {
+                return ((a, b), VisualizeResult::None);
+            }
+            (VisualizeResult::None, VisualizeResult::Block(x)) => {
+                self.b_tx_rec = x.rec(Vector2::zero());
+                return ((a, b), VisualizeResult::Block(x));
+            }
+            (VisualizeResult::Block(x), VisualizeResult::None) => {
+                self.a_tx_rec = x.rec(Vector2::zero());
+                return ((a, b), VisualizeResult::Block(x));
+            }
+            (VisualizeResult::Block(a), VisualizeResult::Block(b)) => (a, b),
+        };
+
+        let max_w = a_layout.width.max(b_layout.width);
+        let max_h = a_layout.height + b_layout.height;
 
         let pad = vis::T * 3f32;
-        let mut tx = context.get_texture(max_w as _, (max_h as f32 + pad) as _);
-        tx.set_texture_filter(
-            context.thread,
-            raylib::ffi::TextureFilter::TEXTURE_FILTER_ANISOTROPIC_16X,
-        );
-        let mut d = context.rl.begin_drawing(context.thread);
-        let mut d = d.begin_texture_mode(context.thread, &mut tx);
-        let mut db = d.begin_blend_mode(BlendMode::BLEND_ALPHA);
-
-        let a_offset = (max_w as f32) - a_texture.width() as f32;
-        db.draw_texture_rec(
-            &a_texture,
-            Rectangle {
-                width: a_texture.width() as _,
-                height: -a_texture.height() as _,
-                ..Default::default()
-            },
-            Vector2::new(a_offset, 0f32),
-            Color::WHITE,
-        );
-        self.a_tx_rec.width = a_texture.width() as _;
-        self.a_tx_rec.height = a_texture.height() as _;
-        self.a_tx_rec.x = a_offset;
-        self.a_tx_rec.y = 0f32;
-
-        let b_offset = (max_w as f32) - b_texture.width() as f32;
-        let b_offset_y = (a_texture.height() as f32 + pad) as f32;
-        db.draw_texture_rec(
-            &b_texture,
-            Rectangle {
-                width: b_texture.width() as _,
-                height: -b_texture.height() as _,
-                ..Default::default()
-            },
-            Vector2::new(b_offset, b_offset_y),
-            Color::WHITE,
-        );
-        self.b_tx_rec.width = b_texture.width() as _;
-        self.b_tx_rec.height = b_texture.height() as _;
-        self.b_tx_rec.x = b_offset;
-        self.b_tx_rec.y = b_offset_y;
-        drop(db);
-        drop(d);
-
-        for b in b_inputs.iter_mut() {
-            b.y += a_texture.height() as f32 + pad;
-            b.x += b_offset;
-        }
-        for b in b_output.iter_mut() {
-            b.y += a_texture.height() as f32 + pad;
-            b.x += b_offset;
-        }
-        for a in a_inputs.iter_mut() {
-            a.x += a_offset;
-        }
-        for a in a_outputs.iter_mut() {
-            a.x += a_offset;
-        }
-        a_inputs.extend(b_inputs);
-        a_outputs.extend(b_output);
+
+        let a_pos = Vector2::new(max_w - a_layout.width, 0f32);
+        let b_pos = Vector2::new(max_w - b_layout.width, a_layout.height + pad);
+        self.a_tx_rec = a_layout.rec(a_pos);
+        self.b_tx_rec = b_layout.rec(b_pos);
+
+        let mut inputs = layout::translate(&a_layout.input_connections, a_pos);
+        inputs.extend(layout::translate(&b_layout.input_connections, b_pos));
+        let mut outputs = layout::translate(&a_layout.output_connections, a_pos);
+        outputs.extend(layout::translate(&b_layout.output_connections, b_pos));
 
         (
             (a, b),
-            VisualizeResult::Block {
-                texture: tx,
-                input_connections: a_inputs,
-                output_connections: a_outputs,
-            },
+            VisualizeResult::Block(
+                Layout::new(max_w, max_h + pad, move |d| {
+                    a_layout.paint(d, a_pos);
+                    b_layout.paint(d, b_pos);
+                })
+                .with_ports(inputs, outputs),
+            ),
         )
     }
 
@@ -322,6 +189,31 @@ where
         self.b.on_unhover(context);
         control::ControlResult::Passthrough
     }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        if self.a_tx_rec.check_collision_point_rec(pos) {
+            return self.a.on_mouse(
+                pos - Vector2::new(self.a_tx_rec.x, self.a_tx_rec.y),
+                event,
+                context,
+            );
+        }
+
+        if self.b_tx_rec.check_collision_point_rec(pos) {
+            return self.b.on_mouse(
+                pos - Vector2::new(self.b_tx_rec.x, self.b_tx_rec.y),
+                event,
+                context,
+            );
+        }
+
+        control::ControlResult::Passthrough
+    }
 }
 
 impl<T> CanStack for T {
@@ -391,13 +283,10 @@ impl<T> DInto<[T; 4]> for (((T, T), T), T) {
     }
 }
 
-pub trait CanConnect {
-    fn connect<I1, OC, O2, S2: Block<OC, Output = O2>>(
-        self,
-        other: S2,
-    ) -> impl Block<I1, Output = O2>
-    where
-        Self: Block<I1, Output = OC>;
+pub trait CanConnect: Sized {
+    /// Feeds the output of this block to `other`. The blocks are checked when the chain is
+    /// used as a [`Block`], so the input type is left to the caller.
+    fn connect<S2>(self, other: S2) -> ConnectedBlocks<Self, S2>;
 }
 
 #[derive(Debug)]
@@ -423,123 +312,29 @@ where
         self.output.process(x)
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: I1,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
-        let (x, in_txt) = self.input.process_and_visualize(input, context);
-        let (out, out_txt) = self.output.process_and_visualize(x, context);
-
-        let ((a_texture, mut a_inputs, mut a_outputs), (b_texture, mut b_inputs, mut b_output)) =
-            match (in_txt, out_txt) {
-                (VisualizeResult::None, VisualizeResult::None) => {
-                    self.in_tx_rec = Default::default();
-                    self.out_tx_rec = Default::default();
-                    return ((out), VisualizeResult::None);
-                }
-                (VisualizeResult::None, VisualizeResult::SimpleTexture(x)) => {
-                    self.in_tx_rec = Default::default();
-                    self.out_tx_rec.width = x.width().abs() as _;
-                    self.out_tx_rec.height = x.height().abs() as _;
-                    self.out_tx_rec.x = 0f32;
-                    self.out_tx_rec.y = 0f32;
-                    return ((out), VisualizeResult::SimpleTexture(x));
-                }
-                (VisualizeResult::SimpleTexture(x), VisualizeResult::None) => {
-                    self.out_tx_rec = Default::default();
-                    self.in_tx_rec.width = x.width().abs() as _;
-                    self.in_tx_rec.height = x.height().abs() as _;
-                    self.in_tx_rec.x = 0f32;
-                    self.in_tx_rec.y = 0f32;
-                    return ((out), VisualizeResult::SimpleTexture(x));
-                }
-                (
-                    VisualizeResult::None,
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                ) => {
-                    self.in_tx_rec = Default::default();
-                    self.out_tx_rec.width = texture.width().abs() as _;
-                    self.out_tx_rec.height = texture.height().abs() as _;
-                    self.out_tx_rec.x = 0f32;
-                    self.out_tx_rec.y = 0f32;
-                    return (
-                        (out),
-                        VisualizeResult::Block {
-                            texture,
-                            input_connections,
-                            output_connections,
-                        },
-                    );
-                }
-                (
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                    VisualizeResult::None,
-                ) => {
-                    self.out_tx_rec = Default::default();
-                    self.in_tx_rec.width = texture.width().abs() as _;
-                    self.in_tx_rec.height = texture.height().abs() as _;
-                    self.in_tx_rec.x = 0f32;
-                    self.in_tx_rec.y = 0f32;
-                    return (
-                        (out),
-                        VisualizeResult::Block {
-                            texture,
-                            input_connections,
-                            output_connections,
-                        },
-                    );
-                }
-                (VisualizeResult::SimpleTexture(a), VisualizeResult::SimpleTexture(b)) => {
-                    ((a, vec![], vec![]), (b, vec![], vec![]))
-                }
-                (
-                    VisualizeResult::SimpleTexture(a),
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                ) => (
-                    (a, vec![], vec![]),
-                    (texture, input_connections, output_connections),
-                ),
-                (
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                    VisualizeResult::SimpleTexture(b),
-                ) => (
-                    (texture, input_connections, output_connections),
-                    (b, vec![], vec![]),
-                ),
-                (
-                    VisualizeResult::Block {
-                        texture: a,
-                        input_connections: a_input,
-                        output_connections: a_output,
-                    },
-                    VisualizeResult::Block {
-                        texture,
-                        input_connections,
-                        output_connections,
-                    },
-                ) => (
-                    (a, a_input, a_output),
-                    (texture, input_connections, output_connections),
-                ),
-            };
-
+    fn process_and_visualize(&mut self, input: I1) -> (Self::Output, VisualizeResult) {
+        let (x, in_vis) = self.input.process_and_visualize(input);
+        let (out, out_vis) = self.output.process_and_visualize(x);
+
+        self.in_tx_rec = Default::default();
+        self.out_tx_rec = Default::default();
+        let (a_layout, b_layout) = match (in_vis, out_vis) {
+            (VisualizeResult::None, VisualizeResult::None) => {
+                return (out, VisualizeResult::None);
+            }
+            (VisualizeResult::None, VisualizeResult::Block(x)) => {
+                self.out_tx_rec = x.rec(Vector2::zero());
+                return (out, VisualizeResult::Block(x));
+            }
+            (VisualizeResult::Block(x), VisualizeResult::None) => {
+                self.in_tx_rec = x.rec(Vector2::zero());
+                return (out, VisualizeResult::Block(x));
+            }
+            (VisualizeResult::Block(a), VisualizeResult::Block(b)) => (a, b),
+        };
+
+        let mut a_outputs = a_layout.output_connections.clone();
+        let mut b_inputs = b_layout.input_connections.clone();
         if a_outputs.len() == 1 && b_inputs.len() > 1 {
             a_outputs.extend(a_outputs.repeat(b_inputs.len() - 1));
         }
@@ -548,94 +343,47 @@ where
             b_inputs.extend(b_inputs.repeat(a_outputs.len() - 1));
         }
 
-        let max_w = a_texture.width() + b_texture.width();
-        let max_h = a_texture.height().max(b_texture.height());
+        let max_w = a_layout.width + b_layout.width;
+        let max_h = a_layout.height.max(b_layout.height);
 
         let pad = vis::T * 10f32;
-        let mut tx = context.get_texture((max_w as f32 + pad) as _, max_h as _);
-        tx.set_texture_filter(
-            context.thread,
-            raylib::ffi::TextureFilter::TEXTURE_FILTER_ANISOTROPIC_16X,
-        );
-        let mut d = context.rl.begin_drawing(context.thread);
-        let mut d = d.begin_texture_mode(context.thread, &mut tx);
-        let mut db = d.begin_blend_mode(BlendMode::BLEND_ALPHA);
-
-        let a_offset = (((max_h as f32) - a_texture.height() as f32) / 2f32).trunc();
-        db.draw_texture_rec(
-            &a_texture,
-            Rectangle {
-                width: a_texture.width() as _,
-                height: -a_texture.height() as _,
-                ..Default::default()
-            },
-            Vector2::new(0f32, a_offset),
-            Color::WHITE,
-        );
-        self.in_tx_rec.width = a_texture.width() as _;
-        self.in_tx_rec.height = a_texture.height() as _;
-        self.in_tx_rec.x = 0f32;
-        self.in_tx_rec.y = a_offset;
-        let b_txt_pos = Vector2::new(
-            (a_texture.width() as f32 + pad) as f32,
-            (((max_h as f32) - b_texture.height() as f32) / 2f32).trunc(),
-        );
-        db.draw_texture_rec(
-            &b_texture,
-            Rectangle {
-                width: b_texture.width() as _,
-                height: -b_texture.height() as _,
-                ..Default::default()
-            },
-            b_txt_pos,
-            Color::WHITE,
+
+        let a_pos = Vector2::new(0f32, ((max_h - a_layout.height) / 2f32).trunc());
+        let b_pos = Vector2::new(
+            a_layout.width + pad,
+            ((max_h - b_layout.height) / 2f32).trunc(),
         );
-        self.out_tx_rec.width = b_texture.width() as _;
-        self.out_tx_rec.height = b_texture.height() as _;
-        self.out_tx_rec.x = b_txt_pos.x;
-        self.out_tx_rec.y = b_txt_pos.y;
+        self.in_tx_rec = a_layout.rec(a_pos);
+        self.out_tx_rec = b_layout.rec(b_pos);
 
-        // draw connections
+        // connections
         assert_eq!(a_outputs.len(), b_inputs.len());
-        for a in a_outputs.iter_mut() {
-            a.y += a_offset;
-        }
-
-        for b in b_inputs.iter_mut() {
-            *b += b_txt_pos;
-        }
-
+        // a loop, `map` would be ambiguous with `CanMap::map` in this module
+        let mut lines = vec![];
         for (i, (a, b)) in a_outputs.iter().zip(b_inputs.iter()).enumerate() {
-            db.draw_line_bezier(
-                a,
-                b,
-                1f32,
-                if self.colored {
-                    vis::LINE_COLORS[i]
-                } else {
-                    vis::BORDER_COLOR
-                },
-            );
-        }
-
-        drop(db);
-        drop(d);
-
-        for a in a_inputs.iter_mut() {
-            a.y += a_offset;
+            let color = if self.colored {
+                vis::LINE_COLORS[i]
+            } else {
+                vis::BORDER_COLOR
+            };
+            lines.push((*a + a_pos, *b + b_pos, color));
         }
 
-        for b in b_output.iter_mut() {
-            *b += b_txt_pos;
-        }
+        let inputs = layout::translate(&a_layout.input_connections, a_pos);
+        let outputs = layout::translate(&b_layout.output_connections, b_pos);
 
         (
             out,
-            VisualizeResult::Block {
-                texture: tx,
-                input_connections: a_inputs,
-                output_connections: b_output,
-            },
+            VisualizeResult::Block(
+                Layout::new(max_w + pad, max_h, move |d| {
+                    a_layout.paint(d, a_pos);
+                    b_layout.paint(d, b_pos);
+                    for (a, b, color) in lines {
+                        d.draw_line_bezier(a, b, 1f32, color);
+                    }
+                })
+                .with_ports(inputs, outputs),
+            ),
         )
     }
 
@@ -669,6 +417,31 @@ where
         control::ControlResult::Passthrough
     }
 
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        if self.in_tx_rec.check_collision_point_rec(pos) {
+            return self.input.on_mouse(
+                pos - Vector2::new(self.in_tx_rec.x, self.in_tx_rec.y),
+                event,
+                context,
+            );
+        }
+
+        if self.out_tx_rec.check_collision_point_rec(pos) {
+            return self.output.on_mouse(
+                pos - Vector2::new(self.out_tx_rec.x, self.out_tx_rec.y),
+                event,
+                context,
+            );
+        }
+
+        control::ControlResult::Passthrough
+    }
+
     fn add_metadata(&mut self, key: &str, value: &str) {
         match (key, value) {
             ("colored", "true") => self.colored = true,
@@ -678,13 +451,7 @@ where
 }
 
 impl<T> CanConnect for T {
-    fn connect<I1, OC, O2, S2: Block<OC, Output = O2>>(
-        self,
-        other: S2,
-    ) -> impl Block<I1, Output = O2>
-    where
-        Self: Block<I1, Output = OC>,
-    {
+    fn connect<S2>(self, other: S2) -> ConnectedBlocks<Self, S2> {
         ConnectedBlocks {
             input: self,
             output: other,
@@ -733,16 +500,12 @@ where
         (self.mapper)(input)
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: I,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
+    fn process_and_visualize(&mut self, input: I) -> (Self::Output, VisualizeResult) {
         let out = self.process(input);
         if self.no_vis {
             return (out, VisualizeResult::None);
         }
-        vis::visualize_simple_box(context, &self.name, out)
+        vis::visualize_simple_box(&self.name, out)
     }
 }
 
@@ -813,3 +576,365 @@ impl<T: Clone> DInto2<(((T, T), T), T)> for T {
         (((self.clone(), self.clone()), self.clone()), self)
     }
 }
+
+/// Blocks that can process a signal in consecutive chunks, carrying what they remember of the
+/// previous samples from one chunk to the next in a `State` kept by the caller. Processing
+/// the chunks one after the other gives the same output as processing the whole signal.
+///
+/// Their layout doesn't depend on the signal they process.
+pub trait Streaming: Block<Wave, Output = Wave> {
+    /// The default is the state before the first sample.
+    type State: Default;
+
+    /// Processes the next chunk of the signal, returning as many samples.
+    fn process_chunk(&mut self, chunk: &[f32], state: &mut Self::State) -> Wave;
+}
+
+/// Chains of streaming blocks stream too, each block keeping its own state.
+impl<S1: Streaming, S2: Streaming> Streaming for ConnectedBlocks<S1, S2> {
+    type State = (S1::State, S2::State);
+
+    fn process_chunk(&mut self, chunk: &[f32], state: &mut Self::State) -> Wave {
+        let x = self.input.process_chunk(chunk, &mut state.0);
+        self.output.process_chunk(&x, &mut state.1)
+    }
+}
+
+pub trait CanFeedback: Streaming + Sized {
+    /// Routes the output of the block back to its input through a `delay` samples delay:
+    /// `y[n] = block(x[n] + y[n - delay])`.
+    ///
+    /// The loop is resolved by running the inner block on chunks of `delay` samples, so a
+    /// delay of `1` processes the signal sample-by-sample and bigger delays block-by-block.
+    /// Fails if the delay is 0, the loop would have no sample to start from.
+    fn feedback(self, delay: usize) -> anyhow::Result<impl Block<Wave, Output = Wave>>;
+}
+
+impl<T: Streaming> CanFeedback for T {
+    fn feedback(self, delay: usize) -> anyhow::Result<impl Block<Wave, Output = Wave>> {
+        if delay == 0 {
+            bail!("feedback loops need at least one sample of delay");
+        }
+        Ok(FeedbackBlock {
+            inner: self,
+            delay,
+            inner_tx_rec: Default::default(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct FeedbackBlock<S> {
+    inner: S,
+    delay: usize,
+
+    inner_tx_rec: Rectangle,
+}
+
+impl<S: Streaming> FeedbackBlock<S> {
+    fn run_loop(&mut self, input: &Wave) -> Wave {
+        let len = input.len();
+        let mut out = vec![0f32; len];
+        let mut state = S::State::default();
+
+        for start in (0..len).step_by(self.delay) {
+            let end = (start + self.delay).min(len);
+            let mut looped = input[start..end].to_vec();
+            if let Some(back) = start.checked_sub(self.delay) {
+                for (x, y) in looped.iter_mut().zip(&out[back..]) {
+                    *x += y;
+                }
+            }
+            let chunk = self.inner.process_chunk(&looped, &mut state);
+            for (y, x) in out[start..end].iter_mut().zip(chunk) {
+                *y = x;
+            }
+        }
+
+        out
+    }
+}
+
+impl<S: Streaming> Block<Wave> for FeedbackBlock<S> {
+    type Output = Wave;
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.run_loop(&input)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.run_loop(&input);
+
+        // the layout of a streaming block doesn't depend on the signal, an empty one lays it
+        // out without processing it again
+        let (_, inner_vis) = self.inner.process_and_visualize(vec![]);
+        let inner_layout = match inner_vis {
+            VisualizeResult::None => {
+                self.inner_tx_rec = Default::default();
+                return (out, VisualizeResult::None);
+            }
+            VisualizeResult::Block(x) => x,
+        };
+
+        let inner_w = inner_layout.width;
+        let inner_h = inner_layout.height;
+        let in_port = inner_layout
+            .input_connections
+            .first()
+            .copied()
+            .unwrap_or(Vector2::new(0f32, inner_h / 2f32));
+        let out_port = inner_layout
+            .output_connections
+            .first()
+            .copied()
+            .unwrap_or(Vector2::new(inner_w, inner_h / 2f32));
+
+        let pad = vis::T * 10f32;
+        let loop_h = vis::T * 8f32;
+        let w = inner_w + pad * 2f32;
+        let h = inner_h + loop_h;
+
+        let inner_pos = Vector2::new(pad, 0f32);
+        self.inner_tx_rec = inner_layout.rec(inner_pos);
+
+        let in_port = in_port + inner_pos;
+        let out_port = out_port + inner_pos;
+        let delay = self.delay;
+
+        let painter = move |d: &mut Canvas| {
+            inner_layout.paint(d, inner_pos);
+
+            // summing node in front of the inner block
+            let sum_r = vis::T * 1.5f32;
+            let sum_center = Vector2::new((pad / 2f32).trunc(), in_port.y);
+            d.draw_circle_lines(sum_center, sum_r, vis::BORDER_COLOR);
+            d.draw_text(
+                "+",
+                (sum_center.x - 2f32) as _,
+                (sum_center.y - 4f32) as _,
+                1,
+                vis::TEXT_COLOR,
+            );
+            d.draw_line_ex(
+                Vector2::new(0f32, in_port.y),
+                Vector2::new(sum_center.x - sum_r, in_port.y),
+                1f32,
+                vis::BORDER_COLOR,
+            );
+            d.draw_line_ex(
+                Vector2::new(sum_center.x + sum_r, in_port.y),
+                in_port,
+                1f32,
+                vis::BORDER_COLOR,
+            );
+            d.draw_line_ex(
+                out_port,
+                Vector2::new(w, out_port.y),
+                1f32,
+                vis::BORDER_COLOR,
+            );
+
+            // return path: out -> down -> back under the block -> up into the summing node
+            let tap_x = (out_port.x + pad / 2f32).trunc();
+            let bottom_y = (inner_h + loop_h / 2f32).trunc();
+            let return_path = [
+                Vector2::new(tap_x, out_port.y),
+                Vector2::new(tap_x, bottom_y),
+                Vector2::new(sum_center.x, bottom_y),
+                Vector2::new(sum_center.x, sum_center.y + sum_r),
+            ];
+            d.draw_line_strip(return_path.to_vec(), 1f32, vis::BORDER_COLOR);
+            let tip = return_path[3];
+            d.draw_triangle_lines(
+                tip,
+                Vector2::new(tip.x - vis::T, tip.y + vis::T * 1.5f32),
+                Vector2::new(tip.x + vis::T, tip.y + vis::T * 1.5f32),
+                vis::BORDER_COLOR,
+            );
+            d.draw_text(
+                &format!("z^-{}", delay),
+                ((w / 2f32) - vis::T * 3f32) as _,
+                (bottom_y - vis::T * 3f32) as _,
+                1,
+                vis::TEXT_COLOR,
+            );
+        };
+
+        (
+            out,
+            VisualizeResult::Block(Layout::new(w, h, painter).with_ports(
+                vec![Vector2::new(0f32, in_port.y)],
+                vec![Vector2::new(w, out_port.y)],
+            )),
+        )
+    }
+
+    fn on_hover(
+        &mut self,
+        pos: Vector2,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        if self.inner_tx_rec.check_collision_point_rec(pos) {
+            self.inner.on_hover(
+                pos - Vector2::new(self.inner_tx_rec.x, self.inner_tx_rec.y),
+                context,
+            );
+        } else {
+            self.inner.on_unhover(context);
+        }
+
+        control::ControlResult::Passthrough
+    }
+
+    fn on_unhover(&mut self, context: &mut control::ControlContext) -> control::ControlResult {
+        self.inner.on_unhover(context);
+        control::ControlResult::Passthrough
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: control::MouseEvent,
+        context: &mut control::ControlContext,
+    ) -> control::ControlResult {
+        if self.inner_tx_rec.check_collision_point_rec(pos) {
+            return self.inner.on_mouse(
+                pos - Vector2::new(self.inner_tx_rec.x, self.inner_tx_rec.y),
+                event,
+                context,
+            );
+        }
+        control::ControlResult::Passthrough
+    }
+
+    fn add_metadata(&mut self, key: &str, value: &str) {
+        self.inner.add_metadata(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::{Biquad, BiquadType, ConstMultiplier, Delay};
+    use crate::transfer::{Lti, TransferFunction};
+
+    fn impulse(len: usize) -> Wave {
+        let mut wave = vec![0f32; len];
+        wave[0] = 1f32;
+        wave
+    }
+
+    #[test]
+    fn feedback_needs_a_delay() {
+        assert!(ConstMultiplier(0.5f32).feedback(0).is_err());
+    }
+
+    #[test]
+    fn gain_in_a_loop_sums_a_geometric_series() {
+        // y[n] = g (1 + y[n - 1]) for a step, so y[n] = g + g^2 + ... + g^(n + 1)
+        let g = 0.9f32;
+        let out = ConstMultiplier(g)
+            .feedback(1)
+            .unwrap()
+            .process(vec![1f32; 64]);
+        for (n, y) in out.iter().enumerate() {
+            let expected = g * (1f32 - g.powi(n as i32 + 1)) / (1f32 - g);
+            assert!(
+                (y - expected).abs() < 1e-4,
+                "y[{n}] = {y}, expected {expected}"
+            );
+        }
+
+        // an impulse comes back every `delay` samples, `g` times smaller each time
+        let out = ConstMultiplier(g).feedback(7).unwrap().process(impulse(64));
+        for (n, y) in out.iter().enumerate() {
+            let expected = if n % 7 == 0 {
+                g.powi(n as i32 / 7 + 1)
+            } else {
+                0f32
+            };
+            assert!(
+                (y - expected).abs() < 1e-6,
+                "y[{n}] = {y}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn delay_in_a_loop_is_a_comb() {
+        // the impulse comes back every 10 samples, 9 from the delay and 1 from the loop
+        let out = Delay(9).feedback(1).unwrap().process(impulse(100));
+        for (n, y) in out.iter().enumerate() {
+            assert_eq!(*y, if n % 10 == 9 { 1f32 } else { 0f32 }, "y[{n}]");
+        }
+
+        // the delay keeps the end of every chunk for the next one
+        let out = Delay(3).feedback(5).unwrap().process(impulse(100));
+        for (n, y) in out.iter().enumerate() {
+            assert_eq!(*y, if n % 8 == 3 { 1f32 } else { 0f32 }, "y[{n}]");
+        }
+    }
+
+    #[test]
+    fn biquad_in_a_loop_keeps_its_state() {
+        // H in a loop of d samples gives H / (1 - z^-d H), or B / (A - z^-d B)
+        let biquad = Biquad::builder()
+            .t(BiquadType::HighPass)
+            .cutoff(2000f32)
+            .build();
+        for delay in [1, 5] {
+            let TransferFunction { b, mut a } = biquad.transfer_function();
+            a.resize(b.len() + delay, 0f32);
+            for (i, x) in b.iter().enumerate() {
+                a[i + delay] -= x;
+            }
+            let expected = TransferFunction { b, a }.impulse_response(200);
+
+            let out = biquad
+                .clone()
+                .feedback(delay)
+                .unwrap()
+                .process(impulse(200));
+            for (n, (y, expected)) in out.iter().zip(&expected).enumerate() {
+                assert!(
+                    (y - expected).abs() < 1e-4,
+                    "delay {delay}, y[{n}] = {y}, expected {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn chain_in_a_loop_keeps_the_state_of_every_block() {
+        // the chain is g H, in a loop of d samples g B / (A - z^-d g B)
+        let g = 0.8f32;
+        let biquad = Biquad::builder()
+            .t(BiquadType::LowPass)
+            .cutoff(3000f32)
+            .build();
+        for delay in [1, 4] {
+            let TransferFunction { mut b, mut a } = biquad.transfer_function();
+            for x in b.iter_mut() {
+                *x *= g;
+            }
+            a.resize(b.len() + delay, 0f32);
+            for (i, x) in b.iter().enumerate() {
+                a[i + delay] -= x;
+            }
+            let expected = TransferFunction { b, a }.impulse_response(200);
+
+            let out = biquad
+                .clone()
+                .connect(ConstMultiplier(g))
+                .feedback(delay)
+                .unwrap()
+                .process(impulse(200));
+            for (n, (y, expected)) in out.iter().zip(&expected).enumerate() {
+                assert!(
+                    (y - expected).abs() < 1e-4,
+                    "delay {delay}, y[{n}] = {y}, expected {expected}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! The analytic signal of a wave, its Hilbert transform as the imaginary part, and what it
+//! gives: instantaneous amplitude, phase and frequency, single-sideband modulation and
+//! frequency shifting.
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::{self, Complex};
+use crate::vis::{self, VisualizeResult, BOX_SIZE, LINE_COLORS};
+use crate::widgets::Range;
+
+/// Analytic signal of `wave`: its negative frequencies removed and its positive ones doubled,
+/// so the real part is the wave and the imaginary part its Hilbert transform.
+pub fn analytic(wave: &[f32]) -> Vec<Complex> {
+    if wave.is_empty() {
+        return vec![];
+    }
+    let n = wave.len().next_power_of_two();
+    let mut x: Vec<Complex> = wave.iter().map(|x| Complex::from(*x)).collect();
+    x.resize(n, Complex::ZERO);
+    spectrum::fft(&mut x);
+    for (k, bin) in x.iter_mut().enumerate() {
+        if k > 0 && k < n - k {
+            *bin = *bin * 2f32;
+        } else if k > n - k {
+            *bin = Complex::ZERO;
+        }
+    }
+    spectrum::ifft(&mut x);
+    x.truncate(wave.len());
+    x
+}
+
+/// Phase of every sample without the jumps of 2π.
+pub fn unwrapped_phase(analytic: &[Complex]) -> Wave {
+    let mut offset = 0f32;
+    let mut previous = 0f32;
+    analytic
+        .iter()
+        .map(|x| {
+            let phase = x.arg();
+            let jump = phase - previous;
+            if jump > std::f32::consts::PI {
+                offset -= 2f32 * std::f32::consts::PI;
+            } else if jump < -std::f32::consts::PI {
+                offset += 2f32 * std::f32::consts::PI;
+            }
+            previous = phase;
+            phase + offset
+        })
+        .collect()
+}
+
+/// Moves every frequency of `wave` by `hz`, negative to move them down. Frequencies moved
+/// past 0 Hz fold back.
+pub fn shift(wave: &[f32], hz: f32) -> Wave {
+    let omega = 2f32 * std::f32::consts::PI * hz / SR as f32;
+    analytic(wave)
+        .into_iter()
+        .enumerate()
+        .map(|(n, x)| (x * Complex::from_polar(1f32, omega * n as f32)).re)
+        .collect()
+}
+
+/// Outputs the real and imaginary parts of the analytic signal, the input and its Hilbert
+/// transform.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Hilbert;
+
+impl Block<Wave> for Hilbert {
+    type Output = (Wave, Wave);
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        analytic(&input).into_iter().map(|x| (x.re, x.im)).unzip()
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        vis::visualize_simple_box("Hilbert", out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Quantity {
+    /// The envelope.
+    #[default]
+    Amplitude,
+    /// Radians, unwrapped.
+    Phase,
+    /// Hz, the derivative of the phase.
+    Frequency,
+}
+
+impl Quantity {
+    pub const ALL: [Quantity; 3] = [Quantity::Amplitude, Quantity::Phase, Quantity::Frequency];
+}
+
+/// Instantaneous amplitude, phase or frequency of every sample, from the analytic signal.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Instantaneous {
+    pub quantity: Quantity,
+}
+
+impl Instantaneous {
+    pub fn measure(&self, wave: &[f32]) -> Wave {
+        let analytic = analytic(wave);
+        match self.quantity {
+            Quantity::Amplitude => analytic.iter().map(|x| x.norm()).collect(),
+            Quantity::Phase => unwrapped_phase(&analytic),
+            Quantity::Frequency => {
+                let scale = SR as f32 / (2f32 * std::f32::consts::PI);
+                // the angle between consecutive samples, the difference of the unwrapped phase
+                // loses precision once it's large. The first sample repeats the second so the
+                // output has the input's length
+                let diffs: Wave = analytic
+                    .windows(2)
+                    .map(|x| (x[1] * x[0].conj()).arg() * scale)
+                    .collect();
+                diffs
+                    .first()
+                    .into_iter()
+                    .chain(diffs.iter())
+                    .copied()
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Block<Wave> for Instantaneous {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.measure(&input)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("Inst.\n{:?}", self.quantity);
+        let (out, result) = vis::visualize_simple_box(&text, out);
+
+        let width = BOX_SIZE * 4f32;
+        let step = (out.len() as f32 / width).ceil().max(1f32) as usize;
+        let values: Wave = out.iter().step_by(step).copied().collect();
+        let result = vis::with_plot(result, width, 50f32, move |d, rec| {
+            let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            vis::draw_curve(d, rec, &values, min, max, LINE_COLORS[0]);
+        });
+        (out, result)
+    }
+}
+
+impl Parameters for Instantaneous {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[Param::choice(
+            "quantity",
+            &["Amplitude", "Phase", "Frequency"],
+        )];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "quantity" => Some(ParamValue::Enum(
+                Quantity::ALL
+                    .iter()
+                    .position(|x| *x == self.quantity)
+                    .unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        if let ("quantity", Some(x)) = (name, value.as_enum()) {
+            self.quantity = Quantity::ALL[x];
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Sideband {
+    #[default]
+    Upper,
+    Lower,
+}
+
+impl Sideband {
+    pub const ALL: [Sideband; 2] = [Sideband::Upper, Sideband::Lower];
+}
+
+/// Single-sideband modulation: the spectrum of the input moved up to `carrier`, above it for
+/// the upper sideband or mirrored below it for the lower one, without the carrier.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ssb {
+    /// Hz
+    #[builder(value = 1000f32)]
+    pub carrier: f32,
+    #[builder(value = default)]
+    pub sideband: Sideband,
+}
+
+impl Default for Ssb {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl Block<Wave> for Ssb {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        match self.sideband {
+            Sideband::Upper => shift(&input, self.carrier),
+            Sideband::Lower => {
+                // the conjugate of the analytic signal holds the mirrored spectrum
+                let omega = 2f32 * std::f32::consts::PI * self.carrier / SR as f32;
+                analytic(&input)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(n, x)| (x.conj() * Complex::from_polar(1f32, omega * n as f32)).re)
+                    .collect()
+            }
+        }
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("SSB {:?}\n{:.0}Hz", self.sideband, self.carrier);
+        vis::visualize_simple_box(&text, out)
+    }
+}
+
+impl Parameters for Ssb {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[
+            Param::float("carrier", Range::log(20f32, 20000f32), "Hz"),
+            Param::choice("sideband", &["Upper", "Lower"]),
+        ];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "carrier" => Some(ParamValue::Float(self.carrier)),
+            "sideband" => Some(ParamValue::Enum(
+                Sideband::ALL
+                    .iter()
+                    .position(|x| *x == self.sideband)
+                    .unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, value) {
+            ("carrier", ParamValue::Float(x)) => self.carrier = x,
+            ("sideband", ParamValue::Enum(x)) => self.sideband = Sideband::ALL[x],
+            _ => {}
+        }
+    }
+}
+
+/// Moves every frequency by the same amount, unlike a pitch shift harmonics stop being
+/// multiples of the fundamental.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrequencyShift {
+    /// Hz
+    pub shift: f32,
+}
+
+impl Default for FrequencyShift {
+    fn default() -> Self {
+        Self { shift: 100f32 }
+    }
+}
+
+impl Block<Wave> for FrequencyShift {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        shift(&input, self.shift)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        vis::visualize_simple_box(&format!("Shift\n{:+.0}Hz", self.shift), out)
+    }
+}
+
+impl Parameters for FrequencyShift {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[Param::float(
+            "shift",
+            Range::linear(-2000f32, 2000f32),
+            "Hz",
+        )];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "shift" => Some(ParamValue::Float(self.shift)),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        if let ("shift", Some(x)) = (name, value.as_float()) {
+            self.shift = x;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::bin_freq;
+
+    /// Every test wave is one transform long, so tones on a bin are periodic.
+    const N: usize = 16384;
+
+    /// In f64, the phase of an f32 one is too coarse to measure its frequency.
+    fn tone(bin: usize) -> Wave {
+        (0..N)
+            .map(|i| (2f64 * std::f64::consts::PI * (bin * i) as f64 / N as f64).sin() as f32)
+            .collect()
+    }
+
+    /// The strongest bin, and the fraction of the power in it.
+    fn peak(wave: &[f32]) -> (usize, f32) {
+        let mut x: Vec<Complex> = wave.iter().map(|x| Complex::from(*x)).collect();
+        spectrum::fft(&mut x);
+        let power: Wave = x[..=N / 2].iter().map(|x| x.norm_sqr()).collect();
+        let bin = (0..power.len())
+            .max_by(|a, b| power[*a].total_cmp(&power[*b]))
+            .unwrap_or_default();
+        (bin, power[bin] / power.iter().sum::<f32>())
+    }
+
+    #[test]
+    fn sines_have_a_steady_frequency() {
+        let freq = bin_freq(372, N);
+        let mut measure = Instantaneous {
+            quantity: Quantity::Frequency,
+        };
+        let out = measure.process(tone(372));
+        assert_eq!(out.len(), N);
+        for x in out.iter() {
+            assert!((x - freq).abs() < 0.01, "{x} instead of {freq}");
+        }
+    }
+
+    #[test]
+    fn amplitudes_follow_the_envelope() {
+        let envelope: Wave = (0..N)
+            .map(|i| 1f32 + 0.5 * (2f32 * std::f32::consts::PI * 3f32 * i as f32 / N as f32).sin())
+            .collect();
+        let wave: Wave = tone(744)
+            .iter()
+            .zip(envelope.iter())
+            .map(|(x, y)| x * y)
+            .collect();
+        let out = Instantaneous::default().process(wave);
+        for (x, y) in out.iter().zip(envelope.iter()) {
+            assert!((x - y).abs() < 0.01, "{x} instead of {y}");
+        }
+    }
+
+    #[test]
+    fn tones_move_to_the_expected_bin() {
+        let hz = bin_freq(100, N);
+        let cases = [
+            (FrequencyShift { shift: hz }.process(tone(200)), 300),
+            (FrequencyShift { shift: -hz }.process(tone(200)), 100),
+            (
+                Ssb::builder().carrier(hz * 4f32).build().process(tone(200)),
+                600,
+            ),
+            (
+                Ssb::builder()
+                    .carrier(hz * 4f32)
+                    .sideband(Sideband::Lower)
+                    .build()
+                    .process(tone(200)),
+                200,
+            ),
+        ];
+        for (i, (wave, expected)) in cases.into_iter().enumerate() {
+            let (bin, fraction) = peak(&wave);
+            assert_eq!(bin, expected, "case {i}");
+            assert!(fraction > 0.99, "case {i}: {fraction}");
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Measuring blocks from the outside: drive them with a test signal and look at what comes out.
+//!
+//! This only describes linear time invariant blocks faithfully, for anything else the result is
+//! the response to that particular test signal.
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use raylib::math::{Rectangle, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::control::{ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::synths::{KroneckerDelta, Sweep};
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::layout::Layout;
+use crate::params::Parameters;
+use crate::render::Canvas;
+use crate::spectrum::{self, Complex};
+use crate::vis::{self, VisualizeResult, BOX_SIZE, T};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum Excitation {
+    /// A unit impulse, the output is the impulse response.
+    #[default]
+    Impulse,
+    /// A sweep followed by as much silence, deconvolved with its inverse filter. Spreads the
+    /// energy over time, and the harmonics of non-linear blocks, which come out before the
+    /// impulse, wrap around to the end of the impulse response instead of landing on top of it.
+    Sweep(Sweep),
+}
+
+/// What a block does to every frequency, derived from its impulse response.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub impulse: Wave,
+    pub step: Wave,
+    /// Hz of every bin of the spectra below, from 0 to Nyquist.
+    pub freqs: Vec<f32>,
+    /// dB
+    pub magnitude: Vec<f32>,
+    /// Unwrapped, in radians.
+    pub phase: Vec<f32>,
+    /// Samples
+    pub group_delay: Vec<f32>,
+}
+
+impl Response {
+    pub fn from_impulse(impulse: Wave) -> Self {
+        let n = impulse.len().max(2).next_power_of_two();
+        let spectrum = spectrum::rfft(&impulse, n);
+
+        // group delay is Re(FFT(n h[n]) / FFT(h)), no need to differentiate the phase
+        let ramp: Wave = impulse
+            .iter()
+            .enumerate()
+            .map(|(i, x)| i as f32 * x)
+            .collect();
+        let ramp_spectrum = spectrum::rfft(&ramp, n);
+        let group_delay = spectrum
+            .iter()
+            .zip(ramp_spectrum.iter())
+            .map(|(h, r)| {
+                if h.norm_sqr() > 1e-12 {
+                    (*r / *h).re
+                } else {
+                    0f32
+                }
+            })
+            .collect();
+
+        let mut phase: Vec<f32> = spectrum.iter().map(Complex::arg).collect();
+        unwrap(&mut phase);
+
+        Self {
+            step: impulse
+                .iter()
+                .scan(0f32, |acc, x| {
+                    *acc += x;
+                    Some(*acc)
+                })
+                .collect(),
+            freqs: (0..spectrum.len())
+                .map(|k| spectrum::bin_freq(k, n))
+                .collect(),
+            magnitude: spectrum.iter().map(|x| spectrum::to_db(x.norm())).collect(),
+            phase,
+            group_delay,
+            impulse,
+        }
+    }
+
+    /// Index of the bin closest to `freq` Hz.
+    pub fn bin(&self, freq: f32) -> usize {
+        let step = SR as f32 / 2f32 / (self.freqs.len().max(2) - 1) as f32;
+        ((freq / step).round() as usize).min(self.freqs.len().saturating_sub(1))
+    }
+
+    /// `n` values of a spectrum at frequencies spaced logarithmically from `min` to `max` Hz.
+    pub fn log_resample(&self, values: &[f32], min: f32, max: f32, n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| {
+                let freq = min * (max / min).powf(i as f32 / (n.max(2) - 1) as f32);
+                values.get(self.bin(freq)).copied().unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// Removes the jumps of `2π` between consecutive values.
+fn unwrap(phase: &mut [f32]) {
+    let (mut previous, mut offset) = (phase.first().copied().unwrap_or_default(), 0f32);
+    for x in phase.iter_mut().skip(1) {
+        let jump = *x - previous;
+        previous = *x;
+        offset -= 2f32 * PI * (jump / (2f32 * PI)).round();
+        *x += offset;
+    }
+}
+
+/// Drives `block` with `excitation` lasting `duration` and measures its response.
+///
+/// The block is processed like any other input, so it shouldn't contain sinks.
+pub fn identify<B: Block<Wave, Output = Wave> + ?Sized>(
+    block: &mut B,
+    excitation: &Excitation,
+    duration: Duration,
+) -> Response {
+    let impulse = match excitation {
+        Excitation::Impulse => block.process(KroneckerDelta::Start.process(duration)),
+        Excitation::Sweep(sweep) => {
+            let mut input = sweep.clone().process(duration);
+            let len = input.len();
+            input.resize(len * 2, 0f32);
+            let output = block.process(input);
+            let deconvolved = spectrum::convolve(&output, &sweep.inverse_filter(duration));
+            // the impulse lands at `len - 1`. The ringing of the band edges of the sweep before
+            // it would leak into every frequency if it were cut off: wrapped around a buffer
+            // as long as the FFT of the response instead, it only adds up with the rest.
+            let (n, start) = (len.max(2).next_power_of_two(), len.saturating_sub(1));
+            let mut impulse = vec![0f32; n];
+            for (i, x) in deconvolved.iter().enumerate() {
+                impulse[(i + n - start) % n] += x;
+            }
+            impulse
+        }
+    };
+    Response::from_impulse(impulse)
+}
+
+const PLOT_WIDTH: f32 = BOX_SIZE * 4f32;
+const PLOT_HEIGHT: f32 = BOX_SIZE;
+/// Range of the frequency axis, Hz.
+const MIN_FREQ: f32 = 10f32;
+const MAX_FREQ: f32 = SR as f32 / 2f32;
+/// dB shown under the peak of the magnitude.
+const MAGNITUDE_RANGE: f32 = 60f32;
+
+/// Wraps a block and plots its magnitude, phase, group delay and step response under it.
+///
+/// The block is measured again every time it's drawn, so its knobs update the plots.
+#[derive(Debug)]
+pub struct BodeView<B> {
+    pub block: B,
+    pub excitation: Excitation,
+    pub duration: Duration,
+}
+
+impl<B> BodeView<B> {
+    pub fn new(block: B) -> Self {
+        Self {
+            block,
+            excitation: Excitation::Impulse,
+            duration: Duration::from_millis(100),
+        }
+    }
+
+    pub fn excitation(mut self, excitation: Excitation) -> Self {
+        self.excitation = excitation;
+        self
+    }
+}
+
+fn draw_plot(d: &mut Canvas, row: usize, title: &str, values: &[f32], min: f32, max: f32) {
+    let rec = Rectangle {
+        x: 0f32,
+        y: PLOT_HEIGHT * row as f32,
+        width: PLOT_WIDTH,
+        height: PLOT_HEIGHT,
+    };
+    vis::draw_border(d, rec);
+    let inner = Rectangle {
+        x: rec.x + T,
+        y: rec.y + T * 4f32,
+        width: rec.width - T * 2f32,
+        height: rec.height - T * 5f32,
+    };
+    vis::draw_curve(d, inner, values, min, max, vis::LINE_COLORS[1]);
+    d.draw_text(
+        title,
+        (rec.x + T) as _,
+        (rec.y + T) as _,
+        1,
+        vis::TEXT_COLOR,
+    );
+}
+
+fn draw_response(d: &mut Canvas, response: &Response) {
+    let n = PLOT_WIDTH as usize;
+    let magnitude = response.log_resample(&response.magnitude, MIN_FREQ, MAX_FREQ, n);
+    let peak = magnitude.iter().copied().fold(f32::MIN, f32::max);
+    let phase = response.log_resample(&response.phase, MIN_FREQ, MAX_FREQ, n);
+    let (phase_min, phase_max) = range(&phase);
+    let delay = response.log_resample(&response.group_delay, MIN_FREQ, MAX_FREQ, n);
+    let (delay_min, delay_max) = range(&delay);
+    let (step_min, step_max) = range(&response.step);
+
+    draw_plot(
+        d,
+        0,
+        &format!("magnitude, peak {peak:.1}dB"),
+        &magnitude,
+        peak - MAGNITUDE_RANGE,
+        peak + 1f32,
+    );
+    draw_plot(
+        d,
+        1,
+        &format!("phase, {phase_min:.1} to {phase_max:.1}rad"),
+        &phase,
+        phase_min - 0.1f32,
+        phase_max + 0.1f32,
+    );
+    draw_plot(
+        d,
+        2,
+        &format!("group delay, {delay_min:.1} to {delay_max:.1} samples"),
+        &delay,
+        delay_min - 0.5f32,
+        delay_max + 0.5f32,
+    );
+    draw_plot(
+        d,
+        3,
+        &format!("step, {step_min:.2} to {step_max:.2}"),
+        &response.step,
+        step_min.min(0f32),
+        step_max.max(0f32) + 0.01f32,
+    );
+}
+
+fn range(values: &[f32]) -> (f32, f32) {
+    values.iter().fold((f32::MAX, f32::MIN), |(min, max), x| {
+        (min.min(*x), max.max(*x))
+    })
+}
+
+impl<B: Block<Wave, Output = Wave>> Block<Wave> for BodeView<B> {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        self.block.parameters()
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.block.process(input)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let (out, result) = self.block.process_and_visualize(input);
+        let response = identify(&mut self.block, &self.excitation, self.duration);
+
+        let (block, width, height) = match result {
+            VisualizeResult::Block(layout) => {
+                let (width, height) = (layout.width, layout.height);
+                (Some(layout), width, height)
+            }
+            VisualizeResult::None => (None, 0f32, 0f32),
+        };
+        let (inputs, outputs) = match &block {
+            Some(layout) => (
+                layout.input_connections.clone(),
+                layout.output_connections.clone(),
+            ),
+            None => {
+                let y = (PLOT_HEIGHT * 2f32).trunc();
+                (
+                    vec![Vector2::new(0f32, y)],
+                    vec![Vector2::new(PLOT_WIDTH, y)],
+                )
+            }
+        };
+        let plots_y = (height + T * 2f32).trunc();
+        let layout = Layout::new(
+            width.max(PLOT_WIDTH),
+            plots_y + PLOT_HEIGHT * 4f32,
+            move |d| {
+                if let Some(block) = block {
+                    block.paint(d, Vector2::zero());
+                }
+                d.translated(Vector2::new(0f32, plots_y), |d| draw_response(d, &response));
+            },
+        )
+        .with_ports(inputs, outputs);
+        (out, VisualizeResult::Block(layout))
+    }
+
+    fn on_hover(&mut self, pos: Vector2, context: &mut ControlContext) -> ControlResult {
+        self.block.on_hover(pos, context)
+    }
+
+    fn on_unhover(&mut self, context: &mut ControlContext) -> ControlResult {
+        self.block.on_unhover(context)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        self.block.on_mouse(pos, event, context)
+    }
+
+    fn add_metadata(&mut self, key: &str, value: &str) {
+        self.block.add_metadata(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::{Biquad, BiquadType, Delay};
+    use crate::transfer::Lti;
+
+    /// Largest difference in dB between the measured magnitude and the transfer function of
+    /// `block`, over `freqs`.
+    fn magnitude_error(block: &Biquad, excitation: &Excitation, freqs: &[f32]) -> f32 {
+        let expected = block.transfer_function();
+        let response = identify(&mut block.clone(), excitation, Duration::from_secs(1));
+        freqs
+            .iter()
+            .map(|freq| {
+                let measured = response.magnitude[response.bin(*freq)];
+                let freq = response.freqs[response.bin(*freq)];
+                (measured - spectrum::to_db(expected.response(freq).norm())).abs()
+            })
+            .fold(0f32, f32::max)
+    }
+
+    #[test]
+    fn measured_biquads_match_their_transfer_function() {
+        let freqs = [100f32, 500f32, 1000f32, 2000f32, 5000f32];
+        for t in [
+            BiquadType::LowPass,
+            BiquadType::HighPass,
+            BiquadType::BandPass,
+        ] {
+            let block = Biquad::builder()
+                .t(t.clone())
+                .cutoff(1000f32)
+                .q(2f32)
+                .build();
+            let impulse = magnitude_error(&block, &Excitation::Impulse, &freqs);
+            assert!(impulse < 0.01f32, "{t:?} from an impulse: {impulse}dB");
+            let sweep = magnitude_error(&block, &Excitation::Sweep(Sweep::default()), &freqs);
+            assert!(sweep < 0.5f32, "{t:?} from a sweep: {sweep}dB");
+        }
+    }
+
+    #[test]
+    fn delays_have_a_flat_group_delay() {
+        for n in [1, 10, 100] {
+            let response = identify(
+                &mut Delay(n),
+                &Excitation::Impulse,
+                Duration::from_millis(50),
+            );
+            assert!(response.magnitude.iter().all(|x| x.abs() < 1e-3));
+            assert!(
+                response
+                    .group_delay
+                    .iter()
+                    .all(|x| (x - n as f32).abs() < 1e-2),
+                "delay of {n}"
+            );
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Measure/arrange pass of the block diagram.
+//!
+//! While processing, every block reports a [`Layout`]: its size, where its ports are and a
+//! painter that draws it. Combinators arrange the layouts of their children into their own
+//! without drawing anything, keeping the computed rectangles for hit-testing. Painters only
+//! run once the whole tree is arranged, all of them into the same [`Canvas`].
+//!
+//! Measuring happens together with processing because some sizes depend on the data, e.g. a
+//! growing `WaveView` is as wide as the longest wave it shows.
+use std::fmt::Debug;
+
+use raylib::math::{Rectangle, Vector2};
+
+use crate::render::Canvas;
+
+/// Draws a block relative to its top left corner.
+pub type Painter = Box<dyn FnOnce(&mut Canvas)>;
+
+pub struct Layout {
+    pub width: f32,
+    pub height: f32,
+    pub input_connections: Vec<Vector2>,
+    pub output_connections: Vec<Vector2>,
+    painter: Painter,
+}
+
+impl Debug for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Layout")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("input_connections", &self.input_connections)
+            .field("output_connections", &self.output_connections)
+            .finish()
+    }
+}
+
+impl Layout {
+    pub fn new(width: f32, height: f32, painter: impl FnOnce(&mut Canvas) + 'static) -> Self {
+        Self {
+            width,
+            height,
+            input_connections: vec![],
+            output_connections: vec![],
+            painter: Box::new(painter),
+        }
+    }
+
+    pub fn with_ports(mut self, inputs: Vec<Vector2>, outputs: Vec<Vector2>) -> Self {
+        self.input_connections = inputs;
+        self.output_connections = outputs;
+        self
+    }
+
+    /// Draws `overlay` after the layout, in the same coordinates.
+    pub fn with_overlay(mut self, overlay: impl FnOnce(&mut Canvas) + 'static) -> Self {
+        let painter = self.painter;
+        self.painter = Box::new(move |d| {
+            painter(d);
+            overlay(d);
+        });
+        self
+    }
+
+    /// Rectangle covered by the layout when placed at `pos`.
+    pub fn rec(&self, pos: Vector2) -> Rectangle {
+        Rectangle {
+            x: pos.x,
+            y: pos.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Draws the layout with its top left corner at `pos`.
+    pub fn paint(self, canvas: &mut Canvas, pos: Vector2) {
+        canvas.translated(pos, self.painter);
+    }
+
+    /// Draws the layout into a canvas of its own size.
+    pub fn into_canvas(self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        self.paint(&mut canvas, Vector2::zero());
+        canvas
+    }
+}
+
+/// Moves every point by `offset`.
+pub fn translate(points: &[Vector2], offset: Vector2) -> Vec<Vector2> {
+    points.iter().map(|x| *x + offset).collect()
+}
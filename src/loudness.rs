@@ -0,0 +1,496 @@
+//! Level and loudness measurements.
+//!
+//! Loudness follows ITU-R BS.1770 (used by EBU R128) for a single channel: the wave is
+//! K-weighted, a high shelf modelling the head followed by a high-pass, then its mean square
+//! is measured over 400 ms (momentary) or 3 s (short-term) windows. The integrated loudness
+//! averages the 400 ms blocks, overlapping by 75%, that pass an absolute gate at -70 LUFS and
+//! a relative gate 10 LU under the loudness of the blocks above the first gate.
+//!
+//! Waves shorter than a window are measured as a single block.
+use raylib::color::Color;
+use raylib::math::{Rectangle, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::blocks::{filter_section, Coefficients};
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::layout::Layout;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::to_db;
+use crate::vis::{self, VisualizeResult, BOX_SIZE, LINE_COLORS, TEXT_COLOR};
+use crate::widgets::Range;
+
+const MOMENTARY: f32 = 0.4;
+const SHORT_TERM: f32 = 3f32;
+/// Hop between gating blocks and between momentary or short-term values, in seconds.
+const HOP: f32 = 0.1;
+const ABSOLUTE_GATE: f32 = -70f32;
+const RELATIVE_GATE: f32 = -10f32;
+
+/// The two K-weighting sections for a sample rate, from the analog prototypes of BS.1770 so
+/// they don't depend on the 48 kHz coefficients of the standard.
+pub fn k_weighting(sample_rate: f32) -> [Coefficients; 2] {
+    let pi = std::f64::consts::PI;
+    let sample_rate = sample_rate as f64;
+
+    // high shelf, +4 dB above about 1.7 kHz
+    let (f0, gain, q) = (
+        1681.974450955533f64,
+        3.999843853973347f64,
+        0.7071752369554196f64,
+    );
+    let k = (pi * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20f64);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1f64 + k / q + k * k;
+    let shelf = (
+        [
+            ((vh + vb * k / q + k * k) / a0) as f32,
+            (2f64 * (k * k - vh) / a0) as f32,
+            ((vh - vb * k / q + k * k) / a0) as f32,
+        ],
+        [
+            (2f64 * (k * k - 1f64) / a0) as f32,
+            ((1f64 - k / q + k * k) / a0) as f32,
+        ],
+    );
+
+    // second order high-pass at 38 Hz, unnormalized like in the standard
+    let (f0, q) = (38.13547087602444f64, 0.5003270373238773f64);
+    let k = (pi * f0 / sample_rate).tan();
+    let a0 = 1f64 + k / q + k * k;
+    let highpass = (
+        [1f32, -2f32, 1f32],
+        [
+            (2f64 * (k * k - 1f64) / a0) as f32,
+            ((1f64 - k / q + k * k) / a0) as f32,
+        ],
+    );
+
+    [shelf, highpass]
+}
+
+pub fn k_weighted(wave: &[f32]) -> Wave {
+    let [shelf, highpass] = k_weighting(SR as f32);
+    filter_section(filter_section(wave.to_vec(), shelf), highpass)
+}
+
+/// Mean square of every `window` samples, every `hop` samples.
+fn mean_squares(wave: &[f32], window: usize, hop: usize) -> Wave {
+    if wave.is_empty() {
+        return vec![];
+    }
+    let window = window.min(wave.len());
+    let mut sums = vec![0f64; wave.len() + 1];
+    for (i, x) in wave.iter().enumerate() {
+        sums[i + 1] = sums[i] + (*x as f64).powi(2);
+    }
+    (0..=wave.len() - window)
+        .step_by(hop.max(1))
+        .map(|start| ((sums[start + window] - sums[start]) / window as f64) as f32)
+        .collect()
+}
+
+fn to_lufs(mean_square: f32) -> f32 {
+    -0.691f32 + 10f32 * mean_square.max(1e-20).log10()
+}
+
+fn seconds(x: f32) -> usize {
+    (x * SR as f32).round() as usize
+}
+
+/// Momentary loudness in LUFS every 100 ms.
+pub fn momentary(wave: &[f32]) -> Wave {
+    mean_squares(&k_weighted(wave), seconds(MOMENTARY), seconds(HOP))
+        .into_iter()
+        .map(to_lufs)
+        .collect()
+}
+
+/// Short-term loudness in LUFS every 100 ms.
+pub fn short_term(wave: &[f32]) -> Wave {
+    mean_squares(&k_weighted(wave), seconds(SHORT_TERM), seconds(HOP))
+        .into_iter()
+        .map(to_lufs)
+        .collect()
+}
+
+/// Gated loudness of the whole wave in LUFS, `-inf` when everything is under the absolute
+/// gate.
+pub fn integrated(wave: &[f32]) -> f32 {
+    let blocks = mean_squares(&k_weighted(wave), seconds(MOMENTARY), seconds(HOP));
+    let mean = |blocks: &[f32]| blocks.iter().sum::<f32>() / blocks.len() as f32;
+
+    let above: Vec<f32> = blocks
+        .into_iter()
+        .filter(|x| to_lufs(*x) > ABSOLUTE_GATE)
+        .collect();
+    if above.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let gate = to_lufs(mean(&above)) + RELATIVE_GATE;
+    let gated: Vec<f32> = above.into_iter().filter(|x| to_lufs(*x) > gate).collect();
+    to_lufs(mean(&gated))
+}
+
+/// Largest absolute value of the wave oversampled 4 times, catching the peaks between
+/// samples that a DAC would reconstruct.
+pub fn true_peak(wave: &[f32]) -> f32 {
+    const OVERSAMPLING: usize = 4;
+    const HALF: isize = 12;
+
+    // Hann windowed sinc for every intermediate position
+    let sinc = |t: f32| {
+        if t == 0f32 {
+            1f32
+        } else {
+            (std::f32::consts::PI * t).sin() / (std::f32::consts::PI * t)
+        }
+    };
+    let taps: Vec<Vec<f32>> = (1..OVERSAMPLING)
+        .map(|p| {
+            let frac = p as f32 / OVERSAMPLING as f32;
+            (-HALF + 1..=HALF)
+                .map(|j| {
+                    let t = frac - j as f32;
+                    sinc(t) * (0.5f32 + 0.5f32 * (std::f32::consts::PI * t / HALF as f32).cos())
+                })
+                .collect()
+        })
+        .collect();
+
+    let sample_peak = peak(wave);
+    let interpolated = (0..wave.len())
+        .flat_map(|i| {
+            taps.iter().map(move |taps| {
+                (-HALF + 1..=HALF)
+                    .zip(taps.iter())
+                    .filter_map(|(j, tap)| {
+                        let n = usize::try_from(i as isize + j).ok()?;
+                        wave.get(n).map(|x| x * tap)
+                    })
+                    .sum::<f32>()
+                    .abs()
+            })
+        })
+        .fold(0f32, f32::max);
+    sample_peak.max(interpolated)
+}
+
+pub fn peak(wave: &[f32]) -> f32 {
+    wave.iter().fold(0f32, |max, x| max.max(x.abs()))
+}
+
+pub fn rms(wave: &[f32]) -> f32 {
+    (wave.iter().map(|x| x * x).sum::<f32>() / wave.len().max(1) as f32).sqrt()
+}
+
+/// Everything a [`Meter`] shows, levels in dB.
+#[derive(Debug, Clone, Default)]
+pub struct Measurement {
+    /// LUFS
+    pub integrated: f32,
+    /// Loudest momentary loudness, LUFS.
+    pub momentary: f32,
+    /// Loudest short-term loudness, LUFS.
+    pub short_term: f32,
+    /// dBTP
+    pub true_peak: f32,
+    /// dBFS
+    pub peak: f32,
+    /// dBFS
+    pub rms: f32,
+    /// Peak over RMS, dB.
+    pub crest_factor: f32,
+    pub dc_offset: f32,
+}
+
+impl Measurement {
+    pub fn new(wave: &[f32]) -> Self {
+        let loudest = |x: Wave| x.into_iter().fold(f32::NEG_INFINITY, f32::max);
+        let (peak_db, rms_db) = (to_db(peak(wave)), to_db(rms(wave)));
+        Self {
+            integrated: integrated(wave),
+            momentary: loudest(momentary(wave)),
+            short_term: loudest(short_term(wave)),
+            true_peak: to_db(true_peak(wave)),
+            peak: peak_db,
+            rms: rms_db,
+            crest_factor: peak_db - rms_db,
+            dc_offset: wave.iter().sum::<f32>() / wave.len().max(1) as f32,
+        }
+    }
+}
+
+/// Shows the loudness and levels of a wave as bars with their values, passes it through.
+#[derive(Debug, Default)]
+pub struct Meter;
+
+const METER_FLOOR: f32 = -60f32;
+const BAR_WIDTH: f32 = 24f32;
+const BAR_HEIGHT: f32 = 90f32;
+const READOUT_SIZE: f32 = 10f32;
+
+impl Block<Wave> for Meter {
+    type Output = Wave;
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        input
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let m = Measurement::new(&input);
+        let bars = [
+            ("M", m.momentary),
+            ("S", m.short_term),
+            ("I", m.integrated),
+            ("TP", m.true_peak),
+            ("PK", m.peak),
+            ("RMS", m.rms),
+        ];
+        let readouts = [
+            format!("I {:.1} LUFS", m.integrated),
+            format!("TP {:.1} dBTP", m.true_peak),
+            format!("crest {:.1} dB", m.crest_factor),
+            format!("DC {:.4}", m.dc_offset),
+        ];
+
+        let width = (BAR_WIDTH * bars.len() as f32).max(BOX_SIZE * 2f32);
+        let bars_y = READOUT_SIZE + 2f32;
+        let height = bars_y + BAR_HEIGHT + READOUT_SIZE * (readouts.len() as f32 + 1f32);
+        let painter = move |d: &mut crate::render::Canvas| {
+            for (i, (label, value)) in bars.iter().enumerate() {
+                let x = BAR_WIDTH * i as f32;
+                let level = ((value - METER_FLOOR) / -METER_FLOOR).clamp(0f32, 1f32);
+                let color = if *value > 0f32 {
+                    Color::RED
+                } else {
+                    LINE_COLORS[i % 2]
+                };
+                d.draw_rectangle_rec(
+                    Rectangle {
+                        x: x + 3f32,
+                        y: bars_y + BAR_HEIGHT * (1f32 - level),
+                        width: BAR_WIDTH - 6f32,
+                        height: BAR_HEIGHT * level,
+                    },
+                    color,
+                );
+                d.draw_text(label, x as _, 0, READOUT_SIZE as _, TEXT_COLOR);
+                let value = if value.is_finite() {
+                    format!("{value:.0}")
+                } else {
+                    "-inf".to_string()
+                };
+                d.draw_text(
+                    &value,
+                    x as _,
+                    (bars_y + BAR_HEIGHT) as _,
+                    READOUT_SIZE as _,
+                    TEXT_COLOR,
+                );
+            }
+            vis::draw_border(
+                d,
+                Rectangle {
+                    x: 0f32,
+                    y: bars_y,
+                    width,
+                    height: BAR_HEIGHT,
+                },
+            );
+            for (i, text) in readouts.iter().enumerate() {
+                d.draw_text(
+                    text,
+                    0,
+                    (bars_y + BAR_HEIGHT + READOUT_SIZE * (i + 1) as f32) as _,
+                    READOUT_SIZE as _,
+                    TEXT_COLOR,
+                );
+            }
+        };
+
+        let center = bars_y + BAR_HEIGHT / 2f32;
+        (
+            input,
+            VisualizeResult::Block(Layout::new(width, height, painter).with_ports(
+                vec![Vector2::new(0f32, center)],
+                vec![Vector2::new(width, center)],
+            )),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum NormalizeTarget {
+    /// Integrated loudness.
+    #[default]
+    Lufs,
+    Peak,
+    TruePeak,
+}
+
+impl NormalizeTarget {
+    pub const ALL: [NormalizeTarget; 3] = [
+        NormalizeTarget::Lufs,
+        NormalizeTarget::Peak,
+        NormalizeTarget::TruePeak,
+    ];
+
+    /// Level of the wave in LUFS or dB.
+    pub fn measure(&self, wave: &[f32]) -> f32 {
+        match self {
+            NormalizeTarget::Lufs => integrated(wave),
+            NormalizeTarget::Peak => to_db(peak(wave)),
+            NormalizeTarget::TruePeak => to_db(true_peak(wave)),
+        }
+    }
+}
+
+/// Scales the wave so its loudness or peak is `level`, silence is left as is.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Normalize {
+    #[builder(value = default)]
+    pub target: NormalizeTarget,
+    /// LUFS or dBFS
+    #[builder(value = -23f32)]
+    pub level: f32,
+}
+
+impl Default for Normalize {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl Normalize {
+    const PARAMS: &'static [Param] = &[
+        Param::choice("target", &["Lufs", "Peak", "TruePeak"]),
+        Param::float("level", Range::linear(-60f32, 0f32), "dB"),
+    ];
+
+    /// Gain in dB bringing the wave to `level`, 0 for silence.
+    pub fn gain(&self, wave: &[f32]) -> f32 {
+        let measured = self.target.measure(wave);
+        if measured.is_finite() && measured > -200f32 {
+            self.level - measured
+        } else {
+            0f32
+        }
+    }
+
+    /// `wave` amplified by `gain` dB.
+    fn amplify(wave: Wave, gain: f32) -> Wave {
+        let gain = 10f32.powf(gain / 20f32);
+        wave.into_iter().map(|x| x * gain).collect()
+    }
+}
+
+impl Block<Wave> for Normalize {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        let gain = self.gain(&input);
+        Self::amplify(input, gain)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let gain = self.gain(&input);
+        let out = Self::amplify(input, gain);
+        vis::visualize_simple_box(
+            &format!(
+                "Normalize\n{:?}\n{:.1}\n{gain:+.1}dB",
+                self.target, self.level
+            ),
+            out,
+        )
+    }
+}
+
+impl Parameters for Normalize {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "target" => Some(ParamValue::Enum(
+                NormalizeTarget::ALL
+                    .iter()
+                    .position(|x| *x == self.target)
+                    .unwrap_or_default(),
+            )),
+            "level" => Some(ParamValue::Float(self.level)),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, value) {
+            ("target", ParamValue::Enum(x)) => self.target = NormalizeTarget::ALL[x],
+            ("level", ParamValue::Float(x)) => self.level = x,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, seconds: f32) -> Wave {
+        (0..(seconds * SR as f32) as usize)
+            .map(|n| amplitude * (2f32 * PI * freq * n as f32 / SR as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn sine_at_1khz_and_minus_20dbfs_is_minus_23_lufs() {
+        let wave = sine(1000f32, 0.1f32, 5f32);
+        let measurement = Measurement::new(&wave);
+        for lufs in [
+            measurement.integrated,
+            measurement.momentary,
+            measurement.short_term,
+        ] {
+            assert!((lufs + 23.01f32).abs() < 0.05, "{lufs} LUFS");
+        }
+    }
+
+    #[test]
+    fn full_scale_sine_peaks_at_0dbtp_between_samples() {
+        // a quarter of the sample rate with a phase of 45°, the samples miss every crest
+        let wave: Wave = [1f32, 1f32, -1f32, -1f32]
+            .iter()
+            .cycle()
+            .take(SR)
+            .map(|x| x * FRAC_1_SQRT_2)
+            .collect();
+        let measurement = Measurement::new(&wave);
+        assert!(
+            (measurement.peak + 3.01f32).abs() < 0.05,
+            "{}",
+            measurement.peak
+        );
+        assert!(
+            measurement.true_peak.abs() < 0.2,
+            "{}",
+            measurement.true_peak
+        );
+    }
+
+    #[test]
+    fn normalize_brings_the_loudness_to_the_level() {
+        let mut normalize = Normalize::default();
+        let out = normalize.process(sine(1000f32, 0.5f32, 5f32));
+        let lufs = integrated(&out);
+        assert!((lufs - normalize.level).abs() < 0.05, "{lufs} LUFS");
+    }
+}
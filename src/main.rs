@@ -3,40 +3,122 @@
 
 use std::env;
 
-use control::ControlContext;
-use graph::Block;
+use anyhow::{bail, Context};
+use control::{ControlContext, MouseEvent};
 use raylib::prelude::*;
-use vis::DrawContext;
+use render::{Backend, RaylibBackend};
+pub mod automation;
 pub mod control;
+pub mod design;
 pub mod dsp;
+pub mod dtmf;
+pub mod dyn_graph;
+pub mod features;
+pub mod goertzel;
+pub mod granular;
 pub mod graph;
+pub mod hilbert;
+pub mod identify;
+pub mod layout;
+pub mod loudness;
+pub mod modem;
+pub mod onset;
+pub mod params;
+pub mod patch;
+pub mod pitch;
+pub mod render;
 pub mod setups;
+pub mod spectrum;
+pub mod stretch;
+pub mod transfer;
 pub mod vis;
 pub mod wav;
+pub mod widgets;
 
 const W: i32 = 1080;
 const H: i32 = 720;
 
+const USAGE: &str = "usage:
+    dsp-blocks list                  list the available setups
+    dsp-blocks run [setup|patch.ron] open a setup (defaults to the playground)
+    dsp-blocks render <setup|patch.ron> [--out <dir>] [--png <file>] [--svg <file>]
+                                     process a setup without opening a window, audio sinks
+                                     and WAV writers write to <dir> (defaults to
+                                     ./target/render)";
+
 fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        [] | ["run"] => run(setups::DEFAULT_SETUP),
+        ["run", setup] => run(setup),
+        ["list"] => {
+            for setup in setups::SETUPS {
+                println!("{:<20}{}", setup.name, setup.description);
+            }
+            Ok(())
+        }
+        ["render", setup, flags @ ..] => {
+            let mut out_dir = "./target/render";
+            let mut png = None;
+            let mut svg = None;
+            let mut flags = flags.iter();
+            while let Some(flag) = flags.next() {
+                match (*flag, flags.next()) {
+                    ("--out", Some(x)) => out_dir = *x,
+                    ("--png", Some(x)) => png = Some(*x),
+                    ("--svg", Some(x)) => svg = Some(*x),
+                    _ => bail!("{USAGE}"),
+                }
+            }
+            render(setup, out_dir, png, svg)
+        }
+        _ => bail!("{USAGE}"),
+    }
+}
+
+fn render(
+    setup_name: &str,
+    out_dir: &str,
+    png: Option<&str>,
+    svg: Option<&str>,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(out_dir)?;
+    let mut system = setups::create(setup_name, &vis::OutputConfig::render(out_dir))?;
+    if png.is_none() && svg.is_none() {
+        system.process();
+        return Ok(());
+    }
+
+    let Some(canvas) = system.process_and_visualize().into_canvas() else {
+        bail!("{setup_name} has nothing to draw");
+    };
+    if let Some(svg) = svg {
+        std::fs::write(svg, render::to_svg(&canvas, vis::BG_COLOR))
+            .with_context(|| format!("writing {svg}"))?;
+    }
+    if let Some(png) = png {
+        std::fs::write(png, render::to_png(&canvas, Color::BLANK))
+            .with_context(|| format!("writing {png}"))?;
+    }
+    Ok(())
+}
+
+fn run(setup_name: &str) -> anyhow::Result<()> {
     let (mut rl, thread) = raylib::init()
         .msaa_4x()
         .size(W, H)
         .title("DSP Blocks")
         .build();
 
-    // select a system
-    let (input, mut system) = setups::playground::create_playground_blocks()?;
-    let mut texture;
+    let mut setup_name = setup_name.to_string();
+    let output = vis::OutputConfig::default();
+    let mut system = setups::create(&setup_name, &output)?;
+    let mut canvas;
 
     macro_rules! redraw {
         () => {{
-            let mut draw_context = DrawContext {
-                thread: &thread,
-                rl: &mut rl,
-            };
-
-            let (_, t) = system.process_and_visualize(input.clone(), &mut draw_context);
-            texture = t;
+            canvas = system.process_and_visualize().into_canvas();
         }};
     }
 
@@ -57,46 +139,52 @@ fn main() -> anyhow::Result<()> {
     let sys_pos = Vector2::new(50.0, 100.0);
 
     let mut last_time_hover = false;
+    // where the left button was pressed, drags are sent there
+    let mut drag_start: Option<Vector2> = None;
     while !rl.window_should_close() {
-        let mouse_pos = rl.get_mouse_position();
-        let mouse_world_pos = rl.get_screen_to_world2D(mouse_pos, cam);
-        // zoom
-        let wheel = rl.get_mouse_wheel_move();
-        if wheel != 0.0 {
-            let zoom_increment = 0.05;
-            cam.zoom += wheel as f32 * zoom_increment;
-            cam.zoom = cam.zoom.max(0.1).min(3.0);
-
-            let delta = mouse_world_pos - cam.target;
-            cam.target +=
-                delta * (1.0 - (1.0 / (cam.zoom / (cam.zoom + wheel as f32 * zoom_increment))));
+        // cycle between the registered setups
+        if rl.is_key_pressed(KeyboardKey::KEY_TAB) {
+            let next = setups::SETUPS
+                .iter()
+                .position(|x| x.name == setup_name)
+                .map(|x| (x + 1) % setups::SETUPS.len())
+                .unwrap_or_default();
+            match (setups::SETUPS[next].create)(&output) {
+                Ok(x) => {
+                    system = x;
+                    setup_name = setups::SETUPS[next].name.to_string();
+                    last_time_hover = false;
+                    drag_start = None;
+                    redraw!();
+                }
+                Err(e) => println!("failed to create {}: {e:?}", setups::SETUPS[next].name),
+            }
         }
 
-        // panning
+        let mouse_pos = rl.get_mouse_position();
+        let mouse_world_pos = rl.get_screen_to_world2D(mouse_pos, cam);
         let mouse_delta = rl.get_mouse_delta();
-        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
-            let delta = mouse_delta.scale_by(-1.0 / cam.zoom);
-            cam.target += delta;
-        }
-
-        cam.target.x = cam.target.x.trunc();
-        cam.target.y = cam.target.y.trunc();
+        let wheel = rl.get_mouse_wheel_move();
 
         // interactive controls
         let mut needs_total_redraw = false;
-        if let Some(x) = texture.as_simple_texture() {
+        let mut wheel_handled = false;
+        if let Some(x) = &canvas {
             let tx_rec = Rectangle {
-                width: x.width() as _,
-                height: x.height() as _,
+                width: x.width,
+                height: x.height,
                 x: sys_pos.x,
                 y: sys_pos.y,
             };
+            let is_inside = tx_rec.check_collision_point_rec(mouse_world_pos);
+            let left_pressed = rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT);
+            let left_down = rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT);
             let mut control_ctx = ControlContext {
                 thread: &thread,
                 rl: &mut rl,
                 is_dirty: false,
             };
-            if tx_rec.check_collision_point_rec(mouse_world_pos) {
+            if is_inside {
                 if mouse_delta.length() > 0f32 {
                     system.on_hover(mouse_world_pos - sys_pos, &mut control_ctx);
                     last_time_hover = true;
@@ -105,9 +193,59 @@ fn main() -> anyhow::Result<()> {
                 system.on_unhover(&mut control_ctx);
                 last_time_hover = false;
             }
+
+            if left_pressed && is_inside {
+                system.on_mouse(
+                    mouse_world_pos - sys_pos,
+                    MouseEvent::Click,
+                    &mut control_ctx,
+                );
+                drag_start = Some(mouse_world_pos);
+            }
+            match drag_start {
+                Some(start) if left_down => {
+                    if mouse_delta.length() > 0f32 {
+                        system.on_mouse(
+                            start - sys_pos,
+                            MouseEvent::Drag(mouse_delta.scale_by(1.0 / cam.zoom)),
+                            &mut control_ctx,
+                        );
+                    }
+                }
+                _ => drag_start = None,
+            }
+            if wheel != 0.0 && is_inside {
+                wheel_handled = system
+                    .on_mouse(
+                        mouse_world_pos - sys_pos,
+                        MouseEvent::Scroll(wheel),
+                        &mut control_ctx,
+                    )
+                    .is_handled();
+            }
             needs_total_redraw = control_ctx.is_dirty;
         }
 
+        // zoom
+        if wheel != 0.0 && !wheel_handled {
+            let zoom_increment = 0.05;
+            cam.zoom += wheel as f32 * zoom_increment;
+            cam.zoom = cam.zoom.max(0.1).min(3.0);
+
+            let delta = mouse_world_pos - cam.target;
+            cam.target +=
+                delta * (1.0 - (1.0 / (cam.zoom / (cam.zoom + wheel as f32 * zoom_increment))));
+        }
+
+        // panning
+        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
+            let delta = mouse_delta.scale_by(-1.0 / cam.zoom);
+            cam.target += delta;
+        }
+
+        cam.target.x = cam.target.x.trunc();
+        cam.target.y = cam.target.y.trunc();
+
         if needs_total_redraw {
             println!("did redraw");
             redraw!();
@@ -117,22 +255,8 @@ fn main() -> anyhow::Result<()> {
         d.clear_background(vis::BG_COLOR);
         {
             let mut d = d.begin_mode2D(cam);
-            if let Some(x) = texture.as_simple_texture() {
-                x.set_texture_filter(
-                    &thread,
-                    raylib::ffi::TextureFilter::TEXTURE_FILTER_ANISOTROPIC_16X,
-                );
-                let mut d = d.begin_blend_mode(BlendMode::BLEND_ALPHA);
-                d.draw_texture_rec(
-                    &x,
-                    Rectangle {
-                        width: x.width() as _,
-                        height: -x.height() as _,
-                        ..Default::default()
-                    },
-                    sys_pos,
-                    Color::WHITE,
-                );
+            if let Some(x) = &canvas {
+                RaylibBackend(&mut d).draw_canvas(x, sys_pos);
             }
         }
 
@@ -140,6 +264,13 @@ fn main() -> anyhow::Result<()> {
         if env::var("NO_TEXT").unwrap_or_default().is_empty() {
             d.draw_text(&format!("Zoom: {:.2}", cam.zoom), 10, 10, 20, Color::WHITE);
             d.draw_text(&format!("Debug: {}", &debug), 10, 30, 20, Color::WHITE);
+            d.draw_text(
+                &format!("Setup: {setup_name} (tab to switch)"),
+                10,
+                50,
+                20,
+                Color::WHITE,
+            );
         }
     }
     Ok(())
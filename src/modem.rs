@@ -0,0 +1,732 @@
+//! Digital modems on audio: FSK (Bell 202 by default), BPSK, QPSK and 16-QAM with
+//! root-raised-cosine pulses, and demodulators recovering the symbol timing and the carrier
+//! phase on their own.
+//!
+//! Bits travel as waves with one sample, 0 or 1, per bit. Every transmission starts with a
+//! preamble of 63 symbols that lets the timing and carrier loops of the demodulator settle,
+//! then gives it the first data symbol and the rotation of the carrier.
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::blocks::synths::{Mls, Noise};
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::Complex;
+use crate::vis::{self, VisualizeResult};
+use crate::widgets::Range;
+
+/// Length of the pulses in symbols.
+const PULSE_SPAN: usize = 8;
+const PREAMBLE_ORDER: u32 = 6;
+
+/// Gains of the timing loop, in symbols per unit of timing error.
+const TIMING_GAINS: (f32, f32) = (0.05f32, 0.0005f32);
+/// Largest difference between the symbol rates of the modulator and the demodulator the
+/// timing loop follows, as a fraction of the rate.
+const MAX_DRIFT: f32 = 0.1f32;
+/// Gains of the carrier loop, in radians per unit of phase error.
+const CARRIER_GAINS: (f32, f32) = (0.05f32, 0.0005f32);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Scheme {
+    /// Binary frequency shift keying, `mark` for 1 and `space` for 0.
+    Fsk,
+    Bpsk,
+    #[default]
+    Qpsk,
+    Qam16,
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 4] = [Scheme::Fsk, Scheme::Bpsk, Scheme::Qpsk, Scheme::Qam16];
+
+    pub fn bits_per_symbol(&self) -> usize {
+        let (i, q) = self.axes();
+        i + q
+    }
+
+    /// Bits carried by the in-phase and quadrature axes.
+    fn axes(&self) -> (usize, usize) {
+        match self {
+            Scheme::Fsk | Scheme::Bpsk => (1, 0),
+            Scheme::Qpsk => (1, 1),
+            Scheme::Qam16 => (2, 2),
+        }
+    }
+
+    /// Square root of the mean power of the points before scaling.
+    fn norm(&self) -> f32 {
+        match self {
+            Scheme::Fsk | Scheme::Bpsk => 1f32,
+            Scheme::Qpsk => 2f32.sqrt(),
+            Scheme::Qam16 => 10f32.sqrt(),
+        }
+    }
+
+    /// Point of a symbol, Gray coded on every axis with a mean power of 1.
+    pub fn map(&self, bits: &[bool]) -> Complex {
+        let (i, _) = self.axes();
+        Complex::new(pam(&bits[..i]), pam(&bits[i..])) / self.norm()
+    }
+
+    /// Nearest point to `y` and its bits.
+    pub fn decide(&self, y: Complex) -> (Complex, Vec<bool>) {
+        let (i, q) = self.axes();
+        let y = y * self.norm();
+        let (re, mut bits) = pam_decide(y.re, i);
+        let (im, q_bits) = pam_decide(y.im, q);
+        bits.extend(q_bits);
+        (Complex::new(re, im) / self.norm(), bits)
+    }
+
+    /// The outermost point of the first quadrant, the preamble alternates between it and its
+    /// opposite.
+    fn preamble_point(&self) -> Complex {
+        let (i, q) = self.axes();
+        let level = |bits: usize| ((1 << bits) - 1) as f32;
+        Complex::new(level(i), level(q)) / self.norm()
+    }
+}
+
+/// Level of Gray coded bits among `2^n` levels evenly spaced by 2 around 0.
+fn pam(bits: &[bool]) -> f32 {
+    let levels = 1usize << bits.len();
+    let gray = bits
+        .iter()
+        .fold(0usize, |acc, bit| (acc << 1) | *bit as usize);
+    let (mut index, mut shift) = (gray, gray >> 1);
+    while shift > 0 {
+        index ^= shift;
+        shift >>= 1;
+    }
+    (2 * index) as f32 - (levels - 1) as f32
+}
+
+/// Nearest level to `x` among `2^n` and its Gray coded bits.
+fn pam_decide(x: f32, n: usize) -> (f32, Vec<bool>) {
+    let levels = 1usize << n;
+    let max = (levels - 1) as f32;
+    let index = ((x + max) / 2f32).round().clamp(0f32, max) as usize;
+    let gray = index ^ (index >> 1);
+    let bits = (0..n).rev().map(|i| (gray >> i) & 1 == 1).collect();
+    ((2 * index) as f32 - max, bits)
+}
+
+/// Root-raised-cosine pulse at `t` symbols from its center, with unit energy.
+fn rrc(t: f32, rolloff: f32) -> f32 {
+    use std::f32::consts::PI;
+    let a = rolloff.clamp(0.001f32, 1f32);
+    if t.abs() < 1e-6 {
+        1f32 - a + 4f32 * a / PI
+    } else if (t.abs() - 1f32 / (4f32 * a)).abs() < 1e-6 {
+        a / 2f32.sqrt()
+            * ((1f32 + 2f32 / PI) * (PI / (4f32 * a)).sin()
+                + (1f32 - 2f32 / PI) * (PI / (4f32 * a)).cos())
+    } else {
+        ((PI * t * (1f32 - a)).sin() + 4f32 * a * t * (PI * t * (1f32 + a)).cos())
+            / (PI * t * (1f32 - (4f32 * a * t).powi(2)))
+    }
+}
+
+/// Mean power of the samples louder than 5% of the loudest one, so the silence around a
+/// transmission doesn't count.
+fn active_power(samples: &[Complex]) -> f32 {
+    let max = samples.iter().map(|x| x.norm_sqr()).fold(0f32, f32::max);
+    let active: Vec<f32> = samples
+        .iter()
+        .map(|x| x.norm_sqr())
+        .filter(|x| *x > 0.05f32 * max)
+        .collect();
+    active.iter().sum::<f32>() / active.len().max(1) as f32
+}
+
+/// Symbols sampled from `soft` at the instants found by a Gardner timing error detector,
+/// starting one symbol in. The steps stay between half a symbol and a symbol and a half, so
+/// loud noise can't stall the loop or send it backwards.
+fn recover_timing(soft: &[Complex], sps: f32) -> Vec<Complex> {
+    let interpolate = |p: f32| {
+        let i = p.floor() as usize;
+        let frac = p - i as f32;
+        soft[i] * (1f32 - frac) + soft[i + 1] * frac
+    };
+    let (kp, ki) = TIMING_GAINS;
+    let mut symbols = vec![];
+    let (mut position, mut drift) = (sps, 0f32);
+    let mut previous = Complex::ZERO;
+    while position + 1f32 < soft.len() as f32 {
+        let y = interpolate(position);
+        let middle = interpolate(position - sps / 2f32);
+        let error = ((y - previous) * middle.conj()).re;
+        drift = (drift + ki * error).clamp(-MAX_DRIFT, MAX_DRIFT);
+        position += (sps * (1f32 - kp * error - drift)).clamp(0.5f32 * sps, 1.5f32 * sps);
+        symbols.push(y);
+        previous = y;
+    }
+    symbols
+}
+
+/// Modulation settings shared by [`Modulator`] and [`Demodulator`].
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modem {
+    #[builder(value = default)]
+    pub scheme: Scheme,
+    /// Symbols per second, nothing is sent nor received unless it is positive.
+    #[builder(value = 1200f32)]
+    pub baud: f32,
+    /// Hz, for PSK and QAM.
+    #[builder(value = 1800f32)]
+    pub carrier: f32,
+    /// Excess bandwidth of the root-raised-cosine pulses.
+    #[builder(value = 0.35f32)]
+    pub rolloff: f32,
+    /// Hz, tone of the 1 bits in FSK.
+    #[builder(value = 1200f32)]
+    pub mark: f32,
+    /// Hz, tone of the 0 bits in FSK.
+    #[builder(value = 2200f32)]
+    pub space: f32,
+}
+
+impl Default for Modem {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl Modem {
+    const PARAMS: &'static [Param] = &[
+        Param::choice("scheme", &["Fsk", "Bpsk", "Qpsk", "Qam16"]),
+        Param::float("baud", Range::log(10f32, 4800f32), "Bd"),
+        Param::float("carrier", Range::log(100f32, 10000f32), "Hz"),
+        Param::float("rolloff", Range::linear(0.05f32, 1f32), ""),
+        Param::float("mark", Range::log(100f32, 10000f32), "Hz"),
+        Param::float("space", Range::log(100f32, 10000f32), "Hz"),
+    ];
+
+    /// Samples per symbol, `None` when the baud isn't positive.
+    fn sps(&self) -> Option<f32> {
+        let sps = SR as f32 / self.baud;
+        (self.baud > 0f32 && sps.is_finite()).then_some(sps)
+    }
+
+    fn preamble(&self) -> Vec<Complex> {
+        let point = self.scheme.preamble_point();
+        Mls {
+            order: PREAMBLE_ORDER,
+        }
+        .sequence()
+        .into_iter()
+        .map(|x| point * x)
+        .collect()
+    }
+
+    /// The preamble followed by the symbols of `bits`, the last one padded with zeros.
+    pub fn symbols(&self, bits: &[bool]) -> Vec<Complex> {
+        let n = self.scheme.bits_per_symbol();
+        let data = bits.chunks(n).map(|chunk| {
+            let mut chunk = chunk.to_vec();
+            chunk.resize(n, false);
+            self.scheme.map(&chunk)
+        });
+        self.preamble().into_iter().chain(data).collect()
+    }
+
+    pub fn modulate(&self, bits: &[bool]) -> Wave {
+        let Some(sps) = self.sps() else {
+            return vec![];
+        };
+        let symbols = self.symbols(bits);
+        match self.scheme {
+            Scheme::Fsk => {
+                // the demodulator decides a symbol once it has heard all of it, half a symbol
+                // of silence lets it hear the end of the last one
+                let len = ((symbols.len() as f32 + 0.5f32) * sps).ceil() as usize;
+                let mut phase = 0f32;
+                (0..len)
+                    .map(|n| {
+                        let Some(symbol) = symbols.get((n as f32 / sps) as usize) else {
+                            return 0f32;
+                        };
+                        let freq = if symbol.re > 0f32 {
+                            self.mark
+                        } else {
+                            self.space
+                        };
+                        let sample = phase.sin();
+                        phase = (phase + 2f32 * std::f32::consts::PI * freq / SR as f32)
+                            % (2f32 * std::f32::consts::PI);
+                        sample
+                    })
+                    .collect()
+            }
+            _ => {
+                let len = ((symbols.len() + PULSE_SPAN) as f32 * sps).ceil() as usize;
+                let half = (PULSE_SPAN / 2) as f32;
+                let omega = 2f32 * std::f32::consts::PI * self.carrier / SR as f32;
+                (0..len)
+                    .map(|n| {
+                        // in symbols, the first pulse is centered half a span in
+                        let t = n as f32 / sps - half;
+                        let first = (t - half).ceil().max(0f32) as usize;
+                        let last = ((t + half).floor().max(0f32) as usize).min(symbols.len() - 1);
+                        let baseband = (first..=last).fold(Complex::ZERO, |acc, k| {
+                            acc + symbols[k] * rrc(t - k as f32, self.rolloff)
+                        });
+                        let carrier = Complex::from_polar(1f32, omega * n as f32);
+                        (baseband * carrier).re
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// For FSK, the difference of the mark and space powers over the last symbol scaled to
+    /// [-1, 1]. For the others, the baseband after the matched filter, mixed down from
+    /// `carrier`.
+    fn soft(&self, wave: &[f32], sps: f32, carrier: f32) -> Vec<Complex> {
+        let mix = |freq: f32| -> Vec<Complex> {
+            let omega = 2f32 * std::f32::consts::PI * freq / SR as f32;
+            wave.iter()
+                .enumerate()
+                .map(|(n, x)| Complex::from_polar(2f32 * x, -omega * n as f32))
+                .collect()
+        };
+        match self.scheme {
+            Scheme::Fsk => {
+                let window = sps.round().max(1f32) as usize;
+                let power = |freq: f32| {
+                    let terms = mix(freq);
+                    let mut sum = Complex::ZERO;
+                    (0..terms.len())
+                        .map(|n| {
+                            sum += terms[n];
+                            if n >= window {
+                                sum = sum - terms[n - window];
+                            }
+                            sum.norm_sqr()
+                        })
+                        .collect::<Wave>()
+                };
+                let (mark, space) = (power(self.mark), power(self.space));
+                mark.iter()
+                    .zip(space.iter())
+                    .map(|(m, s)| Complex::from((m - s) / (m + s).max(1e-12)))
+                    .collect()
+            }
+            _ => {
+                let len = (PULSE_SPAN as f32 * sps).round() as usize;
+                let center = len as f32 / 2f32;
+                let taps: Wave = (0..=len)
+                    .map(|n| rrc((n as f32 - center) / sps, self.rolloff) / sps)
+                    .collect();
+                let mixed = mix(carrier);
+                let baseband: Vec<Complex> = (0..mixed.len())
+                    .map(|n| {
+                        taps.iter()
+                            .enumerate()
+                            .take(n + 1)
+                            .fold(Complex::ZERO, |acc, (i, tap)| acc + mixed[n - i] * *tap)
+                    })
+                    .collect();
+                let gain = active_power(&baseband).sqrt().max(1e-12);
+                baseband.into_iter().map(|x| x / gain).collect()
+            }
+        }
+    }
+
+    /// Tracks the phase of the carrier through `symbols` starting at the preamble, comparing
+    /// them with the preamble then with the nearest points of the constellation. The loop
+    /// starts from the rotation measured over the preamble, it would slip before settling on
+    /// large ones.
+    fn recover_carrier(&self, symbols: &[Complex], preamble: &[Complex]) -> Vec<Complex> {
+        let (kp, ki) = CARRIER_GAINS;
+        // the symbols were rotated back by half the preamble on average
+        let mut freq = preamble_rotation(symbols, preamble);
+        let mut phase = -freq * (preamble.len() - 1) as f32 / 2f32;
+        symbols
+            .iter()
+            .enumerate()
+            .map(|(k, y)| {
+                let y = *y * Complex::from_polar(1f32, -phase);
+                let point = match preamble.get(k) {
+                    Some(point) => *point,
+                    None => self.scheme.decide(y).0,
+                };
+                let error = (y * point.conj()).im / point.norm_sqr().max(1e-12);
+                freq += ki * error;
+                phase += kp * error + freq;
+                y
+            })
+            .collect()
+    }
+
+    /// The symbols from the start of the preamble, timed, scaled and rotated so the preamble
+    /// matches on average, `None` when it isn't found.
+    fn synchronize(
+        &self,
+        wave: &[f32],
+        sps: f32,
+        carrier: f32,
+        preamble: &[Complex],
+    ) -> Option<Vec<Complex>> {
+        let symbols = recover_timing(&self.soft(wave, sps, carrier), sps);
+        if symbols.len() < preamble.len() {
+            return None;
+        }
+
+        // the preamble is where the symbols look the most like it, their ratio there is the
+        // gain and rotation of the channel
+        let energy: f32 = preamble.iter().map(|x| x.norm_sqr()).sum();
+        let (start, correlation) = (0..=symbols.len() - preamble.len())
+            .map(|m| {
+                let c = preamble
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::ZERO, |acc, (i, p)| acc + symbols[m + i] * p.conj());
+                (m, c)
+            })
+            .max_by(|a, b| a.1.norm_sqr().total_cmp(&b.1.norm_sqr()))
+            .unwrap_or_default();
+        if correlation.norm_sqr() == 0f32 {
+            return None;
+        }
+        let correction = correlation.conj() * (energy / correlation.norm_sqr());
+        Some(symbols[start..].iter().map(|y| *y * correction).collect())
+    }
+
+    /// The data symbols, rotated and scaled to match the constellation, and their bits. Empty
+    /// when the preamble isn't found.
+    pub fn demodulate(&self, wave: &[f32]) -> (Vec<Complex>, Vec<bool>) {
+        let Some(sps) = self.sps() else {
+            return (vec![], vec![]);
+        };
+        let preamble = self.preamble();
+        let Some(mut symbols) = self.synchronize(wave, sps, self.carrier, &preamble) else {
+            return (vec![], vec![]);
+        };
+        if self.scheme != Scheme::Fsk {
+            // a carrier a few Hz off smears the pulses through the matched filter, the
+            // rotation over the preamble tells by how much to mix them down again
+            let offset =
+                preamble_rotation(&symbols, &preamble) * self.baud / (2f32 * std::f32::consts::PI);
+            if let Some(x) = self.synchronize(wave, sps, self.carrier + offset, &preamble) {
+                symbols = x;
+            }
+            symbols = self.recover_carrier(&symbols, &preamble);
+        }
+        let data = symbols[preamble.len()..].to_vec();
+        let bits = data.iter().flat_map(|y| self.scheme.decide(*y).1).collect();
+        (data, bits)
+    }
+}
+
+/// Radians the symbols turn by from one to the next over the preamble at their start.
+fn preamble_rotation(symbols: &[Complex], preamble: &[Complex]) -> f32 {
+    (1..preamble.len())
+        .fold(Complex::ZERO, |acc, k| {
+            let current = symbols[k] * preamble[k].conj();
+            let previous = symbols[k - 1] * preamble[k - 1].conj();
+            acc + current * previous.conj()
+        })
+        .arg()
+}
+
+fn to_bits(wave: &[f32]) -> Vec<bool> {
+    wave.iter().map(|x| *x > 0.5f32).collect()
+}
+
+fn to_wave(bits: &[bool]) -> Wave {
+    bits.iter().map(|x| *x as u8 as f32).collect()
+}
+
+/// Random bits, the same for a given `seed`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomBits {
+    pub count: usize,
+    pub seed: u32,
+}
+
+impl Default for RandomBits {
+    fn default() -> Self {
+        Self {
+            count: 1024,
+            seed: 1,
+        }
+    }
+}
+
+impl Block<()> for RandomBits {
+    type Output = Wave;
+
+    fn process(&mut self, _input: ()) -> Self::Output {
+        let noise = Noise {
+            amplitude: 1f32,
+            seed: self.seed,
+        };
+        noise
+            .samples(self.count)
+            .into_iter()
+            .map(|x| (x > 0f32) as u8 as f32)
+            .collect()
+    }
+
+    fn process_and_visualize(&mut self, input: ()) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        vis::visualize_simple_box(&format!("Bits\n{}", self.count), out)
+    }
+}
+
+/// Turns bits into a wave.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Modulator(pub Modem);
+
+impl Block<Wave> for Modulator {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(&mut self.0)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.0.modulate(&to_bits(&input))
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("{:?}\n{:.0}Bd", self.0.scheme, self.0.baud);
+        vis::visualize_simple_box(&text, out)
+    }
+}
+
+/// Recovers the bits of a [`Modulator`] with the same settings, also outputs the in-phase and
+/// quadrature parts of the data symbols for an `XYView`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Demodulator(pub Modem);
+
+impl Block<Wave> for Demodulator {
+    type Output = (Wave, (Wave, Wave));
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(&mut self.0)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        let (symbols, bits) = self.0.demodulate(&input);
+        (to_wave(&bits), symbols.iter().map(|x| (x.re, x.im)).unzip())
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("Demod\n{:?}\n{} bits", self.0.scheme, out.0.len());
+        vis::visualize_simple_box(&text, out)
+    }
+}
+
+impl Parameters for Modem {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "scheme" => Some(ParamValue::Enum(
+                Scheme::ALL
+                    .iter()
+                    .position(|x| *x == self.scheme)
+                    .unwrap_or_default(),
+            )),
+            "baud" => Some(ParamValue::Float(self.baud)),
+            "carrier" => Some(ParamValue::Float(self.carrier)),
+            "rolloff" => Some(ParamValue::Float(self.rolloff)),
+            "mark" => Some(ParamValue::Float(self.mark)),
+            "space" => Some(ParamValue::Float(self.space)),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, value) {
+            ("scheme", ParamValue::Enum(x)) => self.scheme = Scheme::ALL[x],
+            ("baud", ParamValue::Float(x)) => self.baud = x,
+            ("carrier", ParamValue::Float(x)) => self.carrier = x,
+            ("rolloff", ParamValue::Float(x)) => self.rolloff = x,
+            ("mark", ParamValue::Float(x)) => self.mark = x,
+            ("space", ParamValue::Float(x)) => self.space = x,
+            _ => {}
+        }
+    }
+}
+
+/// Additive white Gaussian noise, `snr` dB under the power of the input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Awgn {
+    /// dB
+    pub snr: f32,
+    pub seed: u32,
+}
+
+impl Default for Awgn {
+    fn default() -> Self {
+        Self {
+            snr: 20f32,
+            seed: 1,
+        }
+    }
+}
+
+impl Block<Wave> for Awgn {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        let power = input.iter().map(|x| x * x).sum::<f32>() / input.len().max(1) as f32;
+        let noise = Noise {
+            amplitude: (power / 10f32.powf(self.snr / 10f32)).sqrt(),
+            seed: self.seed,
+        };
+        input
+            .iter()
+            .zip(noise.samples(input.len()))
+            .map(|(x, n)| x + n)
+            .collect()
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        vis::visualize_simple_box(&format!("AWGN\n{:.1}dB", self.snr), out)
+    }
+}
+
+impl Parameters for Awgn {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[Param::float("snr", Range::linear(-10f32, 60f32), "dB")];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "snr" => Some(ParamValue::Float(self.snr)),
+            _ => None,
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        if let ("snr", Some(x)) = (name, value.as_float()) {
+            self.snr = x;
+        }
+    }
+}
+
+/// Compares the sent bits (first input) with the received ones, outputs 1 for every wrong
+/// or missing bit.
+#[derive(Debug, Default)]
+pub struct BitErrors;
+
+impl Block<(Wave, Wave)> for BitErrors {
+    type Output = Wave;
+
+    fn process(&mut self, (sent, received): (Wave, Wave)) -> Self::Output {
+        sent.iter()
+            .enumerate()
+            .map(|(i, x)| match received.get(i) {
+                Some(y) if (*x > 0.5f32) == (*y > 0.5f32) => 0f32,
+                _ => 1f32,
+            })
+            .collect()
+    }
+
+    fn process_and_visualize(&mut self, input: (Wave, Wave)) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let errors = out.iter().sum::<f32>();
+        let rate = errors / out.len().max(1) as f32;
+        let text = format!("BER\n{rate:.1e}\n{errors}/{}", out.len());
+        vis::visualize_simple_box(&text, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends 4096 random bits through `scheme` with noise at `snr` dB, returns the number of
+    /// received bits and of wrong ones.
+    fn transmit(scheme: Scheme, snr: f32) -> (usize, f32) {
+        transmit_through(scheme, snr, 1f32, 0f32)
+    }
+
+    /// Same as [`transmit`], the receiver's clock running `clock` times as fast as the
+    /// sender's and its carrier `carrier_offset` Hz below.
+    fn transmit_through(scheme: Scheme, snr: f32, clock: f32, carrier_offset: f32) -> (usize, f32) {
+        let bits = RandomBits {
+            count: 4096,
+            seed: 3,
+        }
+        .process(());
+        let modem = Modem::builder().scheme(scheme).build();
+        let sender = Modem {
+            carrier: modem.carrier + carrier_offset,
+            ..modem.clone()
+        };
+        let wave = Modulator(sender).process(bits.clone());
+        let noisy = Awgn { snr, seed: 2 }.process(wave);
+        // linear interpolation, the receiver reads a sample every 1 / clock sent samples
+        let resampled: Wave = (0..((noisy.len() - 1) as f32 * clock) as usize)
+            .map(|n| {
+                let position = n as f32 / clock;
+                let (i, frac) = (position as usize, position.fract());
+                noisy[i] * (1f32 - frac) + noisy[(i + 1).min(noisy.len() - 1)] * frac
+            })
+            .collect();
+        let (received, _) = Demodulator(modem).process(resampled);
+        let errors = BitErrors.process((bits, received.clone()));
+        (received.len(), errors.iter().sum())
+    }
+
+    #[test]
+    fn fsk_bits_survive_noise() {
+        assert_eq!(transmit(Scheme::Fsk, 10f32), (4096, 0f32));
+    }
+
+    #[test]
+    fn bpsk_bits_survive_noise() {
+        assert_eq!(transmit(Scheme::Bpsk, 10f32), (4096, 0f32));
+    }
+
+    #[test]
+    fn qpsk_bits_survive_noise() {
+        assert_eq!(transmit(Scheme::Qpsk, 10f32), (4096, 0f32));
+    }
+
+    #[test]
+    fn qam16_bits_survive_noise() {
+        assert_eq!(transmit(Scheme::Qam16, 10f32), (4096, 0f32));
+        // its points are closer together, more noise makes errors but the loops keep up
+        let (received, errors) = transmit(Scheme::Qam16, 3f32);
+        assert_eq!(received, 4096);
+        assert!(errors > 0f32 && errors / 4096f32 < 0.02f32, "{errors}");
+    }
+
+    #[test]
+    fn loops_follow_clock_and_carrier_offsets() {
+        for scheme in [Scheme::Fsk, Scheme::Bpsk, Scheme::Qpsk, Scheme::Qam16] {
+            for clock in [0.995f32, 1.005f32] {
+                let (received, errors) = transmit_through(scheme, 20f32, clock, 2f32);
+                assert!(received >= 4096, "{scheme:?} at {clock}: {received} bits");
+                assert_eq!(errors, 0f32, "{scheme:?} at {clock}");
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_is_sent_without_a_positive_baud() {
+        for baud in [0f32, -1200f32, f32::NAN] {
+            let modem = Modem::builder().baud(baud).build();
+            assert!(Modulator(modem.clone()).process(vec![1f32; 64]).is_empty());
+            assert!(Demodulator(modem).process(vec![1f32; 4096]).0.is_empty());
+        }
+    }
+}
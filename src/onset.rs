@@ -0,0 +1,517 @@
+//! Where notes start, and the beat they follow.
+//!
+//! An onset detection function, computed from the STFT, rises when something new starts; its
+//! peaks are the onsets. The tempo is the strongest periodicity of that function, and the beats
+//! are the peaks that best follow it, found with dynamic programming (Ellis 2007).
+//!
+//! Both blocks output markers, a wave as long as their input with a 1 at every onset or beat,
+//! that `vis::MarkerView` draws over the input.
+use std::time::Duration;
+
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::{self, Complex};
+use crate::vis::VisualizeResult;
+use crate::widgets::{self, Range};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum OnsetFunction {
+    /// Sum of the magnitudes that increased since the previous frame.
+    #[default]
+    SpectralFlux,
+    /// Energy weighted by frequency, good at percussive onsets.
+    HighFrequencyContent,
+    /// Distance of every bin to the one predicted from the two previous frames with a steady
+    /// magnitude and frequency, catches soft onsets through their phase.
+    ComplexDomain,
+}
+
+impl OnsetFunction {
+    pub const ALL: [OnsetFunction; 3] = [
+        OnsetFunction::SpectralFlux,
+        OnsetFunction::HighFrequencyContent,
+        OnsetFunction::ComplexDomain,
+    ];
+
+    /// One value per frame of `stft`.
+    pub fn detect(&self, stft: &[Vec<Complex>]) -> Wave {
+        (0..stft.len())
+            .map(|n| {
+                let bins = &stft[n];
+                let previous = n.checked_sub(1).map(|i| &stft[i]);
+                match (self, previous) {
+                    (OnsetFunction::SpectralFlux, Some(previous)) => bins
+                        .iter()
+                        .zip(previous.iter())
+                        .map(|(x, y)| (x.norm() - y.norm()).max(0f32))
+                        .sum(),
+                    (OnsetFunction::HighFrequencyContent, _) => {
+                        bins.iter()
+                            .enumerate()
+                            .map(|(k, x)| k as f32 * x.norm_sqr())
+                            .sum::<f32>()
+                            / bins.len() as f32
+                    }
+                    (OnsetFunction::ComplexDomain, Some(previous)) => {
+                        let before = n.checked_sub(2).map(|i| &stft[i]).unwrap_or(previous);
+                        bins.iter()
+                            .zip(previous.iter().zip(before.iter()))
+                            .map(|(x, (y, z))| {
+                                let predicted =
+                                    Complex::from_polar(y.norm(), 2f32 * y.arg() - z.arg());
+                                (*x - predicted).norm()
+                            })
+                            .sum()
+                    }
+                    (_, None) => 0f32,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Frames where `detection` is the highest within 3 frames on both sides and `delta` above
+/// its mean over 10 frames, at least `wait` frames apart. `detection` is scaled to a maximum
+/// of 1 first, so `delta` doesn't depend on the level.
+pub fn pick_peaks(detection: &[f32], delta: f32, wait: usize) -> Vec<usize> {
+    const MAX_FRAMES: usize = 3;
+    const MEAN_FRAMES: usize = 10;
+    let peak = detection.iter().copied().fold(0f32, f32::max);
+    if peak <= 0f32 {
+        return vec![];
+    }
+    let detection: Wave = detection.iter().map(|x| x / peak).collect();
+    let around = |n: usize, frames: usize| {
+        &detection[n.saturating_sub(frames)..(n + frames + 1).min(detection.len())]
+    };
+
+    let mut peaks: Vec<usize> = vec![];
+    for (n, x) in detection.iter().enumerate() {
+        let is_max = around(n, MAX_FRAMES).iter().all(|y| y <= x);
+        let mean = around(n, MEAN_FRAMES).iter().sum::<f32>() / around(n, MEAN_FRAMES).len() as f32;
+        let waited = peaks.last().is_none_or(|last| n - last >= wait);
+        if is_max && *x >= mean + delta && waited {
+            peaks.push(n);
+        }
+    }
+    peaks
+}
+
+/// Impulses at `positions` in a wave of `len` samples.
+fn markers(positions: &[usize], len: usize) -> Wave {
+    let mut wave = vec![0f32; len];
+    for x in positions.iter().filter(|x| **x < len) {
+        wave[*x] = 1f32;
+    }
+    wave
+}
+
+/// Outputs the onsets as markers.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnsetDetector {
+    #[builder(value = default)]
+    pub function: OnsetFunction,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    /// Height over the local mean of the detection function scaled to 1.
+    #[builder(value = 0.1f32)]
+    pub delta: f32,
+    /// Shortest time between two onsets, on top of the 3 frames of peak picking.
+    #[builder(value = default)]
+    #[serde(with = "crate::patch::millis")]
+    pub wait: Duration,
+}
+
+impl Default for OnsetDetector {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl OnsetDetector {
+    const PARAMS: &'static [Param] = &[
+        Param::choice(
+            "function",
+            &["SpectralFlux", "HighFrequencyContent", "ComplexDomain"],
+        ),
+        Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+        Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+        Param::float("delta", Range::linear(0f32, 1f32), ""),
+        Param::duration("wait"),
+    ];
+
+    /// The detection function, one value every `hop_length` samples.
+    pub fn detection(&self, wave: &[f32]) -> Wave {
+        self.function.detect(&spectrum::stft(wave, &self.window))
+    }
+
+    /// Samples where onsets start.
+    pub fn onsets(&self, wave: &[f32]) -> Vec<usize> {
+        let hop_length = self.window.hop_length();
+        let wait = (self.wait.as_secs_f32() * SR as f32 / hop_length as f32).round() as usize;
+        pick_peaks(&self.detection(wave), self.delta, wait)
+            .into_iter()
+            .map(|n| n * hop_length)
+            .collect()
+    }
+}
+
+impl Block<Wave> for OnsetDetector {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        markers(&self.onsets(&input), input.len())
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let onsets = self.onsets(&input);
+        let out = markers(&onsets, input.len());
+        let window = self.window.clone();
+        widgets::visualize_with_widgets(
+            &format!("Onsets\n{:?}\n{} found", self.function, onsets.len()),
+            out,
+            2,
+            move |d| {
+                let (frame_size, hop_length) = WindowSetting::widgets();
+                frame_size.draw(d, window.frame_size() as f32);
+                hop_length.draw(d, window.hop_length() as f32);
+            },
+        )
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for OnsetDetector {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "function" => Some(ParamValue::Enum(
+                OnsetFunction::ALL
+                    .iter()
+                    .position(|x| *x == self.function)
+                    .unwrap_or_default(),
+            )),
+            "delta" => Some(ParamValue::Float(self.delta)),
+            "wait" => Some(ParamValue::Duration(self.wait)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("function", ParamValue::Enum(x)) => self.function = OnsetFunction::ALL[*x],
+            ("delta", ParamValue::Float(x)) => self.delta = *x,
+            ("wait", ParamValue::Duration(x)) => self.wait = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+/// Tempo in BPM of a detection function sampled at `frame_rate`: the lag between `min_bpm` and
+/// `max_bpm` where it correlates best with itself, weighted towards 120 BPM by a log-normal
+/// prior of one octave so its multiples don't win.
+pub fn estimate_tempo(detection: &[f32], frame_rate: f32, min_bpm: f32, max_bpm: f32) -> f32 {
+    let mean = detection.iter().sum::<f32>() / detection.len().max(1) as f32;
+    let centered: Wave = detection.iter().map(|x| x - mean).collect();
+    let min_lag = ((60f32 * frame_rate / max_bpm).floor() as usize).max(1);
+    let max_lag =
+        ((60f32 * frame_rate / min_bpm).ceil() as usize).min(centered.len().saturating_sub(2));
+    if min_lag >= max_lag {
+        return 0f32;
+    }
+
+    let scores: Wave = (0..=max_lag + 1)
+        .map(|lag| {
+            let correlation: f32 = centered
+                .iter()
+                .zip(centered.iter().skip(lag))
+                .map(|(x, y)| x * y)
+                .sum();
+            let bpm = 60f32 * frame_rate / lag.max(1) as f32;
+            correlation * (-0.5f32 * (bpm / 120f32).log2().powi(2)).exp()
+        })
+        .collect();
+    let lag = (min_lag..=max_lag)
+        .max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+        .unwrap_or(min_lag);
+    let (a, b, c) = (scores[lag - 1], scores[lag], scores[lag + 1]);
+    let curvature = a - 2f32 * b + c;
+    let offset = if curvature.abs() > f32::EPSILON {
+        (0.5f32 * (a - c) / curvature).clamp(-1f32, 1f32)
+    } else {
+        0f32
+    };
+    60f32 * frame_rate / (lag as f32 + offset)
+}
+
+/// Frames of the beats: every beat adds the detection function at its frame, and pays
+/// `tightness` times the squared log ratio of its distance to the previous beat and `period`.
+pub fn track_beats(detection: &[f32], period: f32, tightness: f32) -> Vec<usize> {
+    let std = {
+        let mean = detection.iter().sum::<f32>() / detection.len().max(1) as f32;
+        (detection.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / detection.len().max(1) as f32)
+            .sqrt()
+    };
+    if detection.is_empty() || period < 1f32 || std == 0f32 {
+        return vec![];
+    }
+    let local: Wave = detection.iter().map(|x| x / std).collect();
+
+    let (shortest, longest) = (
+        (period / 2f32).round() as usize,
+        (period * 2f32).round() as usize,
+    );
+    let mut score = vec![0f32; local.len()];
+    let mut previous: Vec<Option<usize>> = vec![None; local.len()];
+    for n in 0..local.len() {
+        let best = n.checked_sub(shortest.max(1)).and_then(|last| {
+            (n.saturating_sub(longest)..=last)
+                .map(|p| {
+                    let penalty = tightness * ((n - p) as f32 / period).ln().powi(2);
+                    (p, score[p] - penalty)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+        });
+        score[n] = local[n];
+        if let Some((p, s)) = best.filter(|(_, s)| *s > 0f32) {
+            score[n] += s;
+            previous[n] = Some(p);
+        }
+    }
+
+    // the last beat is the best one in the last period
+    let start = local.len().saturating_sub(period.round() as usize);
+    let mut beat = (start..local.len()).max_by(|a, b| score[*a].total_cmp(&score[*b]));
+    let mut beats = vec![];
+    while let Some(n) = beat {
+        beats.push(n);
+        beat = previous[n];
+    }
+    beats.reverse();
+    beats
+}
+
+/// Estimates the tempo and outputs the beats as markers.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeatTracker {
+    #[builder(value = default)]
+    pub function: OnsetFunction,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    #[builder(value = 40f32)]
+    pub min_bpm: f32,
+    #[builder(value = 240f32)]
+    pub max_bpm: f32,
+    /// How strictly beats follow the tempo.
+    #[builder(value = 100f32)]
+    pub tightness: f32,
+}
+
+impl Default for BeatTracker {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl BeatTracker {
+    const PARAMS: &'static [Param] = &[
+        Param::choice(
+            "function",
+            &["SpectralFlux", "HighFrequencyContent", "ComplexDomain"],
+        ),
+        Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+        Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+        Param::float("min_bpm", Range::linear(20f32, 300f32), "BPM"),
+        Param::float("max_bpm", Range::linear(20f32, 300f32), "BPM"),
+        Param::float("tightness", Range::log(1f32, 1000f32), ""),
+    ];
+
+    /// Tempo in BPM and the samples of the beats.
+    pub fn track(&self, wave: &[f32]) -> (f32, Vec<usize>) {
+        let hop_length = self.window.hop_length();
+        let detection = self.function.detect(&spectrum::stft(wave, &self.window));
+        let frame_rate = SR as f32 / hop_length as f32;
+        let bpm = estimate_tempo(&detection, frame_rate, self.min_bpm, self.max_bpm);
+        if bpm <= 0f32 {
+            return (0f32, vec![]);
+        }
+        let beats = track_beats(&detection, 60f32 * frame_rate / bpm, self.tightness);
+        (bpm, beats.into_iter().map(|n| n * hop_length).collect())
+    }
+}
+
+impl Block<Wave> for BeatTracker {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        markers(&self.track(&input).1, input.len())
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let (bpm, beats) = self.track(&input);
+        let out = markers(&beats, input.len());
+        let window = self.window.clone();
+        widgets::visualize_with_widgets(
+            &format!("Beats\n{bpm:.1} BPM\n{} beats", beats.len()),
+            out,
+            2,
+            move |d| {
+                let (frame_size, hop_length) = WindowSetting::widgets();
+                frame_size.draw(d, window.frame_size() as f32);
+                hop_length.draw(d, window.hop_length() as f32);
+            },
+        )
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for BeatTracker {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "function" => Some(ParamValue::Enum(
+                OnsetFunction::ALL
+                    .iter()
+                    .position(|x| *x == self.function)
+                    .unwrap_or_default(),
+            )),
+            "min_bpm" => Some(ParamValue::Float(self.min_bpm)),
+            "max_bpm" => Some(ParamValue::Float(self.max_bpm)),
+            "tightness" => Some(ParamValue::Float(self.tightness)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("function", ParamValue::Enum(x)) => self.function = OnsetFunction::ALL[*x],
+            ("min_bpm", ParamValue::Float(x)) => self.min_bpm = *x,
+            ("max_bpm", ParamValue::Float(x)) => self.max_bpm = *x,
+            ("tightness", ParamValue::Float(x)) => self.tightness = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decaying 2 kHz bursts every `period` samples, starting one period in.
+    fn clicks(period: f32, len: usize) -> Wave {
+        let mut wave = vec![0f32; len];
+        let mut start = period;
+        while (start as usize) < len {
+            for (i, x) in wave[start as usize..].iter_mut().take(2000).enumerate() {
+                let t = i as f32 / SR as f32;
+                *x = (-t * 200f32).exp() * (2f32 * std::f32::consts::PI * 2000f32 * t).sin();
+            }
+            start += period;
+        }
+        wave
+    }
+
+    #[test]
+    fn peaks_are_local_maxima_above_the_mean() {
+        let mut detection = vec![0.1f32; 100];
+        for (n, x) in [(10, 1f32), (12, 0.8), (50, 0.5), (53, 0.02), (80, 0.15)] {
+            detection[n] = x;
+        }
+        assert_eq!(pick_peaks(&detection, 0.1, 0), vec![10, 50]);
+        assert_eq!(pick_peaks(&detection, 0.6, 0), vec![10]);
+        assert_eq!(pick_peaks(&detection, 0.1, 50), vec![10]);
+        // scaled to 1 first
+        let louder: Wave = detection.iter().map(|x| x * 100f32).collect();
+        assert_eq!(pick_peaks(&louder, 0.1, 0), vec![10, 50]);
+        assert!(pick_peaks(&[0f32; 10], 0.1, 0).is_empty());
+    }
+
+    #[test]
+    fn every_click_is_an_onset() {
+        let period = SR as f32 / 2f32;
+        let wave = clicks(period, SR * 4);
+        for function in OnsetFunction::ALL {
+            let detector = OnsetDetector::builder().function(function).build();
+            let onsets = detector.onsets(&wave);
+            assert_eq!(onsets.len(), 7, "{function:?}: {onsets:?}");
+            for (i, n) in onsets.iter().enumerate() {
+                let click = (i + 1) as f32 * period;
+                assert!(
+                    (*n as f32 - click).abs() <= 1024f32,
+                    "{function:?}: {onsets:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn click_trains_have_their_tempo_and_beats() {
+        let period = 60f32 * SR as f32 / 128f32;
+        let wave = clicks(period, SR * 12);
+        let tracker = BeatTracker::default();
+        let (bpm, beats) = tracker.track(&wave);
+        assert!((bpm - 128f32).abs() < 2f32, "{bpm}");
+
+        let expected = (SR * 12) as f32 / period;
+        assert!(beats.len() as f32 >= expected - 2f32, "{beats:?}");
+        for n in beats.iter() {
+            let offset = *n as f32 % period;
+            let distance = offset.min(period - offset);
+            assert!(distance <= 1024f32, "{n} is {distance} samples off a click");
+        }
+    }
+
+    #[test]
+    fn beats_follow_the_period() {
+        let mut detection = vec![0f32; 400];
+        for n in (20..400).step_by(40) {
+            detection[n] = 1f32;
+        }
+        // a stray peak off the grid is skipped
+        detection[150] = 0.8;
+        let beats = track_beats(&detection, 40f32, 100f32);
+        assert_eq!(beats, (20..400).step_by(40).collect::<Vec<_>>());
+        assert!(track_beats(&[0f32; 100], 40f32, 100f32).is_empty());
+        assert!(track_beats(&detection, 0f32, 100f32).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Named, typed parameters of blocks. The controls drawn on blocks, presets and automation all
+//! go through [`Parameters`] instead of knowing about every block.
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::widgets::Range;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ParamKind {
+    Float {
+        range: Range,
+        unit: &'static str,
+    },
+    /// One of the options, set and read as its index.
+    Enum(&'static [&'static str]),
+    Bool,
+    Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParamKind,
+}
+
+impl Param {
+    pub const fn float(name: &'static str, range: Range, unit: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamKind::Float { range, unit },
+        }
+    }
+
+    pub const fn choice(name: &'static str, options: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            kind: ParamKind::Enum(options),
+        }
+    }
+
+    pub const fn bool(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamKind::Bool,
+        }
+    }
+
+    pub const fn duration(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ParamKind::Duration,
+        }
+    }
+
+    /// Range of a float parameter, `None` for the other kinds.
+    pub fn range(&self) -> Option<Range> {
+        match self.kind {
+            ParamKind::Float { range, .. } => Some(range),
+            _ => None,
+        }
+    }
+
+    /// Number of options of an enum parameter, `None` for the other kinds.
+    pub fn options(&self) -> Option<usize> {
+        match self.kind {
+            ParamKind::Enum(options) => Some(options.len()),
+            _ => None,
+        }
+    }
+
+    /// Checks that the value has the right type, clamping floats to their range.
+    pub fn check(&self, value: ParamValue) -> anyhow::Result<ParamValue> {
+        Ok(match (self.kind, value) {
+            (ParamKind::Float { range, .. }, ParamValue::Float(x)) => {
+                ParamValue::Float(x.clamp(range.min, range.max))
+            }
+            (ParamKind::Enum(options), ParamValue::Enum(x)) if x < options.len() => {
+                ParamValue::Enum(x)
+            }
+            (ParamKind::Enum(options), ParamValue::Enum(x)) => {
+                bail!("{} has {} options, got {x}", self.name, options.len())
+            }
+            (ParamKind::Bool, x @ ParamValue::Bool(_)) => x,
+            (ParamKind::Duration, x @ ParamValue::Duration(_)) => x,
+            (kind, value) => bail!("{} is {kind:?}, got {value:?}", self.name),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParamValue {
+    Float(f32),
+    Enum(usize),
+    Bool(bool),
+    Duration(#[serde(with = "crate::patch::millis")] Duration),
+}
+
+impl ParamValue {
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            ParamValue::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_enum(&self) -> Option<usize> {
+        match self {
+            ParamValue::Enum(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ParamValue::Bool(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            ParamValue::Duration(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+pub trait Parameters {
+    fn params(&self) -> &'static [Param];
+
+    /// `None` when the parameter doesn't exist or doesn't have a value yet (e.g. oscillator
+    /// parameters that come from the input before anything was processed).
+    fn get(&self, name: &str) -> Option<ParamValue>;
+
+    /// Implementations get a value already checked by [`Param::check`], see
+    /// [`Parameters::set`].
+    fn set_checked(&mut self, name: &str, value: ParamValue);
+
+    fn param(&self, name: &str) -> Option<&'static Param> {
+        self.params().iter().find(|x| x.name == name)
+    }
+
+    fn set(&mut self, name: &str, value: ParamValue) -> anyhow::Result<()> {
+        let param = self
+            .param(name)
+            .ok_or_else(|| anyhow!("unknown parameter {name:?}"))?;
+        let value = param.check(value)?;
+        self.set_checked(name, value);
+        Ok(())
+    }
+
+    /// Values of every parameter, e.g. to save a preset.
+    fn snapshot(&self) -> Vec<(String, ParamValue)> {
+        self.params()
+            .iter()
+            .filter_map(|x| Some((x.name.to_string(), self.get(x.name)?)))
+            .collect()
+    }
+
+    fn restore(&mut self, values: &[(String, ParamValue)]) -> anyhow::Result<()> {
+        for (name, value) in values {
+            self.set(name, value.clone())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::synths::Oscillator;
+    use crate::granular::{GrainWindow, Granular};
+
+    #[test]
+    fn values_are_checked_against_their_kind() -> anyhow::Result<()> {
+        let gain = Param::float("gain", Range::linear(-2f32, 2f32), "");
+        assert_eq!(
+            gain.check(ParamValue::Float(5f32))?,
+            ParamValue::Float(2f32)
+        );
+        assert_eq!(
+            gain.check(ParamValue::Float(-5f32))?,
+            ParamValue::Float(-2f32)
+        );
+        assert_eq!(gain.check(ParamValue::Float(0.5))?, ParamValue::Float(0.5));
+        assert!(gain.check(ParamValue::Enum(0)).is_err());
+
+        let wave = Param::choice("wave", &["Sine", "Square"]);
+        assert_eq!(wave.check(ParamValue::Enum(1))?, ParamValue::Enum(1));
+        assert!(wave.check(ParamValue::Enum(2)).is_err());
+        assert!(wave.check(ParamValue::Float(1f32)).is_err());
+
+        let wait = Param::duration("wait");
+        let value = ParamValue::Duration(Duration::from_millis(10));
+        assert_eq!(wait.check(value.clone())?, value);
+        assert!(wait.check(ParamValue::Bool(true)).is_err());
+        assert!(Param::bool("on").check(ParamValue::Bool(false)).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn snapshots_restore_blocks() -> anyhow::Result<()> {
+        let mut granular = Granular::default();
+        granular.set("density", ParamValue::Float(2000f32))?;
+        assert_eq!(granular.density, 1000f32);
+        granular.set("window", ParamValue::Enum(2))?;
+        assert_eq!(granular.window, GrainWindow::Gaussian);
+        granular.set(
+            "grain_size",
+            ParamValue::Duration(Duration::from_millis(20)),
+        )?;
+        assert!(granular.set("window", ParamValue::Enum(4)).is_err());
+        assert!(granular.set("density", ParamValue::Enum(0)).is_err());
+        assert!(granular.set("volume", ParamValue::Float(1f32)).is_err());
+        // failed sets leave the value alone
+        assert_eq!(granular.window, GrainWindow::Gaussian);
+
+        let snapshot = granular.snapshot();
+        assert_eq!(snapshot.len(), granular.params().len());
+        let mut restored = Granular::default();
+        restored.restore(&snapshot)?;
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.grain_size, Duration::from_millis(20));
+
+        // parameters without a value yet are left out
+        let oscillator = Oscillator::default();
+        assert!(oscillator.snapshot().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+//! Text (RON) descriptions of dynamic graphs.
+//!
+//! ```ron
+//! (
+//!     nodes: [
+//!         (name: "controls", block: Constant(Oscillator((freq: 110.0, phase: 0.0, duration: 230.0, wave: Sinusoid)))),
+//!         (name: "osc", block: Oscillator, automation: [
+//!             (param: "freq", curve: Breakpoints(points: [(time: 0.0, value: 110.0), (time: 230.0, value: 880.0)], interpolation: Exponential)),
+//!         ]),
+//!         (name: "gain", block: ConstMultiplier(0.5)),
+//!         (name: "lpf", block: Biquad((t: LowPass)), params: [("cutoff", Float(800.0))]),
+//!         (name: "view", block: WaveView(t: Small, inputs: 1)),
+//!     ],
+//!     edges: [
+//!         ("controls.out", "osc.in"),
+//!         ("osc.out", "gain.in"),
+//!         ("gain.out", "lpf.in"),
+//!         ("lpf.out", "view.in"),
+//!     ],
+//! )
+//! ```
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::automation::{Automated, Lane};
+use crate::design::Design;
+use crate::dsp::blocks::*;
+use crate::dtmf::{DtmfDecoder, DtmfEncoder};
+use crate::dyn_graph::{default_port_names, Constant, DynBlock, Embedded, Graph, Value};
+use crate::features::FeatureExtractor;
+use crate::goertzel::Goertzel;
+use crate::granular::Granular;
+use crate::graph::Discard;
+use crate::hilbert::{FrequencyShift, Hilbert, Instantaneous, Ssb};
+use crate::loudness::{Meter, Normalize};
+use crate::modem::{Awgn, BitErrors, Demodulator, Modem, Modulator, RandomBits};
+use crate::onset::{BeatTracker, OnsetDetector};
+use crate::params::ParamValue;
+use crate::pitch::PitchDetector;
+use crate::stretch::{PitchShift, TimeStretch, Wsola};
+use crate::transfer::TransferFunction;
+use crate::vis::{self, OutputConfig, WaveViewType};
+use crate::wav::{WavReader, WavWriter};
+
+/// (De)serializes a `Duration` as floating point milliseconds.
+pub mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(value.as_secs_f64() * 1000f64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let ms = f64::deserialize(deserializer)?;
+        Ok(Duration::from_secs_f64(ms / 1000f64))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PadSide {
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BasicOp {
+    Mix,
+    Amp,
+    Diff,
+}
+
+/// Every block that can be created from a patch. Blocks generic over the number of waves
+/// they take (`Basic`, `AutoPad`, `WaveView`) accept between 1 and 4 inputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BlockDesc {
+    Constant(Value),
+    Oscillator,
+    KroneckerDelta(synths::KroneckerDelta),
+    HeavisideStep,
+    Sweep(synths::Sweep),
+    Multitone(synths::Multitone),
+    Mls(synths::Mls),
+    ImpulseTrain(synths::ImpulseTrain),
+    Noise(synths::Noise),
+    KarplusStrong(synths::KarplusStrong),
+    DtmfEncoder(DtmfEncoder),
+    RandomBits(RandomBits),
+    AutoPad {
+        side: PadSide,
+        inputs: usize,
+    },
+    Basic {
+        op: BasicOp,
+        inputs: usize,
+    },
+    Envelope(EnvelopeBlock),
+    ConstMultiplier(f32),
+    Delay(usize),
+    Biquad(Biquad),
+    TransferFunction(TransferFunction),
+    Filter(Design),
+    Pitch(PitchDetector),
+    Onsets(OnsetDetector),
+    Beats(BeatTracker),
+    Features(FeatureExtractor),
+    Goertzel(Goertzel),
+    DtmfDecoder(DtmfDecoder),
+    Normalize(Normalize),
+    Modulator(Modem),
+    Awgn(Awgn),
+    Demodulator(Modem),
+    BitErrors,
+    Hilbert,
+    Instantaneous(Instantaneous),
+    Ssb(Ssb),
+    FrequencyShift(FrequencyShift),
+    TimeStretch(TimeStretch),
+    PitchShift(PitchShift),
+    Wsola(Wsola),
+    Granular(Granular),
+    ModalBank(synths::ModalBank),
+    Identity,
+    WaveView {
+        t: WaveViewType,
+        inputs: usize,
+    },
+    MarkerView,
+    XYView,
+    Meter,
+    WavReader(String),
+    /// A relative path goes in the output directory, see [`OutputConfig::path`].
+    WavWriter(String),
+    AudioSink,
+    Discard,
+}
+
+macro_rules! with_arity {
+    ($inputs:expr, $name:literal, |$n:ident, $t:ident| $build:expr) => {
+        match $inputs {
+            1 => {
+                const $n: usize = 1;
+                type $t = Wave;
+                Box::new($build) as Box<dyn DynBlock>
+            }
+            2 => {
+                const $n: usize = 2;
+                type $t = (Wave, Wave);
+                Box::new($build) as Box<dyn DynBlock>
+            }
+            3 => {
+                const $n: usize = 3;
+                type $t = ((Wave, Wave), Wave);
+                Box::new($build) as Box<dyn DynBlock>
+            }
+            4 => {
+                const $n: usize = 4;
+                type $t = (((Wave, Wave), Wave), Wave);
+                Box::new($build) as Box<dyn DynBlock>
+            }
+            n => bail!("{} takes between 1 and 4 inputs, got {n}", $name),
+        }
+    };
+}
+
+impl BlockDesc {
+    /// Files and sinks go where `output` says.
+    pub fn build(&self, output: &OutputConfig) -> anyhow::Result<Box<dyn DynBlock>> {
+        let block: Box<dyn DynBlock> = match self {
+            BlockDesc::Constant(x) => Box::new(Constant(x.clone())),
+            BlockDesc::Oscillator => Box::new(Embedded::new(synths::Oscillator::default())),
+            BlockDesc::KroneckerDelta(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::HeavisideStep => Box::new(Embedded::new(synths::HeavisideStep)),
+            BlockDesc::Sweep(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Multitone(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Mls(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::ImpulseTrain(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Noise(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::KarplusStrong(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::DtmfEncoder(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::RandomBits(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::AutoPad { side, inputs } => {
+                with_arity!(*inputs, "AutoPad", |N, I| Embedded::<_, I, I>::new(
+                    match side {
+                        PadSide::Start => AutoPad::<N>::Start,
+                        PadSide::End => AutoPad::<N>::End,
+                    }
+                ))
+            }
+            BlockDesc::Basic { op, inputs } => {
+                with_arity!(*inputs, "Basic", |N, I| Embedded::<_, I, Wave>::new(
+                    match op {
+                        BasicOp::Mix => Basic::<N>::Mix,
+                        BasicOp::Amp => Basic::<N>::Amp,
+                        BasicOp::Diff => Basic::<N>::Diff,
+                    }
+                ))
+            }
+            BlockDesc::Envelope(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::ConstMultiplier(x) => Box::new(Embedded::new(ConstMultiplier(*x))),
+            BlockDesc::Delay(x) => Box::new(Embedded::new(Delay(*x))),
+            BlockDesc::Biquad(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::TransferFunction(x) => Box::new(Embedded::new(TransferFunction::new(
+                x.b.clone(),
+                x.a.clone(),
+            )?)),
+            BlockDesc::Filter(x) => Box::new(Embedded::new(x.sos()?)),
+            BlockDesc::Pitch(x) => Box::new(Embedded::<_, Wave, (Wave, Wave)>::new(x.clone())),
+            BlockDesc::Onsets(x) => Box::new(Embedded::<_, Wave, Wave>::new(x.clone())),
+            BlockDesc::Beats(x) => Box::new(Embedded::<_, Wave, Wave>::new(x.clone())),
+            BlockDesc::Features(x) => Box::new(Embedded::<_, Wave, Wave>::new(
+                x.clone().with_output(output),
+            )),
+            BlockDesc::Goertzel(x) => Box::new(Embedded::<_, Wave, Wave>::new(x.clone())),
+            BlockDesc::DtmfDecoder(x) => Box::new(Embedded::<_, Wave, Wave>::new(x.clone())),
+            BlockDesc::Normalize(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Modulator(x) => Box::new(Embedded::new(Modulator(x.clone()))),
+            BlockDesc::Awgn(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Demodulator(x) => Box::new(Embedded::new(Demodulator(x.clone()))),
+            BlockDesc::BitErrors => Box::new(Embedded::new(BitErrors)),
+            BlockDesc::Hilbert => Box::new(Embedded::new(Hilbert)),
+            BlockDesc::Instantaneous(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Ssb(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::FrequencyShift(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::TimeStretch(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::PitchShift(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Wsola(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Granular(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::ModalBank(x) => Box::new(Embedded::new(x.clone())),
+            BlockDesc::Identity => Box::new(Embedded::<_, Wave, Wave>::new(vis::Identity)),
+            BlockDesc::WaveView { t, inputs } => {
+                with_arity!(*inputs, "WaveView", |N, I| Embedded::<_, I, I>::new(
+                    vis::WaveView::<N>::builder().t(t.clone()).build()
+                ))
+            }
+            BlockDesc::MarkerView => Box::new(Embedded::<_, (Wave, Wave), (Wave, Wave)>::new(
+                vis::MarkerView,
+            )),
+            BlockDesc::XYView => Box::new(Embedded::new(vis::XYView)),
+            BlockDesc::Meter => Box::new(Embedded::<_, Wave, Wave>::new(Meter)),
+            BlockDesc::WavReader(path) => Box::new(Embedded::new(WavReader::new(path)?)),
+            BlockDesc::WavWriter(path) => {
+                Box::new(Embedded::new(WavWriter::new(output.path(path))?))
+            }
+            BlockDesc::AudioSink => Box::new(Embedded::new(output.audio_sink()?)),
+            BlockDesc::Discard => Box::new(Embedded::<_, Wave, ()>::new(Discard)),
+        };
+        Ok(block)
+    }
+
+    /// Builds a block with some of its parameters automated, only for the blocks implementing
+    /// [`Automatable`](crate::automation::Automatable).
+    pub fn build_automated(&self, lanes: &[Lane]) -> anyhow::Result<Box<dyn DynBlock>> {
+        let lanes = lanes.to_vec();
+        let block: Box<dyn DynBlock> = match self {
+            BlockDesc::Oscillator => {
+                Box::new(Embedded::<_, synths::OscillatorControls, Wave>::new(
+                    Automated::new(synths::Oscillator::default(), lanes)?,
+                ))
+            }
+            BlockDesc::ConstMultiplier(x) => Box::new(Embedded::<_, Wave, Wave>::new(
+                Automated::new(ConstMultiplier(*x), lanes)?,
+            )),
+            BlockDesc::Biquad(x) => Box::new(Embedded::<_, Wave, Wave>::new(Automated::new(
+                x.clone(),
+                lanes,
+            )?)),
+            x => bail!("{x:?} can't be automated"),
+        };
+        Ok(block)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDesc {
+    pub name: String,
+    pub block: BlockDesc,
+    /// Port names, defaults to `in`/`out` or `in0, in1, ...` when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<String>>,
+    /// Parameter values set after building the block, see [`Parameters`](crate::params::Parameters).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<(String, ParamValue)>,
+    /// Parameters changing over time, see [`Lane`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub automation: Vec<Lane>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Patch {
+    pub nodes: Vec<NodeDesc>,
+    /// `("node.port", "node.port")` pairs going from an output to an input.
+    pub edges: Vec<(String, String)>,
+    #[serde(default)]
+    pub colored: bool,
+}
+
+impl Patch {
+    pub fn load<T: AsRef<Path>>(path: T) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading {}", path.as_ref().display()))?;
+        Self::parse(&text).with_context(|| format!("parsing {}", path.as_ref().display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_text()?)?;
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn build(&self, output: &OutputConfig) -> anyhow::Result<Graph> {
+        let mut graph = Graph::new();
+        for node in self.nodes.iter() {
+            let block = if node.automation.is_empty() {
+                node.block.build(output)
+            } else {
+                node.block.build_automated(&node.automation)
+            };
+            let block = block.with_context(|| format!("building node {:?}", node.name))?;
+            let inputs = node
+                .inputs
+                .clone()
+                .unwrap_or_else(|| default_port_names("in", block.input_arity()));
+            let outputs = node
+                .outputs
+                .clone()
+                .unwrap_or_else(|| default_port_names("out", block.output_arity()));
+            graph.add_dyn_node(&node.name, block, inputs, outputs)?;
+            if !node.params.is_empty() {
+                graph
+                    .parameters(&node.name)?
+                    .restore(&node.params)
+                    .with_context(|| format!("setting parameters of node {:?}", node.name))?;
+            }
+        }
+        for (from, to) in self.edges.iter() {
+            graph.connect(from, to)?;
+        }
+        graph.validate()?;
+        Ok(if self.colored { graph.colored() } else { graph })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYGROUND: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/patches/playground.ron");
+
+    #[test]
+    fn playground_patch_builds_and_round_trips() -> anyhow::Result<()> {
+        let output = OutputConfig::render(std::env::temp_dir());
+        let patch = Patch::load(PLAYGROUND)?;
+        patch.build(&output)?;
+        let text = patch.to_text()?;
+        let reparsed = Patch::parse(&text)?;
+        assert_eq!(reparsed.nodes.len(), patch.nodes.len());
+        assert_eq!(reparsed.edges, patch.edges);
+        assert_eq!(reparsed.to_text()?, text);
+        reparsed.build(&output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn transfer_functions_need_a_nonzero_leading_pole_coefficient() -> anyhow::Result<()> {
+        let desc =
+            |a: &str| ron::from_str::<BlockDesc>(&format!("TransferFunction((b: [1.0], a: {a}))"));
+        let output = OutputConfig::default();
+        assert!(desc("[2.0, 0.5]")?.build(&output).is_ok());
+        assert!(desc("[0.0, 0.5]")?.build(&output).is_err());
+        assert!(desc("[]")?.build(&output).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,591 @@
+//! Fundamental frequency over time, one estimate per frame of a [`WindowSetting`].
+//!
+//! Every method looks for the period of the frame between those of `max_freq` and `min_freq`,
+//! at most half the frame: frames have to hold two periods of the lowest pitch, e.g. 4096
+//! samples for A0.
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::{Wave, SR};
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::{self, Complex};
+use crate::vis::VisualizeResult;
+use crate::widgets::{self, Range};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum PitchMethod {
+    /// Cumulative mean normalized difference, de Cheveigné and Kawahara 2002.
+    #[default]
+    Yin,
+    /// Probabilistic YIN, Mauch and Dixon 2014: the YIN candidates of many thresholds, tracked
+    /// across frames with a hidden Markov model that also decides which frames are unvoiced.
+    Pyin,
+    /// Normalized autocorrelation, the shortest lag with a peak close to the highest one.
+    Autocorrelation,
+    /// Peak of the real cepstrum, the period of the harmonics in the log spectrum.
+    Cepstrum,
+}
+
+impl PitchMethod {
+    pub const ALL: [PitchMethod; 4] = [
+        PitchMethod::Yin,
+        PitchMethod::Pyin,
+        PitchMethod::Autocorrelation,
+        PitchMethod::Cepstrum,
+    ];
+}
+
+/// Outputs the pitch in Hz and a confidence between 0 and 1 for every frame.
+///
+/// Only pYIN marks frames as unvoiced, with a pitch of 0; the other methods always give their
+/// best guess and leave it to the confidence, except for frames too short to search any
+/// period, which get 0 for both.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PitchDetector {
+    #[builder(value = default)]
+    pub method: PitchMethod,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    #[builder(value = 40f32)]
+    pub min_freq: f32,
+    #[builder(value = 2000f32)]
+    pub max_freq: f32,
+    /// Absolute threshold of YIN, the first dip under it is the period.
+    #[builder(value = 0.1f32)]
+    pub threshold: f32,
+}
+
+impl Default for PitchDetector {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Offset of the extremum of the parabola through `y[i - 1]`, `y[i]` and `y[i + 1]`.
+fn parabolic(y: &[f32], i: usize) -> f32 {
+    if i == 0 || i + 1 >= y.len() {
+        return 0f32;
+    }
+    let (a, b, c) = (y[i - 1], y[i], y[i + 1]);
+    let curvature = a - 2f32 * b + c;
+    if curvature.abs() < f32::EPSILON {
+        0f32
+    } else {
+        (0.5f32 * (a - c) / curvature).clamp(-1f32, 1f32)
+    }
+}
+
+/// Products of the start of a frame with itself shifted by every lag up to `max_lag`.
+struct Lagged {
+    /// `Σ x[j] x[j + τ]`
+    correlation: Vec<f32>,
+    /// `Σ x[j]^2`
+    energy: f32,
+    /// `Σ x[j + τ]^2`
+    shifted_energy: Vec<f32>,
+}
+
+impl Lagged {
+    /// Sums over the first `frame.len() - max_lag` samples, the correlation with an FFT.
+    ///
+    /// `None` if the frame doesn't even hold `max_lag + 1` samples.
+    fn new(frame: &[f32], max_lag: usize) -> Option<Self> {
+        if frame.len() <= max_lag {
+            return None;
+        }
+        let len = frame.len() - max_lag;
+        let head: Wave = frame[..len].iter().rev().copied().collect();
+        let full = spectrum::convolve(&head, frame);
+        let mut prefix = vec![0f32; frame.len() + 1];
+        for (i, x) in frame.iter().enumerate() {
+            prefix[i + 1] = prefix[i] + x * x;
+        }
+        Some(Self {
+            correlation: (0..=max_lag).map(|t| full[len - 1 + t]).collect(),
+            energy: prefix[len],
+            shifted_energy: (0..=max_lag).map(|t| prefix[t + len] - prefix[t]).collect(),
+        })
+    }
+
+    /// `Σ (x[j] - x[j + τ])^2`
+    fn difference(&self) -> Wave {
+        self.correlation
+            .iter()
+            .zip(self.shifted_energy.iter())
+            .map(|(c, e)| (self.energy + e - 2f32 * c).max(0f32))
+            .collect()
+    }
+
+    fn normalized_correlation(&self) -> Wave {
+        self.correlation
+            .iter()
+            .zip(self.shifted_energy.iter())
+            .map(|(c, e)| {
+                let norm = (self.energy * e).sqrt();
+                if norm > 0f32 {
+                    c / norm
+                } else {
+                    0f32
+                }
+            })
+            .collect()
+    }
+}
+
+/// Each difference divided by the mean of the ones at shorter lags, 1 at lag 0.
+fn cumulative_mean_normalized(difference: &[f32]) -> Wave {
+    let mut sum = 0f32;
+    difference
+        .iter()
+        .enumerate()
+        .map(|(t, d)| {
+            sum += d;
+            if t == 0 || sum == 0f32 {
+                1f32
+            } else {
+                d * t as f32 / sum
+            }
+        })
+        .collect()
+}
+
+/// First lag from `min_lag` with `cmnd` under `threshold`, moved to the bottom of its dip.
+fn first_dip(cmnd: &[f32], min_lag: usize, threshold: f32) -> Option<usize> {
+    let mut t = (min_lag..cmnd.len()).find(|t| cmnd[*t] < threshold)?;
+    while t + 1 < cmnd.len() && cmnd[t + 1] < cmnd[t] {
+        t += 1;
+    }
+    Some(t)
+}
+
+/// Beta(2, 18) distribution of the pYIN thresholds, with a mean of 0.1.
+fn pyin_thresholds() -> Vec<(f32, f32)> {
+    const COUNT: usize = 100;
+    let thresholds: Vec<f32> = (1..=COUNT).map(|i| i as f32 / COUNT as f32).collect();
+    let density: Vec<f32> = thresholds.iter().map(|s| s * (1f32 - s).powi(17)).collect();
+    let total: f32 = density.iter().sum();
+    thresholds
+        .into_iter()
+        .zip(density.into_iter().map(|x| x / total))
+        .collect()
+}
+
+/// Resolution and range of the pitches pYIN tracks.
+const PYIN_BINS_PER_SEMITONE: f32 = 10f32;
+const PYIN_MAX_JUMP: usize = 25;
+const PYIN_SWITCH: f32 = 0.01f32;
+
+impl PitchDetector {
+    const PARAMS: &'static [Param] = &[
+        Param::choice("method", &["YIN", "pYIN", "Autocorrelation", "Cepstrum"]),
+        Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+        Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+        Param::float("min_freq", Range::log(10f32, 5000f32), "Hz"),
+        Param::float("max_freq", Range::log(20f32, 20000f32), "Hz"),
+        Param::float("threshold", Range::linear(0.01f32, 1f32), ""),
+    ];
+
+    /// Range of the periods searched, in samples.
+    fn lags(&self) -> (usize, usize) {
+        let max_lag = ((SR as f32 / self.min_freq).ceil() as usize)
+            .min(self.window.frame_size() / 2)
+            .max(2);
+        let min_lag = ((SR as f32 / self.max_freq).floor() as usize).clamp(1, max_lag - 1);
+        (min_lag, max_lag)
+    }
+
+    /// `(pitch, confidence)` of every frame.
+    pub fn track(&self, wave: &[f32]) -> Vec<(f32, f32)> {
+        let frames = self.window.frames(wave);
+        match self.method {
+            PitchMethod::Yin => frames.map(|x| self.yin(&x)).collect(),
+            PitchMethod::Pyin => self.pyin(frames.map(|x| self.pyin_candidates(&x)).collect()),
+            PitchMethod::Autocorrelation => frames.map(|x| self.autocorrelation(&x)).collect(),
+            PitchMethod::Cepstrum => frames.map(|x| self.cepstrum(&x)).collect(),
+        }
+    }
+
+    fn yin(&self, frame: &[f32]) -> (f32, f32) {
+        let (min_lag, max_lag) = self.lags();
+        let Some(lagged) = Lagged::new(frame, max_lag + 1) else {
+            return (0f32, 0f32);
+        };
+        let difference = lagged.difference();
+        let cmnd = cumulative_mean_normalized(&difference);
+        let t = first_dip(&cmnd[..=max_lag], min_lag, self.threshold).unwrap_or_else(|| {
+            // no dip deep enough, the lowest one
+            (min_lag..=max_lag)
+                .min_by(|a, b| cmnd[*a].total_cmp(&cmnd[*b]))
+                .unwrap_or(min_lag)
+        });
+        let period = t as f32 + parabolic(&difference, t);
+        (SR as f32 / period, (1f32 - cmnd[t]).clamp(0f32, 1f32))
+    }
+
+    fn autocorrelation(&self, frame: &[f32]) -> (f32, f32) {
+        const TOLERANCE: f32 = 0.9f32;
+        let (min_lag, max_lag) = self.lags();
+        let Some(lagged) = Lagged::new(frame, max_lag + 1) else {
+            return (0f32, 0f32);
+        };
+        let nccf = lagged.normalized_correlation();
+        let peaks: Vec<usize> = (min_lag.max(1)..=max_lag)
+            .filter(|t| nccf[*t] > nccf[t - 1] && nccf[*t] >= nccf[t + 1])
+            .collect();
+        let highest = peaks.iter().map(|t| nccf[*t]).fold(0f32, f32::max);
+        match peaks.into_iter().find(|t| nccf[*t] >= highest * TOLERANCE) {
+            Some(t) => {
+                let period = t as f32 + parabolic(&nccf, t);
+                (SR as f32 / period, nccf[t].clamp(0f32, 1f32))
+            }
+            None => (0f32, 0f32),
+        }
+    }
+
+    /// Needs harmonics, a pure sine has no period in its spectrum. The confidence is how much
+    /// the peak stands out of the cepstrum around it.
+    fn cepstrum(&self, frame: &[f32]) -> (f32, f32) {
+        const FLOOR: f32 = 0.01f32;
+        const TOLERANCE: f32 = 0.7f32;
+        let (min_lag, max_lag) = self.lags();
+        let n = frame.len().next_power_of_two();
+        let windowed: Wave = frame
+            .iter()
+            .zip(spectrum::hann(frame.len()))
+            .map(|(x, w)| x * w)
+            .collect();
+        // floored under the peak, the noise between harmonics would hide their period
+        let bins = spectrum::rfft(&windowed, n);
+        let floor = bins.iter().map(Complex::norm).fold(0f32, f32::max) * FLOOR + 1e-10f32;
+        let log_spectrum: Vec<Complex> = bins
+            .iter()
+            .map(|x| Complex::from(x.norm().max(floor).ln()))
+            .collect();
+        let cepstrum = spectrum::irfft(&log_spectrum, n);
+
+        let max_lag = max_lag.min((n / 2).saturating_sub(1));
+        if max_lag <= min_lag {
+            return (0f32, 0f32);
+        }
+        // multiples of the period (rahmonics) can be as high as the period itself
+        let peaks: Vec<usize> = (min_lag.max(1)..=max_lag)
+            .filter(|t| cepstrum[*t] > cepstrum[t - 1] && cepstrum[*t] >= cepstrum[t + 1])
+            .collect();
+        let highest = peaks.iter().map(|t| cepstrum[*t]).fold(0f32, f32::max);
+        let t = peaks
+            .into_iter()
+            .find(|t| cepstrum[*t] >= highest * TOLERANCE)
+            .unwrap_or(min_lag);
+        let rms = ((min_lag..=max_lag)
+            .map(|t| cepstrum[t].powi(2))
+            .sum::<f32>()
+            / (max_lag + 1 - min_lag) as f32)
+            .sqrt();
+        let period = t as f32 + parabolic(&cepstrum, t);
+        let confidence = if cepstrum[t] > 0f32 {
+            (1f32 - rms / cepstrum[t]).clamp(0f32, 1f32)
+        } else {
+            0f32
+        };
+        (SR as f32 / period, confidence)
+    }
+
+    /// `(pitch, probability)` of the dips found by the pYIN thresholds, the probabilities sum
+    /// to at most 1.
+    fn pyin_candidates(&self, frame: &[f32]) -> Vec<(f32, f32)> {
+        let (min_lag, max_lag) = self.lags();
+        let Some(lagged) = Lagged::new(frame, max_lag + 1) else {
+            return vec![];
+        };
+        let difference = lagged.difference();
+        let cmnd = cumulative_mean_normalized(&difference);
+        let mut probabilities = vec![0f32; max_lag + 1];
+        for (threshold, weight) in pyin_thresholds() {
+            if let Some(t) = first_dip(&cmnd[..=max_lag], min_lag, threshold) {
+                probabilities[t] += weight;
+            }
+        }
+        probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| **p > 0f32)
+            .map(|(t, p)| (SR as f32 / (t as f32 + parabolic(&difference, t)), *p))
+            .collect()
+    }
+
+    /// Viterbi decoding over pitch bins plus an unvoiced state. Pitches move by at most
+    /// `PYIN_MAX_JUMP` bins between frames, preferably less, and switching between voiced and
+    /// unvoiced costs `PYIN_SWITCH`.
+    fn pyin(&self, candidates: Vec<Vec<(f32, f32)>>) -> Vec<(f32, f32)> {
+        if candidates.is_empty() {
+            return vec![];
+        }
+        let bin = |freq: f32| {
+            (12f32 * PYIN_BINS_PER_SEMITONE * (freq / self.min_freq).log2()).round() as isize
+        };
+        let bins = (bin(self.max_freq).max(0) + 1) as usize;
+        let unvoiced = bins;
+        let floor = 1e-9f32.ln();
+
+        // emissions: the most likely candidate of every bin, and the unvoiced probability
+        let emissions: Vec<(Vec<Option<(f32, f32)>>, f32)> = candidates
+            .iter()
+            .map(|candidates| {
+                let mut voiced = vec![None::<(f32, f32)>; bins];
+                for (freq, p) in candidates {
+                    let b = bin(*freq);
+                    if b >= 0 && (b as usize) < bins {
+                        let slot = &mut voiced[b as usize];
+                        let total = slot.map(|(_, q)| q).unwrap_or_default() + p;
+                        let best = match slot {
+                            Some((f, q)) if *q >= *p => *f,
+                            _ => *freq,
+                        };
+                        *slot = Some((best, total));
+                    }
+                }
+                let voicing: f32 = candidates.iter().map(|(_, p)| p).sum();
+                (voiced, (1f32 - voicing).max(0f32))
+            })
+            .collect();
+        let emission = |frame: usize, state: usize| {
+            let (voiced, unvoiced_p) = &emissions[frame];
+            let p = if state == unvoiced {
+                *unvoiced_p
+            } else {
+                voiced[state].map(|(_, p)| p).unwrap_or_default()
+            };
+            if p > 0f32 {
+                p.ln()
+            } else {
+                floor
+            }
+        };
+
+        // triangular transition weights over the jump in bins
+        let jumps: Vec<f32> = (0..=PYIN_MAX_JUMP)
+            .map(|j| (PYIN_MAX_JUMP + 1 - j) as f32)
+            .collect();
+        let total = jumps[0] + 2f32 * jumps[1..].iter().sum::<f32>();
+        let stay_voiced: Vec<f32> = jumps
+            .iter()
+            .map(|w| (w / total * (1f32 - PYIN_SWITCH)).ln())
+            .collect();
+        let (to_unvoiced, stay_unvoiced, to_voiced) = (
+            PYIN_SWITCH.ln(),
+            (1f32 - PYIN_SWITCH).ln(),
+            (PYIN_SWITCH / bins as f32).ln(),
+        );
+
+        let mut scores: Vec<f32> = (0..=bins)
+            .map(|s| emission(0, s) - ((bins + 1) as f32).ln())
+            .collect();
+        let mut back: Vec<Vec<usize>> = vec![];
+        for frame in 1..emissions.len() {
+            let mut next = vec![f32::NEG_INFINITY; bins + 1];
+            let mut from = vec![0; bins + 1];
+            for s in 0..bins {
+                let low = s.saturating_sub(PYIN_MAX_JUMP);
+                let high = (s + PYIN_MAX_JUMP).min(bins - 1);
+                for p in low..=high {
+                    let score = scores[p] + stay_voiced[p.abs_diff(s)];
+                    if score > next[s] {
+                        (next[s], from[s]) = (score, p);
+                    }
+                }
+                let score = scores[unvoiced] + to_voiced;
+                if score > next[s] {
+                    (next[s], from[s]) = (score, unvoiced);
+                }
+                next[s] += emission(frame, s);
+            }
+            for p in 0..=bins {
+                let score = scores[p]
+                    + if p == unvoiced {
+                        stay_unvoiced
+                    } else {
+                        to_unvoiced
+                    };
+                if score > next[unvoiced] {
+                    (next[unvoiced], from[unvoiced]) = (score, p);
+                }
+            }
+            next[unvoiced] += emission(frame, unvoiced);
+            scores = next;
+            back.push(from);
+        }
+
+        let mut state = (0..=bins)
+            .max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+            .unwrap_or(unvoiced);
+        let mut path = vec![state; emissions.len()];
+        for (frame, from) in back.iter().enumerate().rev() {
+            state = from[state];
+            path[frame] = state;
+        }
+
+        path.into_iter()
+            .zip(emissions.iter())
+            .map(|(state, (voiced, unvoiced_p))| {
+                let voicing = 1f32 - unvoiced_p;
+                match voiced.get(state).copied().flatten() {
+                    Some((freq, _)) => (freq, voicing),
+                    None => (0f32, voicing),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Block<Wave> for PitchDetector {
+    type Output = (Wave, Wave);
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.track(&input).into_iter().unzip()
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let (pitch, confidence) = self.process(input);
+        let mut voiced: Vec<f32> = pitch.iter().copied().filter(|x| *x > 0f32).collect();
+        voiced.sort_by(f32::total_cmp);
+        let median = voiced.get(voiced.len() / 2).copied().unwrap_or_default();
+        let window = self.window.clone();
+        widgets::visualize_with_widgets(
+            &format!("Pitch\n{:?}\n{median:.1}Hz", self.method),
+            (pitch, confidence),
+            2,
+            move |d| {
+                let (frame_size, hop_length) = WindowSetting::widgets();
+                frame_size.draw(d, window.frame_size() as f32);
+                hop_length.draw(d, window.hop_length() as f32);
+            },
+        )
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for PitchDetector {
+    fn params(&self) -> &'static [Param] {
+        Self::PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "method" => Some(ParamValue::Enum(
+                PitchMethod::ALL
+                    .iter()
+                    .position(|x| *x == self.method)
+                    .unwrap_or_default(),
+            )),
+            "min_freq" => Some(ParamValue::Float(self.min_freq)),
+            "max_freq" => Some(ParamValue::Float(self.max_freq)),
+            "threshold" => Some(ParamValue::Float(self.threshold)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("method", ParamValue::Enum(x)) => self.method = PitchMethod::ALL[*x],
+            ("min_freq", ParamValue::Float(x)) => self.min_freq = *x,
+            ("max_freq", ParamValue::Float(x)) => self.max_freq = *x,
+            ("threshold", ParamValue::Float(x)) => self.threshold = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::dsp::blocks::synths::{Oscillator, OscillatorControls, WaveType};
+
+    /// A0, E0 and C#0, the lowest notes of a piano and of 5 and 6 string basses.
+    const NOTES: [f32; 3] = [27.5f32, 20.601722, 17.323914];
+
+    fn oscillator(wave: WaveType, freq: f32) -> Wave {
+        Oscillator::default().process(OscillatorControls {
+            freq,
+            phase: 0f32,
+            duration: Duration::from_secs(1),
+            wave,
+        })
+    }
+
+    fn detector(method: PitchMethod) -> PitchDetector {
+        PitchDetector::builder()
+            .method(method)
+            .window(
+                WindowSetting::builder()
+                    .frame_size(8192)
+                    .hop_length(4096)
+                    .build(),
+            )
+            .min_freq(15f32)
+            .build()
+    }
+
+    /// Every frame that doesn't run past the end of `wave` is within a cent of `freq`.
+    fn assert_within_a_cent(method: PitchMethod, wave: &[f32], freq: f32) {
+        let detector = detector(method);
+        let frames = (wave.len() - detector.window.frame_size()) / detector.window.hop_length();
+        for (pitch, _) in detector.track(wave).into_iter().take(frames + 1) {
+            let cents = 1200f32 * (pitch / freq).log2();
+            assert!(cents.abs() < 1f32, "{method:?}: {pitch}Hz for {freq}Hz");
+        }
+    }
+
+    #[test]
+    fn low_notes_are_detected_within_a_cent() {
+        for freq in NOTES {
+            let sine = oscillator(WaveType::Sinusoid, freq);
+            for method in [
+                PitchMethod::Yin,
+                PitchMethod::Pyin,
+                PitchMethod::Autocorrelation,
+            ] {
+                assert_within_a_cent(method, &sine, freq);
+            }
+            let harmonic = oscillator(WaveType::Sawtooth, freq);
+            assert_within_a_cent(PitchMethod::Cepstrum, &harmonic, freq);
+        }
+    }
+
+    #[test]
+    fn frames_too_short_for_a_period_are_unvoiced() {
+        let sine = oscillator(WaveType::Sinusoid, 440f32);
+        for method in PitchMethod::ALL {
+            let detector = PitchDetector::builder()
+                .method(method)
+                .window(
+                    WindowSetting::builder()
+                        .frame_size(2)
+                        .hop_length(512)
+                        .build(),
+                )
+                .build();
+            for (pitch, confidence) in detector.track(&sine) {
+                assert_eq!((pitch, confidence), (0f32, 0f32), "{method:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,950 @@
+//! Backend agnostic drawing: blocks record their visualization into a [`Canvas`] which is
+//! later replayed by a [`Backend`] (raylib for the window, SVG and PNG for files).
+use std::fmt::Write;
+
+use raylib::color::Color;
+use raylib::math::{Rectangle, Vector2};
+use raylib::prelude::RaylibDraw;
+
+/// raylib's default font can't be drawn smaller than this.
+pub const MIN_FONT_SIZE: i32 = 10;
+
+#[derive(Debug, Clone)]
+pub enum DrawCommand {
+    Line {
+        start: Vector2,
+        end: Vector2,
+        thick: f32,
+        color: Color,
+    },
+    Bezier {
+        start: Vector2,
+        end: Vector2,
+        thick: f32,
+        color: Color,
+    },
+    /// Connected line segments, used for waveforms so they stay a single SVG element.
+    LineStrip {
+        points: Vec<Vector2>,
+        thick: f32,
+        color: Color,
+    },
+    RectangleLines {
+        rec: Rectangle,
+        thick: f32,
+        color: Color,
+    },
+    /// Filled rectangle, used for the cells of heatmaps.
+    Rectangle { rec: Rectangle, color: Color },
+    TriangleLines {
+        v1: Vector2,
+        v2: Vector2,
+        v3: Vector2,
+        color: Color,
+    },
+    CircleLines {
+        center: Vector2,
+        radius: f32,
+        color: Color,
+    },
+    Text {
+        text: String,
+        pos: Vector2,
+        font_size: i32,
+        color: Color,
+    },
+}
+
+impl DrawCommand {
+    fn translate(&mut self, offset: Vector2) {
+        match self {
+            DrawCommand::Line { start, end, .. } | DrawCommand::Bezier { start, end, .. } => {
+                *start += offset;
+                *end += offset;
+            }
+            DrawCommand::LineStrip { points, .. } => {
+                for point in points.iter_mut() {
+                    *point += offset;
+                }
+            }
+            DrawCommand::RectangleLines { rec, .. } | DrawCommand::Rectangle { rec, .. } => {
+                rec.x += offset.x;
+                rec.y += offset.y;
+            }
+            DrawCommand::TriangleLines { v1, v2, v3, .. } => {
+                *v1 += offset;
+                *v2 += offset;
+                *v3 += offset;
+            }
+            DrawCommand::CircleLines { center, .. } => *center += offset,
+            DrawCommand::Text { pos, .. } => *pos += offset,
+        }
+    }
+}
+
+/// A display list with a size. The drawing methods mirror the ones of `RaylibDraw`.
+#[derive(Debug, Clone, Default)]
+pub struct Canvas {
+    pub width: f32,
+    pub height: f32,
+    pub commands: Vec<DrawCommand>,
+
+    /// Added to everything drawn, see [`Canvas::translated`].
+    origin: Vector2,
+}
+
+impl Canvas {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            commands: vec![],
+            origin: Vector2::zero(),
+        }
+    }
+
+    /// Runs `f` with everything it draws moved by `offset`.
+    pub fn translated<T>(&mut self, offset: Vector2, f: impl FnOnce(&mut Self) -> T) -> T {
+        let origin = self.origin;
+        self.origin += offset;
+        let result = f(self);
+        self.origin = origin;
+        result
+    }
+
+    fn push(&mut self, mut command: DrawCommand) {
+        command.translate(self.origin);
+        self.commands.push(command);
+    }
+
+    pub fn draw_line_ex(&mut self, start: Vector2, end: Vector2, thick: f32, color: Color) {
+        self.push(DrawCommand::Line {
+            start,
+            end,
+            thick,
+            color,
+        });
+    }
+
+    pub fn draw_line_bezier(&mut self, start: Vector2, end: Vector2, thick: f32, color: Color) {
+        self.push(DrawCommand::Bezier {
+            start,
+            end,
+            thick,
+            color,
+        });
+    }
+
+    pub fn draw_line_strip(&mut self, points: Vec<Vector2>, thick: f32, color: Color) {
+        self.push(DrawCommand::LineStrip {
+            points,
+            thick,
+            color,
+        });
+    }
+
+    pub fn draw_rectangle_lines_ex(&mut self, rec: Rectangle, thick: f32, color: Color) {
+        self.push(DrawCommand::RectangleLines { rec, thick, color });
+    }
+
+    pub fn draw_rectangle_rec(&mut self, rec: Rectangle, color: Color) {
+        self.push(DrawCommand::Rectangle { rec, color });
+    }
+
+    pub fn draw_triangle_lines(&mut self, v1: Vector2, v2: Vector2, v3: Vector2, color: Color) {
+        self.push(DrawCommand::TriangleLines { v1, v2, v3, color });
+    }
+
+    pub fn draw_circle_lines(&mut self, center: Vector2, radius: f32, color: Color) {
+        self.push(DrawCommand::CircleLines {
+            center,
+            radius,
+            color,
+        });
+    }
+
+    pub fn draw_text(&mut self, text: &str, x: i32, y: i32, font_size: i32, color: Color) {
+        self.push(DrawCommand::Text {
+            text: text.to_string(),
+            pos: Vector2::new(x as f32, y as f32),
+            font_size,
+            color,
+        });
+    }
+}
+
+pub trait Backend {
+    fn draw(&mut self, command: &DrawCommand, offset: Vector2);
+
+    fn draw_canvas(&mut self, canvas: &Canvas, offset: Vector2) {
+        for command in canvas.commands.iter() {
+            self.draw(command, offset);
+        }
+    }
+}
+
+pub struct RaylibBackend<'a, D>(pub &'a mut D);
+
+impl<D: RaylibDraw> Backend for RaylibBackend<'_, D> {
+    fn draw(&mut self, command: &DrawCommand, offset: Vector2) {
+        let d = &mut *self.0;
+        match command {
+            DrawCommand::Line {
+                start,
+                end,
+                thick,
+                color,
+            } => d.draw_line_ex(*start + offset, *end + offset, *thick, *color),
+            DrawCommand::Bezier {
+                start,
+                end,
+                thick,
+                color,
+            } => d.draw_line_bezier(*start + offset, *end + offset, *thick, *color),
+            DrawCommand::LineStrip {
+                points,
+                thick,
+                color,
+            } => {
+                for segment in points.windows(2) {
+                    d.draw_line_ex(segment[0] + offset, segment[1] + offset, *thick, *color);
+                }
+            }
+            DrawCommand::RectangleLines { rec, thick, color } => d.draw_rectangle_lines_ex(
+                Rectangle {
+                    x: rec.x + offset.x,
+                    y: rec.y + offset.y,
+                    ..*rec
+                },
+                *thick,
+                *color,
+            ),
+            DrawCommand::Rectangle { rec, color } => d.draw_rectangle_rec(
+                Rectangle {
+                    x: rec.x + offset.x,
+                    y: rec.y + offset.y,
+                    ..*rec
+                },
+                *color,
+            ),
+            DrawCommand::TriangleLines { v1, v2, v3, color } => {
+                d.draw_triangle_lines(*v1 + offset, *v2 + offset, *v3 + offset, *color)
+            }
+            DrawCommand::CircleLines {
+                center,
+                radius,
+                color,
+            } => d.draw_circle_lines(
+                (center.x + offset.x) as _,
+                (center.y + offset.y) as _,
+                *radius,
+                *color,
+            ),
+            DrawCommand::Text {
+                text,
+                pos,
+                font_size,
+                color,
+            } => d.draw_text(
+                text,
+                (pos.x + offset.x) as _,
+                (pos.y + offset.y) as _,
+                *font_size,
+                *color,
+            ),
+        }
+    }
+}
+
+/// Renders canvases as SVG, doesn't need a window or a GPU.
+#[derive(Debug, Default)]
+pub struct SvgBackend {
+    body: String,
+}
+
+fn svg_paint(attribute: &str, color: &Color) -> String {
+    format!(
+        "{attribute}=\"rgb({},{},{})\" {attribute}-opacity=\"{:.3}\"",
+        color.r,
+        color.g,
+        color.b,
+        color.a as f32 / 255f32
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl SvgBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps everything drawn so far in an `<svg>` document, the background is drawn
+    /// unless it is fully transparent.
+    pub fn finish(self, width: f32, height: f32, background: Color) -> String {
+        let background = if background.a > 0 {
+            format!(
+                "<rect width=\"100%\" height=\"100%\" {}/>\n",
+                svg_paint("fill", &background)
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n{background}{}</svg>\n",
+            self.body
+        )
+    }
+}
+
+impl Backend for SvgBackend {
+    fn draw(&mut self, command: &DrawCommand, offset: Vector2) {
+        let _ = match command {
+            DrawCommand::Line {
+                start,
+                end,
+                thick,
+                color,
+            } => writeln!(
+                self.body,
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"{thick}\" {}/>",
+                start.x + offset.x,
+                start.y + offset.y,
+                end.x + offset.x,
+                end.y + offset.y,
+                svg_paint("stroke", color)
+            ),
+            DrawCommand::Bezier {
+                start,
+                end,
+                thick,
+                color,
+            } => {
+                let (start, end) = (*start + offset, *end + offset);
+                let mid_x = (start.x + end.x) / 2f32;
+                writeln!(
+                    self.body,
+                    "<path d=\"M {} {} C {mid_x} {}, {mid_x} {}, {} {}\" fill=\"none\" stroke-width=\"{thick}\" {}/>",
+                    start.x,
+                    start.y,
+                    start.y,
+                    end.y,
+                    end.x,
+                    end.y,
+                    svg_paint("stroke", color)
+                )
+            }
+            DrawCommand::LineStrip {
+                points,
+                thick,
+                color,
+            } => {
+                let points = points
+                    .iter()
+                    .map(|p| format!("{},{}", p.x + offset.x, p.y + offset.y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(
+                    self.body,
+                    "<polyline points=\"{points}\" fill=\"none\" stroke-width=\"{thick}\" {}/>",
+                    svg_paint("stroke", color)
+                )
+            }
+            DrawCommand::RectangleLines { rec, thick, color } => writeln!(
+                self.body,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke-width=\"{thick}\" {}/>",
+                rec.x + offset.x + thick / 2f32,
+                rec.y + offset.y + thick / 2f32,
+                rec.width - thick,
+                rec.height - thick,
+                svg_paint("stroke", color)
+            ),
+            DrawCommand::Rectangle { rec, color } => writeln!(
+                self.body,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+                rec.x + offset.x,
+                rec.y + offset.y,
+                rec.width,
+                rec.height,
+                svg_paint("fill", color)
+            ),
+            DrawCommand::TriangleLines { v1, v2, v3, color } => writeln!(
+                self.body,
+                "<polygon points=\"{},{} {},{} {},{}\" fill=\"none\" {}/>",
+                v1.x + offset.x,
+                v1.y + offset.y,
+                v2.x + offset.x,
+                v2.y + offset.y,
+                v3.x + offset.x,
+                v3.y + offset.y,
+                svg_paint("stroke", color)
+            ),
+            DrawCommand::CircleLines {
+                center,
+                radius,
+                color,
+            } => writeln!(
+                self.body,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{radius}\" fill=\"none\" {}/>",
+                center.x + offset.x,
+                center.y + offset.y,
+                svg_paint("stroke", color)
+            ),
+            DrawCommand::Text {
+                text,
+                pos,
+                font_size,
+                color,
+            } => {
+                let font_size = (*font_size).max(MIN_FONT_SIZE);
+                let x = pos.x + offset.x;
+                let mut y = pos.y + offset.y;
+                let mut result = Ok(());
+                for line in text.split('\n') {
+                    result = result.and(writeln!(
+                        self.body,
+                        "<text x=\"{x}\" y=\"{y}\" font-family=\"monospace\" font-size=\"{font_size}\" dominant-baseline=\"hanging\" {}>{}</text>",
+                        svg_paint("fill", color),
+                        escape(line)
+                    ));
+                    y += font_size as f32;
+                }
+                result
+            }
+        };
+    }
+}
+
+/// Renders a canvas to a standalone SVG document. The output only depends on the canvas,
+/// so exported diagrams can be checked in and diffed.
+pub fn to_svg(canvas: &Canvas, background: Color) -> String {
+    let mut backend = SvgBackend::new();
+    backend.draw_canvas(canvas, Vector2::zero());
+    backend.finish(canvas.width, canvas.height, background)
+}
+
+/// Renders canvases into an RGBA image in memory, doesn't need a window or a GPU. Shapes are
+/// antialiased over one pixel, texts use a built-in 5x7 pixel font.
+#[derive(Debug, Clone)]
+pub struct RasterBackend {
+    pub width: usize,
+    pub height: usize,
+    /// RGBA rows from the top, not premultiplied.
+    pub pixels: Vec<u8>,
+}
+
+/// Columns of the glyphs from ' ' to '~', the lowest bit at the top.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5f, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12],
+    [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50],
+    [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1c, 0x00],
+    [0x14, 0x08, 0x3e, 0x08, 0x14],
+    [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e],
+    [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46],
+    [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3c, 0x4a, 0x49, 0x49, 0x30],
+    [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x06, 0x49, 0x49, 0x29, 0x1e],
+    [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00],
+    [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08],
+    [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e],
+    [0x7e, 0x11, 0x11, 0x11, 0x7e],
+    [0x7f, 0x49, 0x49, 0x49, 0x36],
+    [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c],
+    [0x7f, 0x49, 0x49, 0x49, 0x41],
+    [0x7f, 0x09, 0x09, 0x09, 0x01],
+    [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f],
+    [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01],
+    [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40],
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+    [0x7f, 0x04, 0x08, 0x10, 0x7f],
+    [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06],
+    [0x3e, 0x41, 0x51, 0x21, 0x5e],
+    [0x7f, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01],
+    [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f],
+    [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x07, 0x08, 0x70, 0x08, 0x07],
+    [0x61, 0x51, 0x49, 0x45, 0x43],
+    [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20],
+    [0x00, 0x41, 0x41, 0x7f, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00],
+    [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7f, 0x48, 0x44, 0x44, 0x38],
+    [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f],
+    [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x08, 0x7e, 0x09, 0x01, 0x02],
+    [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78],
+    [0x00, 0x44, 0x7d, 0x40, 0x00],
+    [0x20, 0x40, 0x44, 0x3d, 0x00],
+    [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00],
+    [0x7c, 0x04, 0x18, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78],
+    [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08],
+    [0x08, 0x14, 0x14, 0x18, 0x7c],
+    [0x7c, 0x08, 0x04, 0x04, 0x08],
+    [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20],
+    [0x3c, 0x40, 0x40, 0x20, 0x7c],
+    [0x1c, 0x20, 0x40, 0x20, 0x1c],
+    [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44],
+    [0x0c, 0x50, 0x50, 0x50, 0x3c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44],
+    [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+/// Points along the curve raylib draws for `draw_line_bezier`, the same as the SVG one.
+fn bezier_points(start: Vector2, end: Vector2) -> Vec<Vector2> {
+    const SEGMENTS: usize = 24;
+    let mid_x = (start.x + end.x) / 2f32;
+    let controls = [
+        start,
+        Vector2::new(mid_x, start.y),
+        Vector2::new(mid_x, end.y),
+        end,
+    ];
+    (0..=SEGMENTS)
+        .map(|i| {
+            let t = i as f32 / SEGMENTS as f32;
+            let weights = [
+                (1f32 - t).powi(3),
+                3f32 * (1f32 - t).powi(2) * t,
+                3f32 * (1f32 - t) * t * t,
+                t.powi(3),
+            ];
+            controls
+                .iter()
+                .zip(weights)
+                .fold(Vector2::zero(), |sum, (p, w)| sum + *p * w)
+        })
+        .collect()
+}
+
+impl RasterBackend {
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        let pixel = [background.r, background.g, background.b, background.a];
+        Self {
+            width,
+            height,
+            pixels: pixel.repeat(width * height),
+        }
+    }
+
+    /// Draws `color` over the pixel at `(x, y)`, `coverage` being the fraction of the pixel
+    /// inside the shape.
+    fn blend(&mut self, x: i64, y: i64, color: &Color, coverage: f32) {
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return;
+        };
+        if x >= self.width || y >= self.height || coverage <= 0f32 {
+            return;
+        }
+        let pixel = &mut self.pixels[(y * self.width + x) * 4..][..4];
+        let alpha = color.a as f32 / 255f32 * coverage.min(1f32);
+        let below = pixel[3] as f32 / 255f32 * (1f32 - alpha);
+        let out = alpha + below;
+        for (channel, source) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            let mixed = (source as f32 * alpha + *channel as f32 * below) / out.max(1e-6);
+            *channel = mixed.round() as u8;
+        }
+        pixel[3] = (out * 255f32).round() as u8;
+    }
+
+    /// Pixels within `thick / 2` of the segment.
+    fn segment(&mut self, start: Vector2, end: Vector2, thick: f32, color: &Color) {
+        let reach = thick / 2f32 + 1f32;
+        let (x0, x1) = (start.x.min(end.x) - reach, start.x.max(end.x) + reach);
+        let (y0, y1) = (start.y.min(end.y) - reach, start.y.max(end.y) + reach);
+        let direction = end - start;
+        let length_sqr = direction.dot(direction);
+        for y in y0.floor() as i64..y1.ceil() as i64 {
+            for x in x0.floor() as i64..x1.ceil() as i64 {
+                let p = Vector2::new(x as f32 + 0.5f32, y as f32 + 0.5f32);
+                let t = if length_sqr > 0f32 {
+                    ((p - start).dot(direction) / length_sqr).clamp(0f32, 1f32)
+                } else {
+                    0f32
+                };
+                let distance = (p - (start + direction * t)).length();
+                let coverage = (thick / 2f32 + 0.5f32 - distance).clamp(0f32, 1f32);
+                self.blend(x, y, color, coverage);
+            }
+        }
+    }
+
+    fn fill(&mut self, rec: Rectangle, color: &Color) {
+        let overlap = |a: f32, b: f32, pixel: i64| {
+            (b.min(pixel as f32 + 1f32) - a.max(pixel as f32)).clamp(0f32, 1f32)
+        };
+        let (x1, y1) = (rec.x + rec.width, rec.y + rec.height);
+        for y in rec.y.floor() as i64..y1.ceil() as i64 {
+            for x in rec.x.floor() as i64..x1.ceil() as i64 {
+                let coverage = overlap(rec.x, x1, x) * overlap(rec.y, y1, y);
+                self.blend(x, y, color, coverage);
+            }
+        }
+    }
+
+    fn circle(&mut self, center: Vector2, radius: f32, color: &Color) {
+        let reach = radius + 1f32;
+        for y in (center.y - reach).floor() as i64..(center.y + reach).ceil() as i64 {
+            for x in (center.x - reach).floor() as i64..(center.x + reach).ceil() as i64 {
+                let p = Vector2::new(x as f32 + 0.5f32, y as f32 + 0.5f32);
+                let distance = ((p - center).length() - radius).abs();
+                self.blend(x, y, color, 1f32 - distance);
+            }
+        }
+    }
+
+    /// Scales the 5x7 font so a line is `font_size` pixels high like raylib's default font,
+    /// characters it doesn't have are drawn as '?'.
+    fn text(&mut self, text: &str, pos: Vector2, font_size: i32, color: &Color) {
+        let scale = font_size.max(MIN_FONT_SIZE) as f32 / 10f32;
+        for (row, line) in text.split('\n').enumerate() {
+            let top = pos.y + (row * 10 + 1) as f32 * scale;
+            for (i, c) in line.chars().enumerate() {
+                let glyph = FONT[(c as usize)
+                    .checked_sub(' ' as usize)
+                    .filter(|x| *x < FONT.len())
+                    .unwrap_or('?' as usize - ' ' as usize)];
+                let left = pos.x + (i * 6) as f32 * scale;
+                for (column, bits) in glyph.iter().enumerate() {
+                    for bit in (0..7).filter(|bit| bits & (1 << bit) != 0) {
+                        let rec = Rectangle::new(
+                            left + column as f32 * scale,
+                            top + bit as f32 * scale,
+                            scale,
+                            scale,
+                        );
+                        self.fill(rec, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The image as a PNG file, its pixel data in stored (uncompressed) deflate blocks.
+    pub fn to_png(&self) -> Vec<u8> {
+        fn crc32(bytes: &[u8]) -> u32 {
+            let mut crc = !0u32;
+            for byte in bytes {
+                crc ^= *byte as u32;
+                for _ in 0..8 {
+                    crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+                }
+            }
+            !crc
+        }
+        fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+            png.extend((data.len() as u32).to_be_bytes());
+            let start = png.len();
+            png.extend(kind);
+            png.extend(data);
+            let crc = crc32(&png[start..]);
+            png.extend(crc.to_be_bytes());
+        }
+
+        // every row starts with its filter type, 0 for none
+        let raw: Vec<u8> = self
+            .pixels
+            .chunks(self.width * 4)
+            .flat_map(|row| std::iter::once(0u8).chain(row.iter().copied()))
+            .collect();
+        let mut zlib = vec![0x78, 0x01];
+        let blocks = raw.chunks(u16::MAX as usize);
+        let count = blocks.len();
+        for (i, block) in blocks.enumerate() {
+            zlib.push((i + 1 == count) as u8);
+            let len = block.len() as u16;
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend(block);
+        }
+        if count == 0 {
+            zlib.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        let (a, b) = raw.iter().fold((1u32, 0u32), |(a, b), x| {
+            let a = (a + *x as u32) % 65521;
+            (a, (b + a) % 65521)
+        });
+        zlib.extend(((b << 16) | a).to_be_bytes());
+
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, no filtering, not interlaced
+        header.extend([8, 6, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib);
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+impl Backend for RasterBackend {
+    fn draw(&mut self, command: &DrawCommand, offset: Vector2) {
+        match command {
+            DrawCommand::Line {
+                start,
+                end,
+                thick,
+                color,
+            } => self.segment(*start + offset, *end + offset, *thick, color),
+            DrawCommand::Bezier {
+                start,
+                end,
+                thick,
+                color,
+            } => {
+                for segment in bezier_points(*start + offset, *end + offset).windows(2) {
+                    self.segment(segment[0], segment[1], *thick, color);
+                }
+            }
+            DrawCommand::LineStrip {
+                points,
+                thick,
+                color,
+            } => {
+                for segment in points.windows(2) {
+                    self.segment(segment[0] + offset, segment[1] + offset, *thick, color);
+                }
+            }
+            DrawCommand::RectangleLines { rec, thick, color } => {
+                let (x, y) = (rec.x + offset.x, rec.y + offset.y);
+                let thick = thick.min(rec.width / 2f32).min(rec.height / 2f32);
+                let inner = rec.height - 2f32 * thick;
+                for side in [
+                    Rectangle::new(x, y, rec.width, thick),
+                    Rectangle::new(x, y + rec.height - thick, rec.width, thick),
+                    Rectangle::new(x, y + thick, thick, inner),
+                    Rectangle::new(x + rec.width - thick, y + thick, thick, inner),
+                ] {
+                    self.fill(side, color);
+                }
+            }
+            DrawCommand::Rectangle { rec, color } => self.fill(
+                Rectangle {
+                    x: rec.x + offset.x,
+                    y: rec.y + offset.y,
+                    ..*rec
+                },
+                color,
+            ),
+            DrawCommand::TriangleLines { v1, v2, v3, color } => {
+                let (v1, v2, v3) = (*v1 + offset, *v2 + offset, *v3 + offset);
+                for (start, end) in [(v1, v2), (v2, v3), (v3, v1)] {
+                    self.segment(start, end, 1f32, color);
+                }
+            }
+            DrawCommand::CircleLines {
+                center,
+                radius,
+                color,
+            } => self.circle(*center + offset, *radius, color),
+            DrawCommand::Text {
+                text,
+                pos,
+                font_size,
+                color,
+            } => self.text(text, *pos + offset, *font_size, color),
+        }
+    }
+}
+
+/// Renders a canvas to a PNG image, without a window so it also works on machines without a
+/// display.
+pub fn to_png(canvas: &Canvas, background: Color) -> Vec<u8> {
+    let (width, height) = (canvas.width.ceil() as usize, canvas.height.ceil() as usize);
+    let mut backend = RasterBackend::new(width, height, background);
+    backend.draw_canvas(canvas, Vector2::zero());
+    backend.to_png()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::Delay;
+    use crate::graph::{Block, CanConnect, CanStack};
+    use crate::vis::{BG_COLOR, BORDER_COLOR, BOX_SIZE, LINE_COLORS, T};
+
+    fn draw<I>(mut block: impl Block<I>, input: I) -> Canvas {
+        block.process_and_visualize(input).1.into_canvas().unwrap()
+    }
+
+    /// Top left corners of the outlined rectangles and the texts, in drawing order.
+    fn boxes(canvas: &Canvas) -> (Vec<Vector2>, Vec<(&str, Vector2)>) {
+        let mut rectangles = vec![];
+        let mut texts = vec![];
+        for command in canvas.commands.iter() {
+            match command {
+                DrawCommand::RectangleLines { rec, .. } => {
+                    assert_eq!((rec.width, rec.height), (BOX_SIZE, BOX_SIZE));
+                    rectangles.push(Vector2::new(rec.x, rec.y));
+                }
+                DrawCommand::Text { text, pos, .. } => texts.push((text.as_str(), *pos)),
+                _ => {}
+            }
+        }
+        (rectangles, texts)
+    }
+
+    #[test]
+    fn connected_blocks_are_side_by_side() {
+        let canvas = draw(Delay(1).connect(Delay(2)), vec![0f32; 8]);
+        let gap = T * 10f32;
+        assert_eq!(
+            (canvas.width, canvas.height),
+            (BOX_SIZE * 2f32 + gap, BOX_SIZE)
+        );
+
+        let (rectangles, texts) = boxes(&canvas);
+        let second = Vector2::new(BOX_SIZE + gap, 0f32);
+        assert_eq!(rectangles, vec![Vector2::zero(), second]);
+        assert_eq!(texts[0].0, "Delay\nz^-1");
+        assert_eq!(texts[1].0, "Delay\nz^-2");
+        assert_eq!(texts[1].1 - texts[0].1, second);
+
+        // one edge from the output of the first box to the input of the second
+        let center = (BOX_SIZE / 2f32).trunc();
+        let edges: Vec<_> = canvas
+            .commands
+            .iter()
+            .filter_map(|x| match x {
+                DrawCommand::Bezier { start, end, .. } => Some((*start, *end)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![(
+                Vector2::new(BOX_SIZE, center),
+                Vector2::new(BOX_SIZE + gap, center)
+            )]
+        );
+    }
+
+    #[test]
+    fn stacked_blocks_are_one_under_the_other() {
+        let canvas = draw(Delay(1).stack(Delay(2)), (vec![0f32; 8], vec![0f32; 8]));
+        let gap = T * 3f32;
+        assert_eq!(
+            (canvas.width, canvas.height),
+            (BOX_SIZE, BOX_SIZE * 2f32 + gap)
+        );
+
+        let (rectangles, texts) = boxes(&canvas);
+        let second = Vector2::new(0f32, BOX_SIZE + gap);
+        assert_eq!(rectangles, vec![Vector2::zero(), second]);
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts[1].1 - texts[0].1, second);
+        assert!(canvas
+            .commands
+            .iter()
+            .all(|x| !matches!(x, DrawCommand::Bezier { .. })));
+    }
+
+    #[test]
+    fn diagrams_export_to_svg() {
+        let chain = Delay(1)
+            .stack(Delay(2))
+            .connect(Delay(3).stack(Delay(4)))
+            .colored();
+        let canvas = draw(chain, (vec![0f32; 8], vec![0f32; 8]));
+        let svg = to_svg(&canvas, BG_COLOR);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("<rect width=\"100%\" height=\"100%\" fill=\"rgb(0,0,0)\""));
+        // the boxes, inset by half their border
+        let border = svg_paint("stroke", &BORDER_COLOR);
+        let boxes = svg
+            .lines()
+            .filter(|x| x.starts_with("<rect x=") && x.contains(&border))
+            .count();
+        assert_eq!(boxes, 4);
+        assert!(svg.contains("<rect x=\"1\" y=\"1\" width=\"53\" height=\"53\" fill=\"none\""));
+        // every line of the labels is a text
+        assert!(svg.contains("dominant-baseline=\"hanging\" fill=\"rgb(255,255,255)\" fill-opacity=\"1.000\">Delay</text>"));
+        assert!(svg.contains(">z^-4</text>"));
+        // one bezier per connection, in the colors of the lines
+        let paths: Vec<_> = svg
+            .lines()
+            .filter(|x| x.starts_with("<path d=\"M "))
+            .collect();
+        assert_eq!(paths.len(), 2);
+        for (path, color) in paths.iter().zip(LINE_COLORS.iter()) {
+            assert!(path.contains(" C "), "{path}");
+            assert!(path.contains(&svg_paint("stroke", color)), "{path}");
+        }
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn rasterized_canvases_are_pngs() {
+        let red = Color::new(255, 0, 0, 255);
+        let mut canvas = Canvas::new(20f32, 10f32);
+        canvas.draw_rectangle_rec(Rectangle::new(2f32, 2f32, 4f32, 4f32), red);
+        canvas.draw_text("A", 10, 0, 10, Color::WHITE);
+
+        let mut backend = RasterBackend::new(20, 10, Color::BLANK);
+        backend.draw_canvas(&canvas, Vector2::zero());
+        let pixel = |x: usize, y: usize| &backend.pixels[(y * 20 + x) * 4..][..4];
+        assert_eq!(pixel(3, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(7, 3), [0, 0, 0, 0]);
+        // the left column of the 'A' starts one row under the top of the line
+        assert_eq!(pixel(10, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(10, 2), [255, 255, 255, 255]);
+
+        let png = backend.to_png();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+        assert_eq!(&png[16..24], &[0, 0, 0, 20, 0, 0, 0, 10]);
+        // one stored deflate block of 10 rows of 20 pixels and their filter bytes
+        let data = 10 * (1 + 20 * 4);
+        assert_eq!(png.len(), 8 + (12 + 13) + (12 + 2 + 5 + data + 4) + 12);
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+    }
+}
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use synths::OscillatorControls;
+
+use crate::dsp::blocks::*;
+use crate::graph::{Block, CanConnect};
+use crate::identify::BodeView;
+use crate::vis::{self, OutputConfig};
+
+/// A biquad followed by a short delay, measured as a whole: drag the knobs of the biquad to
+/// see the plots follow.
+pub fn create_bode_blocks(
+    output: &OutputConfig,
+) -> anyhow::Result<(
+    OscillatorControls,
+    impl Block<OscillatorControls, Output = Wave>,
+)> {
+    let controls = OscillatorControls {
+        duration: Duration::from_millis(230),
+        freq: 110f32,
+        phase: 0f32,
+        wave: synths::WaveType::Sawtooth,
+    };
+
+    let system = synths::Oscillator::default()
+        .connect(BodeView::new(Biquad::default().connect(Delay(SR / 1000))))
+        .connect(vis::WaveView::small())
+        .connect(output.audio_sink()?)
+        .colored();
+
+    Ok((controls, system))
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use synths::OscillatorControls;
+
+use crate::design::{Band, Design, Family};
+use crate::dsp::blocks::*;
+use crate::graph::{Block, CanConnect};
+use crate::identify::BodeView;
+use crate::transfer::PoleZeroView;
+use crate::vis::{self, OutputConfig};
+
+/// A sawtooth through a steep elliptic low-pass, then a Butterworth band-pass, each with its
+/// measured response and its poles and zeros.
+pub fn create_design_blocks(
+    output: &OutputConfig,
+) -> anyhow::Result<(
+    OscillatorControls,
+    impl Block<OscillatorControls, Output = Wave>,
+)> {
+    let controls = OscillatorControls {
+        duration: Duration::from_millis(230),
+        freq: 110f32,
+        phase: 0f32,
+        wave: synths::WaveType::Sawtooth,
+    };
+
+    let lowpass = Design::new(
+        Family::Elliptic {
+            ripple: 1f32,
+            attenuation: 60f32,
+        },
+        Band::LowPass(2000f32),
+        6,
+    )
+    .sos()?;
+    let bandpass = Design::new(Family::Butterworth, Band::BandPass(400f32, 1200f32), 3).sos()?;
+
+    let system = synths::Oscillator::default()
+        .connect(BodeView::new(PoleZeroView::new(lowpass)))
+        .connect(BodeView::new(PoleZeroView::new(bandpass)))
+        .connect(vis::WaveView::small())
+        .connect(output.audio_sink()?)
+        .colored();
+
+    Ok((controls, system))
+}
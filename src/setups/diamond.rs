@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::vis::WaveView;
+
+/// A diamond: the oscillator feeds a gain and a delay that are mixed back together,
+/// which is awkward to express with the static combinators.
+pub fn create_diamond_blocks() -> anyhow::Result<((), Graph)> {
+    let total_dur = Duration::from_millis(230);
+
+    let mut graph = Graph::new();
+    graph.add_constant(
+        "controls",
+        synths::OscillatorControls {
+            duration: total_dur,
+            freq: 110f32,
+            phase: 0f32,
+            wave: synths::WaveType::Sinusoid,
+        },
+    )?;
+    graph.add_node("osc", synths::Oscillator::default())?;
+    graph.add_node::<Wave, Wave, _>("osc_view", WaveView::<1>::small())?;
+    graph.add_node("gain", ConstMultiplier(0.5f32))?;
+    graph.add_node("delay", Delay(SR / 200))?;
+    graph.add_node_with_ports::<(Wave, Wave), Wave, _>(
+        "mix",
+        Basic::<2>::Mix,
+        &["dry", "wet"],
+        &["out"],
+    )?;
+    graph.add_node::<Wave, Wave, _>("view", WaveView::<1>::small())?;
+
+    graph.connect("controls.out", "osc.in")?;
+    graph.connect("osc.out", "osc_view.in")?;
+    graph.connect("osc_view.out", "gain.in")?;
+    graph.connect("osc_view.out", "delay.in")?;
+    graph.connect("gain.out", "mix.dry")?;
+    graph.connect("delay.out", "mix.wet")?;
+    graph.connect("mix.out", "view.in")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dtmf::{DtmfDecoder, DtmfEncoder, COLUMNS, ROWS};
+use crate::dyn_graph::Graph;
+use crate::goertzel::Goertzel;
+use crate::vis::WaveView;
+
+/// Every DTMF key played in noise, the power of the eight tones and the decoded keys.
+pub fn create_dtmf_blocks() -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("tone", Duration::from_millis(60))?;
+    graph.add_constant("noise_duration", Duration::from_secs(2))?;
+    graph.add_node(
+        "encoder",
+        DtmfEncoder {
+            digits: "0123456789*#ABCD".to_string(),
+        },
+    )?;
+    graph.add_node(
+        "noise",
+        synths::Noise {
+            amplitude: 0.2f32,
+            seed: 1,
+        },
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), (Wave, Wave), _>(
+        "pad",
+        AutoPad::<2>::End,
+        &["tones", "noise"],
+        &["tones", "noise"],
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), Wave, _>(
+        "mix",
+        Basic::<2>::Mix,
+        &["tones", "noise"],
+        &["out"],
+    )?;
+    graph.add_node::<Wave, Wave, _>("view", WaveView::<1>::small())?;
+    graph.add_node(
+        "goertzel",
+        Goertzel::builder()
+            .freqs(ROWS.into_iter().chain(COLUMNS).collect())
+            .build(),
+    )?;
+    graph.add_node("decoder", DtmfDecoder::default())?;
+
+    graph.connect("tone.out", "encoder.in")?;
+    graph.connect("noise_duration.out", "noise.in")?;
+    graph.connect("encoder.out", "pad.tones")?;
+    graph.connect("noise.out", "pad.noise")?;
+    graph.connect("pad.tones", "mix.tones")?;
+    graph.connect("pad.noise", "mix.noise")?;
+    graph.connect("mix.out", "view.in")?;
+    graph.connect("view.out", "goertzel.in")?;
+    graph.connect("view.out", "decoder.in")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
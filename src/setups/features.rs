@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::features::{Feature, FeatureExtractor};
+
+/// An exponential sweep from 100 Hz to 8 kHz described by spectral features.
+pub fn create_features_blocks() -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_secs(2))?;
+    graph.add_node(
+        "sweep",
+        synths::Sweep::builder().start(100f32).end(8000f32).build(),
+    )?;
+    graph.connect("duration.out", "sweep.in")?;
+
+    for (name, feature) in [
+        ("log_mel", Feature::LogMel),
+        ("mfcc", Feature::Mfcc),
+        ("chroma", Feature::Chroma),
+        ("centroid", Feature::Centroid),
+        ("flatness", Feature::Flatness),
+    ] {
+        graph.add_node(
+            name,
+            FeatureExtractor::builder()
+                .feature(feature)
+                .window(
+                    WindowSetting::builder()
+                        .frame_size(2048)
+                        .hop_length(512)
+                        .build(),
+                )
+                .build(),
+        )?;
+        graph.connect("sweep.out", &format!("{name}.in"))?;
+    }
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
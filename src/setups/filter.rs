@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use synths::OscillatorControls;
+
+use crate::dsp::blocks::*;
+use crate::graph::{Block, CanConnect};
+use crate::vis::{self, OutputConfig};
+
+/// An oscillator, a biquad and a gain with their knobs, to play with the interactive controls.
+pub fn create_filter_blocks(
+    output: &OutputConfig,
+) -> anyhow::Result<(
+    OscillatorControls,
+    impl Block<OscillatorControls, Output = Wave>,
+)> {
+    let controls = OscillatorControls {
+        duration: Duration::from_millis(230),
+        freq: 110f32,
+        phase: 0f32,
+        wave: synths::WaveType::Sawtooth,
+    };
+
+    let system = synths::Oscillator::default()
+        .connect(vis::WaveView::small())
+        .connect(Biquad::default())
+        .connect(vis::WaveView::small())
+        .connect(ConstMultiplier(0.5f32))
+        .connect(output.audio_sink()?)
+        .colored();
+
+    Ok((controls, system))
+}
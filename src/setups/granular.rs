@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::granular::{GrainWindow, Granular};
+use crate::vis::{OutputConfig, WaveView};
+
+/// Two grain clouds read from a sweep: sparse long grains around its middle, and a dense
+/// cloud of short grains an octave down from all over it.
+pub fn create_granular_blocks(output: &OutputConfig) -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_secs(1))?;
+    graph.add_node(
+        "sweep",
+        synths::Sweep::builder().start(200f32).end(2000f32).build(),
+    )?;
+    graph.add_node("sparse", Granular::default())?;
+    graph.add_node(
+        "dense",
+        Granular::builder()
+            .duration(Duration::from_secs(2))
+            .grain_size(Duration::from_millis(30))
+            .density(300f32)
+            .jitter(0.5f32)
+            .pitch(-12f32)
+            .window(GrainWindow::Gaussian)
+            .seed(2)
+            .build(),
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), (Wave, Wave), _>(
+        "pad",
+        AutoPad::<2>::End,
+        &["sparse", "dense"],
+        &["sparse", "dense"],
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), Wave, _>(
+        "mix",
+        Basic::<2>::Mix,
+        &["sparse", "dense"],
+        &["out"],
+    )?;
+    graph.add_node::<Wave, Wave, _>("view", WaveView::<1>::small())?;
+    graph.add_node("sink", output.audio_sink()?)?;
+
+    graph.connect("duration.out", "sweep.in")?;
+    graph.connect("sweep.out", "sparse.in")?;
+    graph.connect("sweep.out", "dense.in")?;
+    graph.connect("sparse.out", "pad.sparse")?;
+    graph.connect("dense.out", "pad.dense")?;
+    graph.connect("pad.sparse", "mix.sparse")?;
+    graph.connect("pad.dense", "mix.dense")?;
+    graph.connect("mix.out", "view.in")?;
+    graph.connect("view.out", "sink.in")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
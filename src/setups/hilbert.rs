@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::hilbert::{FrequencyShift, Hilbert, Instantaneous, Quantity, Sideband, Ssb};
+use crate::vis::{OutputConfig, WaveView};
+
+/// The instantaneous frequency of a sweep and the envelope of resonant pings, moved up to an
+/// upper sideband then shifted back down.
+pub fn create_hilbert_blocks(output: &OutputConfig) -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_secs(2))?;
+    graph.add_node(
+        "sweep",
+        synths::Sweep::builder()
+            .t(synths::SweepType::Linear)
+            .start(200f32)
+            .end(2000f32)
+            .build(),
+    )?;
+    graph.add_node_with_ports::<Wave, (Wave, Wave), _>(
+        "hilbert",
+        Hilbert,
+        &["in"],
+        &["re", "im"],
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), (Wave, Wave), _>(
+        "analytic",
+        WaveView::<2>::small(),
+        &["re", "im"],
+        &["re", "im"],
+    )?;
+    graph.add_node(
+        "frequency",
+        Instantaneous {
+            quantity: Quantity::Frequency,
+        },
+    )?;
+    graph.connect("duration.out", "sweep.in")?;
+    graph.connect("sweep.out", "hilbert.in")?;
+    graph.connect("hilbert.re", "analytic.re")?;
+    graph.connect("hilbert.im", "analytic.im")?;
+    graph.connect("sweep.out", "frequency.in")?;
+
+    graph.add_node("impulses", synths::ImpulseTrain { rate: 3f32 })?;
+    graph.add_node(
+        "ping",
+        Biquad::builder()
+            .t(BiquadType::BandPass)
+            .cutoff(300f32)
+            .q(20f32)
+            .build(),
+    )?;
+    graph.add_node(
+        "envelope",
+        Instantaneous {
+            quantity: Quantity::Amplitude,
+        },
+    )?;
+    graph.add_node(
+        "ssb",
+        Ssb::builder()
+            .carrier(1000f32)
+            .sideband(Sideband::Upper)
+            .build(),
+    )?;
+    graph.add_node("shift", FrequencyShift { shift: -700f32 })?;
+    graph.add_node("sink", output.audio_sink()?)?;
+    graph.connect("duration.out", "impulses.in")?;
+    graph.connect("impulses.out", "ping.in")?;
+    graph.connect("ping.out", "envelope.in")?;
+    graph.connect("ping.out", "ssb.in")?;
+    graph.connect("ssb.out", "shift.in")?;
+    graph.connect("shift.out", "sink.in")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
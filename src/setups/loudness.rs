@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use synths::OscillatorControls;
+
+use crate::dsp::blocks::*;
+use crate::graph::{Block, CanConnect};
+use crate::loudness::{Meter, Normalize};
+use crate::vis::OutputConfig;
+
+/// A full scale sawtooth metered before and after being normalized to -23 LUFS.
+pub fn create_loudness_blocks(
+    output: &OutputConfig,
+) -> anyhow::Result<(
+    OscillatorControls,
+    impl Block<OscillatorControls, Output = Wave>,
+)> {
+    let controls = OscillatorControls {
+        duration: Duration::from_secs(3),
+        freq: 110f32,
+        phase: 0f32,
+        wave: synths::WaveType::Sawtooth,
+    };
+
+    let system = synths::Oscillator::default()
+        .connect(Meter)
+        .connect(Normalize::default())
+        .connect(Meter)
+        .connect(output.audio_sink()?)
+        .colored();
+
+    Ok((controls, system))
+}
@@ -1 +1,198 @@
+use anyhow::bail;
+use raylib::math::Vector2;
+
+use crate::control::{ControlContext, ControlResult, MouseEvent};
+use crate::graph::Block;
+use crate::patch::Patch;
+use crate::vis::{OutputConfig, VisualizeResult};
+
+pub mod bode;
+pub mod design;
+pub mod diamond;
+pub mod dtmf;
+pub mod features;
+pub mod filter;
+pub mod granular;
+pub mod hilbert;
+pub mod loudness;
+pub mod modem;
+pub mod onsets;
+pub mod physical;
+pub mod pitch;
 pub mod playground;
+pub mod polezero;
+pub mod signals;
+pub mod stretch;
+pub mod sweep;
+
+/// A system together with its input, so setups with different input types can be
+/// selected at runtime.
+pub trait System {
+    fn process(&mut self);
+    fn process_and_visualize(&mut self) -> VisualizeResult;
+    fn on_hover(&mut self, pos: Vector2, context: &mut ControlContext) -> ControlResult;
+    fn on_unhover(&mut self, context: &mut ControlContext) -> ControlResult;
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult;
+}
+
+struct BoundSystem<I, B> {
+    input: I,
+    block: B,
+}
+
+impl<I: Clone, B: Block<I>> System for BoundSystem<I, B> {
+    fn process(&mut self) {
+        self.block.process(self.input.clone());
+    }
+
+    fn process_and_visualize(&mut self) -> VisualizeResult {
+        self.block.process_and_visualize(self.input.clone()).1
+    }
+
+    fn on_hover(&mut self, pos: Vector2, context: &mut ControlContext) -> ControlResult {
+        self.block.on_hover(pos, context)
+    }
+
+    fn on_unhover(&mut self, context: &mut ControlContext) -> ControlResult {
+        self.block.on_unhover(context)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        self.block.on_mouse(pos, event, context)
+    }
+}
+
+pub fn bind<I, B>((input, block): (I, B)) -> Box<dyn System>
+where
+    I: Clone + 'static,
+    B: Block<I> + 'static,
+{
+    Box::new(BoundSystem { input, block })
+}
+
+pub struct Setup {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub create: fn(&OutputConfig) -> anyhow::Result<Box<dyn System>>,
+}
+
+pub const DEFAULT_SETUP: &str = "playground";
+
+pub const SETUPS: &[Setup] = &[
+    Setup {
+        name: "playground",
+        description: "oscillators, envelopes, impulses and steps",
+        create: |output| Ok(bind(playground::create_playground_blocks(output)?)),
+    },
+    Setup {
+        name: "playground-patch",
+        description: "the playground loaded from patches/playground.ron",
+        create: |output| Ok(bind(playground::create_playground_graph(output)?)),
+    },
+    Setup {
+        name: "diamond",
+        description: "a dynamic graph mixing a gain and a delay of the same oscillator",
+        create: |_| Ok(bind(diamond::create_diamond_blocks()?)),
+    },
+    Setup {
+        name: "filter",
+        description: "a sawtooth through a biquad, drag the knobs to change them",
+        create: |output| Ok(bind(filter::create_filter_blocks(output)?)),
+    },
+    Setup {
+        name: "sweep",
+        description: "a sine sweep and a stepped gain driven by automation lanes",
+        create: |output| Ok(bind(sweep::create_sweep_blocks(output)?)),
+    },
+    Setup {
+        name: "signals",
+        description: "sweeps, a multitone comb, an MLS and an impulse train",
+        create: |_| Ok(bind(signals::create_signals_blocks()?)),
+    },
+    Setup {
+        name: "bode",
+        description: "frequency response of a biquad and a delay, measured with an impulse",
+        create: |output| Ok(bind(bode::create_bode_blocks(output)?)),
+    },
+    Setup {
+        name: "polezero",
+        description: "poles and zeros of a biquad and of a cascade of two resonances",
+        create: |output| Ok(bind(polezero::create_polezero_blocks(output)?)),
+    },
+    Setup {
+        name: "design",
+        description: "an elliptic low-pass and a Butterworth band-pass designed as biquad cascades",
+        create: |output| Ok(bind(design::create_design_blocks(output)?)),
+    },
+    Setup {
+        name: "pitch",
+        description: "an A0 sawtooth tracked with YIN, pYIN, autocorrelation and the cepstrum",
+        create: |_| Ok(bind(pitch::create_pitch_blocks()?)),
+    },
+    Setup {
+        name: "onsets",
+        description: "onsets, tempo and beats of resonant pings at 128 BPM",
+        create: |_| Ok(bind(onsets::create_onsets_blocks()?)),
+    },
+    Setup {
+        name: "features",
+        description: "log-mel, MFCC, chroma, centroid and flatness of a sweep",
+        create: |_| Ok(bind(features::create_features_blocks()?)),
+    },
+    Setup {
+        name: "loudness",
+        description: "a sawtooth metered before and after normalizing it to -23 LUFS",
+        create: |output| Ok(bind(loudness::create_loudness_blocks(output)?)),
+    },
+    Setup {
+        name: "dtmf",
+        description: "DTMF keys in noise, their tones measured with Goertzel and decoded",
+        create: |_| Ok(bind(dtmf::create_dtmf_blocks()?)),
+    },
+    Setup {
+        name: "modem",
+        description: "random bits through a 16-QAM modem and a noisy channel, with the bit errors",
+        create: |_| Ok(bind(modem::create_modem_blocks()?)),
+    },
+    Setup {
+        name: "hilbert",
+        description: "instantaneous frequency and envelope, single sideband and frequency shift",
+        create: |output| Ok(bind(hilbert::create_hilbert_blocks(output)?)),
+    },
+    Setup {
+        name: "stretch",
+        description: "pings time-stretched with a phase vocoder and WSOLA, and pitch-shifted",
+        create: |output| Ok(bind(stretch::create_stretch_blocks(output)?)),
+    },
+    Setup {
+        name: "granular",
+        description: "grain clouds read from a sweep, their grains drawn over it",
+        create: |output| Ok(bind(granular::create_granular_blocks(output)?)),
+    },
+    Setup {
+        name: "physical",
+        description: "a Karplus-Strong plucked string and a modal bell",
+        create: |output| Ok(bind(physical::create_physical_blocks(output)?)),
+    },
+];
+
+/// Creates a registered setup by name, or loads a patch if given a path to a `.ron` file.
+pub fn create(name: &str, output: &OutputConfig) -> anyhow::Result<Box<dyn System>> {
+    if name.ends_with(".ron") {
+        return Ok(bind(((), Patch::load(name)?.build(output)?)));
+    }
+    match SETUPS.iter().find(|x| x.name == name) {
+        Some(setup) => (setup.create)(output),
+        None => bail!("unknown setup {name:?}, use `list` to see the available ones"),
+    }
+}
@@ -0,0 +1,57 @@
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::modem::{Awgn, BitErrors, Demodulator, Modem, Modulator, RandomBits, Scheme};
+use crate::vis::{WaveView, XYView};
+
+/// Random bits sent with 16-QAM through a noisy channel, the received constellation and the
+/// bit errors.
+pub fn create_modem_blocks() -> anyhow::Result<((), Graph)> {
+    let modem = Modem::builder().scheme(Scheme::Qam16).build();
+    let mut graph = Graph::new();
+    graph.add_node(
+        "bits",
+        RandomBits {
+            count: 2048,
+            seed: 1,
+        },
+    )?;
+    graph.add_node("modulator", Modulator(modem.clone()))?;
+    graph.add_node(
+        "channel",
+        Awgn {
+            snr: 10f32,
+            seed: 2,
+        },
+    )?;
+    graph.add_node::<Wave, Wave, _>("view", WaveView::<1>::small())?;
+    graph.add_node_with_ports::<Wave, (Wave, (Wave, Wave)), _>(
+        "demodulator",
+        Demodulator(modem),
+        &["in"],
+        &["bits", "i", "q"],
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), (Wave, Wave), _>(
+        "constellation",
+        XYView,
+        &["i", "q"],
+        &["i", "q"],
+    )?;
+    graph.add_node_with_ports::<(Wave, Wave), Wave, _>(
+        "errors",
+        BitErrors,
+        &["sent", "received"],
+        &["out"],
+    )?;
+
+    graph.connect("bits.out", "modulator.in")?;
+    graph.connect("modulator.out", "channel.in")?;
+    graph.connect("channel.out", "view.in")?;
+    graph.connect("view.out", "demodulator.in")?;
+    graph.connect("demodulator.i", "constellation.i")?;
+    graph.connect("demodulator.q", "constellation.q")?;
+    graph.connect("bits.out", "errors.sent")?;
+    graph.connect("demodulator.bits", "errors.received")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
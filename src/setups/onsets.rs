@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::onset::{BeatTracker, OnsetDetector};
+use crate::vis::MarkerView;
+
+/// Resonant pings at 128 BPM, with the detected onsets and beats drawn over them.
+pub fn create_onsets_blocks() -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_secs(4))?;
+    graph.add_node(
+        "impulses",
+        synths::ImpulseTrain {
+            rate: 128f32 / 60f32,
+        },
+    )?;
+    graph.add_node(
+        "ping",
+        Biquad::builder()
+            .t(BiquadType::BandPass)
+            .cutoff(800f32)
+            .q(30f32)
+            .build(),
+    )?;
+    graph.connect("duration.out", "impulses.in")?;
+    graph.connect("impulses.out", "ping.in")?;
+
+    graph.add_node("onsets", OnsetDetector::default())?;
+    graph.add_node("beats", BeatTracker::default())?;
+    for name in ["onsets", "beats"] {
+        let view = format!("{name}_view");
+        graph.add_node::<(Wave, Wave), (Wave, Wave), _>(&view, MarkerView)?;
+        graph.connect("ping.out", &format!("{name}.in"))?;
+        graph.connect("ping.out", &format!("{view}.in0"))?;
+        graph.connect(&format!("{name}.out"), &format!("{view}.in1"))?;
+    }
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
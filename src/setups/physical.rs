@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::graph::CanFeedback;
+use crate::vis::{OutputConfig, WaveView};
+
+/// A plucked string echoing through a feedback loop and a struck bell.
+pub fn create_physical_blocks(output: &OutputConfig) -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_secs(3))?;
+    graph.add_node("string", synths::KarplusStrong::default())?;
+    graph.add_node::<Wave, Wave, _>("echo", ConstMultiplier(0.5f32).feedback(SR / 4)?)?;
+    graph.add_node("strike", synths::KroneckerDelta::Start)?;
+    graph.add_node("bell", synths::ModalBank::default())?;
+    graph.add_node_with_ports::<(Wave, Wave), Wave, _>(
+        "mix",
+        Basic::<2>::Mix,
+        &["string", "bell"],
+        &["out"],
+    )?;
+    graph.add_node::<Wave, Wave, _>("view", WaveView::<1>::small())?;
+    graph.add_node("sink", output.audio_sink()?)?;
+
+    graph.connect("duration.out", "string.in")?;
+    graph.connect("duration.out", "strike.in")?;
+    graph.connect("strike.out", "bell.in")?;
+    graph.connect("string.out", "echo.in")?;
+    graph.connect("echo.out", "mix.string")?;
+    graph.connect("bell.out", "mix.bell")?;
+    graph.connect("mix.out", "view.in")?;
+    graph.connect("view.out", "sink.in")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
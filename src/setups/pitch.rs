@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::pitch::{PitchDetector, PitchMethod};
+use crate::vis::WaveView;
+
+/// An A0 sawtooth tracked by every pitch detector, each plotting its pitch and confidence.
+pub fn create_pitch_blocks() -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant(
+        "a0",
+        synths::OscillatorControls {
+            duration: Duration::from_millis(500),
+            freq: 27.5f32,
+            phase: 0f32,
+            wave: synths::WaveType::Sawtooth,
+        },
+    )?;
+    graph.add_node("osc", synths::Oscillator::default())?;
+    graph.connect("a0.out", "osc.in")?;
+
+    for (name, method) in [
+        ("yin", PitchMethod::Yin),
+        ("pyin", PitchMethod::Pyin),
+        ("autocorrelation", PitchMethod::Autocorrelation),
+        ("cepstrum", PitchMethod::Cepstrum),
+    ] {
+        let detector = PitchDetector::builder()
+            .method(method)
+            .window(
+                WindowSetting::builder()
+                    .frame_size(4096)
+                    .hop_length(512)
+                    .build(),
+            )
+            .min_freq(20f32)
+            .build();
+        graph.add_node_with_ports(name, detector, &["in"], &["pitch", "confidence"])?;
+        graph.connect("osc.out", &format!("{name}.in"))?;
+
+        let view = format!("{name}_view");
+        graph.add_node::<(Wave, Wave), (Wave, Wave), _>(&view, WaveView::<2>::small())?;
+        graph.connect(&format!("{name}.pitch"), &format!("{view}.in0"))?;
+        graph.connect(&format!("{name}.confidence"), &format!("{view}.in1"))?;
+    }
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
@@ -3,10 +3,11 @@ use std::time::Duration;
 use synths::OscillatorControls;
 
 use crate::dsp::blocks::*;
-use crate::graph::{Block, CanConnect, CanFork, CanStack, MetadataExt};
-use crate::vis::{Identity, WaveView};
+use crate::dyn_graph::Graph;
+use crate::graph::{Block, CanConnect, CanFork, CanStack};
+use crate::vis::{Identity, OutputConfig, WaveView};
 use crate::wav::WavWriter;
-use crate::{graph, vis};
+use crate::{graph, patch, vis};
 
 type Input1 = (
     ((OscillatorControls, OscillatorControls), OscillatorControls),
@@ -16,6 +17,7 @@ type Input1 = (
 type Input2 = (((Duration, Duration), Duration), Duration);
 
 pub fn create_playground_blocks(
+    output: &OutputConfig,
 ) -> anyhow::Result<((Input1, Input2), impl Block<(Input1, Input2), Output = ()>)> {
     let total_dur = Duration::from_millis(230);
 
@@ -36,11 +38,11 @@ pub fn create_playground_blocks(
                 .connect(vis::WaveView::small()),
         );
     let sys_1 = /* _ */
-        blocks::synths::Oscillator.connect(vis::WaveView::small())
-        .stack(blocks::synths::Oscillator.connect(vis::WaveView::small()))
-        .stack(blocks::synths::Oscillator.connect(vis::WaveView::small()))
-        .stack(blocks::synths::Oscillator.connect(vis::WaveView::small()))
-        .connect(Basic::Mix.connect(WavWriter::new("./target/out.wav")?).connect(vis::AudioSink::try_default()?))
+        blocks::synths::Oscillator::default().connect(vis::WaveView::small())
+        .stack(blocks::synths::Oscillator::default().connect(vis::WaveView::small()))
+        .stack(blocks::synths::Oscillator::default().connect(vis::WaveView::small()))
+        .stack(blocks::synths::Oscillator::default().connect(vis::WaveView::small()))
+        .connect(Basic::Mix.connect(WavWriter::new(output.path("out.wav"))?).connect(output.audio_sink()?))
         .fork(envelope)
         .connect(vis::WaveView::grow())
         .colored();
@@ -100,8 +102,15 @@ pub fn create_playground_blocks(
 
     let out_sys = sys_1
         .stack(sys_2)
-        // .connect(vis::AudioSink::try_default()?)
+        // .connect(output.audio_sink()?)
         .connect(graph::Discard);
 
     Ok(((input_sys_1, input_sys_2), out_sys))
 }
+
+/// Same system as [`create_playground_blocks`], loaded from `patches/playground.ron`.
+pub fn create_playground_graph(output: &OutputConfig) -> anyhow::Result<((), Graph)> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/patches/playground.ron");
+    let graph = patch::Patch::load(path)?.build(output)?;
+    Ok(((), graph))
+}
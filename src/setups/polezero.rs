@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use synths::OscillatorControls;
+
+use crate::dsp::blocks::*;
+use crate::graph::{Block, CanConnect};
+use crate::transfer::{Lti, PoleZeroView};
+use crate::vis::{self, OutputConfig};
+
+/// Poles and zeros of a biquad, drag its knobs to move them, then of two resonances in
+/// cascade as a single transfer function.
+pub fn create_polezero_blocks(
+    output: &OutputConfig,
+) -> anyhow::Result<(
+    OscillatorControls,
+    impl Block<OscillatorControls, Output = Wave>,
+)> {
+    let controls = OscillatorControls {
+        duration: Duration::from_millis(230),
+        freq: 110f32,
+        phase: 0f32,
+        wave: synths::WaveType::Sawtooth,
+    };
+
+    let low = Biquad::builder()
+        .t(BiquadType::BandPass)
+        .cutoff(440f32)
+        .q(4f32)
+        .build();
+    let high = Biquad::builder()
+        .t(BiquadType::BandPass)
+        .cutoff(2500f32)
+        .q(8f32)
+        .build();
+    let formants = low.transfer_function().cascade(&high.transfer_function());
+
+    let system = synths::Oscillator::default()
+        .connect(PoleZeroView::new(Biquad::default()))
+        .connect(PoleZeroView::new(formants))
+        .connect(vis::WaveView::small())
+        .connect(output.audio_sink()?)
+        .colored();
+
+    Ok((controls, system))
+}
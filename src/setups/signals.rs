@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::vis::WaveView;
+
+/// The test signals used to measure systems, side by side.
+pub fn create_signals_blocks() -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_millis(230))?;
+    graph.add_node("delta", synths::KroneckerDelta::Start)?;
+    graph.add_node(
+        "lin_sweep",
+        synths::Sweep::builder()
+            .t(synths::SweepType::Linear)
+            .build(),
+    )?;
+    graph.add_node("exp_sweep", synths::Sweep::default())?;
+    graph.add_node("comb", synths::Multitone::comb(110f32, 8))?;
+    graph.add_node("mls", synths::Mls { order: 8 })?;
+    graph.add_node("impulses", synths::ImpulseTrain { rate: 40f32 })?;
+
+    for name in ["delta", "lin_sweep", "exp_sweep", "comb", "mls", "impulses"] {
+        let view = format!("{name}_view");
+        graph.add_node::<Wave, Wave, _>(&view, WaveView::<1>::small())?;
+        graph.connect("duration.out", &format!("{name}.in"))?;
+        graph.connect(&format!("{name}.out"), &format!("{view}.in"))?;
+    }
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
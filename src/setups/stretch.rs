@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use crate::dsp::blocks::*;
+use crate::dyn_graph::Graph;
+use crate::stretch::{PitchShift, TimeStretch, Wsola};
+use crate::vis::{OutputConfig, WaveView};
+
+/// Resonant pings made half again as long with a phase vocoder and with WSOLA, and a fifth
+/// higher with the same duration.
+pub fn create_stretch_blocks(output: &OutputConfig) -> anyhow::Result<((), Graph)> {
+    let mut graph = Graph::new();
+    graph.add_constant("duration", Duration::from_secs(2))?;
+    graph.add_node("impulses", synths::ImpulseTrain { rate: 4f32 })?;
+    graph.add_node(
+        "ping",
+        Biquad::builder()
+            .t(BiquadType::BandPass)
+            .cutoff(440f32)
+            .q(20f32)
+            .build(),
+    )?;
+    graph.add_node::<Wave, Wave, _>("original", WaveView::<1>::small())?;
+    graph.connect("duration.out", "impulses.in")?;
+    graph.connect("impulses.out", "ping.in")?;
+    graph.connect("ping.out", "original.in")?;
+
+    graph.add_node("vocoder", TimeStretch::default())?;
+    graph.add_node("wsola", Wsola::default())?;
+    graph.add_node("pitch", PitchShift::default())?;
+    graph.add_node("sink", output.audio_sink()?)?;
+    for name in ["vocoder", "wsola", "pitch"] {
+        let view = format!("{name}_view");
+        graph.add_node::<Wave, Wave, _>(&view, WaveView::<1>::small())?;
+        graph.connect("original.out", &format!("{name}.in"))?;
+        graph.connect(&format!("{name}.out"), &format!("{view}.in"))?;
+    }
+    graph.connect("pitch_view.out", "sink.in")?;
+
+    graph.validate()?;
+    Ok(((), graph.colored()))
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use synths::OscillatorControls;
+
+use crate::automation::{Automated, Breakpoint, Curve, Interpolation, Lane};
+use crate::dsp::blocks::*;
+use crate::graph::{Block, CanConnect};
+use crate::vis::{self, OutputConfig};
+
+/// An exponential sine sweep through a gain stepping between levels, with the lanes drawn
+/// over the blocks they automate.
+pub fn create_sweep_blocks(
+    output: &OutputConfig,
+) -> anyhow::Result<(
+    OscillatorControls,
+    impl Block<OscillatorControls, Output = Wave>,
+)> {
+    let controls = OscillatorControls {
+        duration: Duration::from_millis(230),
+        freq: 110f32,
+        phase: 0f32,
+        wave: synths::WaveType::Sinusoid,
+    };
+
+    let sweep = Lane::new(
+        "freq",
+        Curve::breakpoints(
+            vec![
+                Breakpoint::new(Duration::ZERO, 55f32),
+                Breakpoint::new(Duration::from_millis(230), 1760f32),
+            ],
+            Interpolation::Exponential,
+        ),
+    );
+    let steps = Lane::new(
+        "gain",
+        Curve::breakpoints(
+            [0f32, 1f32, 0.25f32, 0.75f32, 0.5f32]
+                .into_iter()
+                .enumerate()
+                .map(|(i, x)| Breakpoint::new(Duration::from_millis(46 * i as u64), x))
+                .collect(),
+            Interpolation::Step,
+        ),
+    )
+    .smoothing(Duration::from_millis(5));
+
+    let system = Automated::new(synths::Oscillator::default(), vec![sweep])?
+        .connect(vis::WaveView::grow())
+        .connect(Automated::new(ConstMultiplier(1f32), vec![steps])?)
+        .connect(vis::WaveView::grow())
+        .connect(output.audio_sink()?)
+        .colored();
+
+    Ok((controls, system))
+}
@@ -0,0 +1,273 @@
+//! Complex numbers and the FFT, for everything that looks at waves in the frequency domain.
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::Wave;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex::new(0f32, 0f32);
+    pub const ONE: Complex = Complex::new(1f32, 0f32);
+
+    pub const fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_polar(r: f32, theta: f32) -> Self {
+        let (sin, cos) = theta.sin_cos();
+        Self::new(r * cos, r * sin)
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn norm_sqr(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn arg(&self) -> f32 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn exp(&self) -> Self {
+        Self::from_polar(self.re.exp(), self.im)
+    }
+
+    pub fn sqrt(&self) -> Self {
+        Self::from_polar(self.norm().sqrt(), self.arg() / 2f32)
+    }
+}
+
+impl From<f32> for Complex {
+    fn from(re: f32) -> Self {
+        Self::new(re, 0f32)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, rhs: Complex) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f32> for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: f32) -> Complex {
+        Complex::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Complex {
+        let d = rhs.norm_sqr();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+impl Div<f32> for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: f32) -> Complex {
+        Complex::new(self.re / rhs, self.im / rhs)
+    }
+}
+
+/// In place radix-2 FFT, the length has to be a power of two.
+pub fn fft(x: &mut [Complex]) {
+    transform(x, false);
+}
+
+/// Inverse of [`fft`], scaled by `1 / n` so `ifft(fft(x)) == x`.
+pub fn ifft(x: &mut [Complex]) {
+    transform(x, true);
+    let n = x.len() as f32;
+    for x in x.iter_mut() {
+        *x = *x / n;
+    }
+}
+
+fn transform(x: &mut [Complex], inverse: bool) {
+    let n = x.len();
+    assert!(n.is_power_of_two(), "fft length {n} is not a power of two");
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            x.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1f64 } else { -1f64 };
+    let mut len = 2;
+    while len <= n {
+        // twiddles in f64, accumulating them in f32 drifts on long transforms
+        let twiddles: Vec<Complex> = (0..len / 2)
+            .map(|k| {
+                let (sin, cos) =
+                    (sign * 2f64 * std::f64::consts::PI * k as f64 / len as f64).sin_cos();
+                Complex::new(cos as f32, sin as f32)
+            })
+            .collect();
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let a = x[start + k];
+                let b = x[start + k + len / 2] * twiddles[k];
+                x[start + k] = a + b;
+                x[start + k + len / 2] = a - b;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Spectrum of a real wave zero padded to `n` (a power of two), only the `n / 2 + 1` bins up
+/// to Nyquist since the others are their conjugates.
+pub fn rfft(wave: &[f32], n: usize) -> Vec<Complex> {
+    let mut x: Vec<Complex> = wave.iter().take(n).map(|x| Complex::from(*x)).collect();
+    x.resize(n, Complex::ZERO);
+    fft(&mut x);
+    x.truncate(n / 2 + 1);
+    x
+}
+
+/// Inverse of [`rfft`], `n` samples long.
+pub fn irfft(bins: &[Complex], n: usize) -> Wave {
+    let mut x = vec![Complex::ZERO; n];
+    for (k, bin) in bins.iter().enumerate().take(n / 2 + 1) {
+        x[k] = *bin;
+        if k > 0 && k < n - k {
+            x[n - k] = bin.conj();
+        }
+    }
+    ifft(&mut x);
+    x.into_iter().map(|x| x.re).collect()
+}
+
+/// Linear convolution, `a.len() + b.len() - 1` samples long.
+pub fn convolve(a: &[f32], b: &[f32]) -> Wave {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let len = a.len() + b.len() - 1;
+    let n = len.next_power_of_two();
+    let (fa, fb) = (rfft(a, n), rfft(b, n));
+    let product: Vec<Complex> = fa.iter().zip(fb.iter()).map(|(x, y)| *x * *y).collect();
+    let mut out = irfft(&product, n);
+    out.truncate(len);
+    out
+}
+
+/// Frequency in Hz of bin `k` of an `n` points transform.
+pub fn bin_freq(k: usize, n: usize) -> f32 {
+    k as f32 * crate::dsp::SR as f32 / n as f32
+}
+
+pub fn to_db(x: f32) -> f32 {
+    20f32 * x.max(1e-10).log10()
+}
+
+/// Periodic Hann window of `n` samples.
+pub fn hann(n: usize) -> Wave {
+    (0..n)
+        .map(|i| 0.5f32 - 0.5f32 * (2f32 * std::f32::consts::PI * i as f32 / n as f32).cos())
+        .collect()
+}
+
+/// Short-time Fourier transform with a Hann window, `n / 2 + 1` bins per frame with `n` the
+/// frame size rounded up to a power of two. Frames are centered on every `hop_length`th sample,
+/// zero padded past both ends.
+pub fn stft(wave: &[f32], window: &WindowSetting) -> Vec<Vec<Complex>> {
+    let n = window.frame_size().next_power_of_two();
+    let hann = hann(window.frame_size());
+    window
+        .centered_frames(wave)
+        .map(|frame| {
+            let frame: Wave = frame.iter().zip(hann.iter()).map(|(x, w)| x * w).collect();
+            rfft(&frame, n)
+        })
+        .collect()
+}
+
+/// Inverse of [`stft`]: every frame windowed again and added back centered on its hop,
+/// divided by the sum of the squared windows. `len` samples long.
+pub fn istft(frames: &[Vec<Complex>], window: &WindowSetting, len: usize) -> Wave {
+    let (frame_size, hop_length) = (window.frame_size(), window.hop_length());
+    let n = frame_size.next_power_of_two();
+    let hann = hann(frame_size);
+    let mut out = vec![0f32; len];
+    let mut norm = vec![0f32; len];
+    for (m, bins) in frames.iter().enumerate() {
+        let frame = irfft(bins, n);
+        for (i, (x, w)) in frame.iter().zip(hann.iter()).enumerate() {
+            let j = (m * hop_length + i).checked_sub(frame_size / 2);
+            if let Some(j) = j.filter(|j| *j < len) {
+                out[j] += x * w;
+                norm[j] += w * w;
+            }
+        }
+    }
+    out.iter()
+        .zip(norm.iter())
+        .map(|(x, norm)| if *norm > 1e-3 { x / norm } else { 0f32 })
+        .collect()
+}
@@ -0,0 +1,518 @@
+//! Changing the duration of a wave without changing its pitch and the other way around: a
+//! phase vocoder working on the STFT, and WSOLA working on the wave itself, which keeps the
+//! transients of speech sharper.
+use raylib::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::control::{self, ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::WindowSetting;
+use crate::dsp::Wave;
+use crate::graph::Block;
+use crate::params::{Param, ParamValue, Parameters};
+use crate::spectrum::{self, Complex};
+use crate::vis::VisualizeResult;
+use crate::widgets::{self, Range};
+
+/// `frame_size` samples of `wave` centered on `center`, zero padded past both ends.
+fn frame_at(wave: &[f32], center: usize, frame_size: usize) -> Wave {
+    (0..frame_size)
+        .map(|i| {
+            let j = (center + i).checked_sub(frame_size / 2);
+            j.and_then(|j| wave.get(j)).copied().unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Wraps a phase to [-π, π].
+fn principal(phase: f32) -> f32 {
+    use std::f32::consts::PI;
+    phase - 2f32 * PI * ((phase + PI) / (2f32 * PI)).floor()
+}
+
+/// Bins louder than their two neighbours on each side.
+fn peaks(magnitudes: &[f32]) -> Vec<usize> {
+    (0..magnitudes.len())
+        .filter(|k| {
+            let neighbours = k.saturating_sub(2)..(k + 3).min(magnitudes.len());
+            neighbours
+                .filter(|i| i != k)
+                .all(|i| magnitudes[*k] > magnitudes[i])
+        })
+        .collect()
+}
+
+/// The peak every bin belongs to: the nearest one, the regions split halfway between peaks.
+fn regions(peaks: &[usize], bins: usize) -> Vec<usize> {
+    let mut nearest = vec![0; bins];
+    for (i, peak) in peaks.iter().enumerate() {
+        let start = if i == 0 {
+            0
+        } else {
+            (peaks[i - 1] + peak) / 2 + 1
+        };
+        let end = peaks.get(i + 1).map_or(bins, |next| (peak + next) / 2 + 1);
+        nearest[start..end].fill(*peak);
+    }
+    nearest
+}
+
+/// `wave` lasting `factor` times as long with a phase vocoder: frames read every
+/// `hop_length / factor` samples and written back every `hop_length`, their phases advanced
+/// by the frequency measured in every bin. With `phase_locking`, only the peaks of the
+/// spectrum are advanced and the bins around them keep their phase relative to the peak,
+/// which sounds less phasey.
+pub fn phase_vocoder(
+    wave: &[f32],
+    factor: f32,
+    window: &WindowSetting,
+    phase_locking: bool,
+) -> Wave {
+    let (frame_size, hop_length) = (window.frame_size(), window.hop_length());
+    let len = (wave.len() as f32 * factor).round() as usize;
+    if wave.is_empty() || len == 0 {
+        return vec![];
+    }
+    let n = frame_size.next_power_of_two();
+    let hann = spectrum::hann(frame_size);
+    let analysis_hop = hop_length as f32 / factor;
+    let count = len.div_ceil(hop_length);
+
+    let mut previous: Option<(usize, Vec<Complex>)> = None;
+    let mut phases = vec![0f32; n / 2 + 1];
+    let frames: Vec<Vec<Complex>> = (0..count)
+        .map(|m| {
+            let center = (m as f32 * analysis_hop).round() as usize;
+            let frame: Wave = frame_at(wave, center, frame_size)
+                .iter()
+                .zip(hann.iter())
+                .map(|(x, w)| x * w)
+                .collect();
+            let bins = spectrum::rfft(&frame, n);
+            let magnitudes: Wave = bins.iter().map(|x| x.norm()).collect();
+
+            match &previous {
+                None => phases = bins.iter().map(|x| x.arg()).collect(),
+                Some((previous_center, previous_bins)) => {
+                    let hop = (center - previous_center).max(1) as f32;
+                    let advance = |k: usize| {
+                        let omega = 2f32 * std::f32::consts::PI * k as f32 / n as f32;
+                        let deviation =
+                            principal(bins[k].arg() - previous_bins[k].arg() - omega * hop);
+                        (omega + deviation / hop) * hop_length as f32
+                    };
+                    if phase_locking {
+                        let peaks = peaks(&magnitudes);
+                        for peak in &peaks {
+                            phases[*peak] += advance(*peak);
+                        }
+                        for (k, peak) in regions(&peaks, bins.len()).into_iter().enumerate() {
+                            if k != peak && !peaks.is_empty() {
+                                phases[k] = phases[peak] + bins[k].arg() - bins[peak].arg();
+                            }
+                        }
+                    } else {
+                        for (k, phase) in phases.iter_mut().enumerate() {
+                            *phase += advance(k);
+                        }
+                    }
+                }
+            }
+            previous = Some((center, bins));
+
+            magnitudes
+                .iter()
+                .zip(phases.iter())
+                .map(|(r, phase)| Complex::from_polar(*r, *phase))
+                .collect()
+        })
+        .collect();
+
+    spectrum::istft(&frames, window, len)
+}
+
+/// `wave` lasting `factor` times as long with WSOLA: frames of the input added every
+/// `hop_length` samples of the output, each one taken up to `tolerance` samples away from
+/// where it should be so it lines up best with what follows the previous frame in the input.
+pub fn wsola(wave: &[f32], factor: f32, window: &WindowSetting, tolerance: usize) -> Wave {
+    let (frame_size, hop_length) = (window.frame_size(), window.hop_length());
+    let len = (wave.len() as f32 * factor).round() as usize;
+    if wave.is_empty() || len == 0 {
+        return vec![];
+    }
+    let hann = spectrum::hann(frame_size);
+    let analysis_hop = hop_length as f32 / factor;
+    let sample = |i: isize| {
+        usize::try_from(i)
+            .ok()
+            .and_then(|i| wave.get(i))
+            .copied()
+            .unwrap_or_default()
+    };
+
+    let half = (frame_size / 2) as isize;
+    let mut out = vec![0f32; len];
+    let mut norm = vec![0f32; len];
+    // start of the frame of the input taken last time
+    let mut previous: isize = 0;
+    for m in 0..len.div_ceil(hop_length) {
+        let target = (m as f32 * analysis_hop).round() as isize - half;
+        let start = if m == 0 {
+            target
+        } else {
+            // the frame that would continue the previous one seamlessly
+            let natural = previous + hop_length as isize;
+            let similarity = |offset: isize| {
+                (0..frame_size as isize)
+                    .map(|i| sample(natural + i) * sample(target + offset + i))
+                    .sum::<f32>()
+            };
+            let tolerance = tolerance as isize;
+            let best = (-tolerance..=tolerance)
+                .map(|offset| (offset, similarity(offset)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(0, |(offset, _)| offset);
+            target + best
+        };
+        for (i, w) in hann.iter().enumerate() {
+            let j = (m * hop_length + i).checked_sub(frame_size / 2);
+            if let Some(j) = j.filter(|j| *j < len) {
+                out[j] += sample(start + i as isize) * w;
+                norm[j] += w;
+            }
+        }
+        previous = start;
+    }
+
+    out.iter()
+        .zip(norm.iter())
+        .map(|(x, norm)| if *norm > 1e-3 { x / norm } else { 0f32 })
+        .collect()
+}
+
+/// Changes the duration of the input by `factor` keeping its pitch, with a phase vocoder.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeStretch {
+    /// Output duration over input duration.
+    #[builder(value = 1.5f32)]
+    pub factor: f32,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    #[builder(value = true)]
+    pub phase_locking: bool,
+}
+
+impl Default for TimeStretch {
+    fn default() -> Self {
+        Self::builder()
+            .window(
+                WindowSetting::builder()
+                    .frame_size(2048)
+                    .hop_length(512)
+                    .build(),
+            )
+            .build()
+    }
+}
+
+impl Block<Wave> for TimeStretch {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        phase_vocoder(&input, self.factor, &self.window, self.phase_locking)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("Stretch\n×{:.2}", self.factor);
+        visualize_with_window(&text, out, &self.window)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for TimeStretch {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[
+            Param::float("factor", Range::log(0.25f32, 4f32), ""),
+            Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+            Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+            Param::bool("phase_locking"),
+        ];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "factor" => Some(ParamValue::Float(self.factor)),
+            "phase_locking" => Some(ParamValue::Bool(self.phase_locking)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("factor", ParamValue::Float(x)) => self.factor = *x,
+            ("phase_locking", ParamValue::Bool(x)) => self.phase_locking = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+/// Changes the pitch of the input by `semitones` keeping its duration: stretched with a
+/// phase vocoder, then resampled back to the original length.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PitchShift {
+    #[builder(value = 7f32)]
+    pub semitones: f32,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+}
+
+impl Default for PitchShift {
+    fn default() -> Self {
+        Self::builder()
+            .window(
+                WindowSetting::builder()
+                    .frame_size(2048)
+                    .hop_length(512)
+                    .build(),
+            )
+            .build()
+    }
+}
+
+impl PitchShift {
+    pub fn shift(&self, wave: &[f32]) -> Wave {
+        let ratio = 2f32.powf(self.semitones / 12f32);
+        let stretched = phase_vocoder(wave, ratio, &self.window, true);
+        if stretched.is_empty() {
+            return vec![0f32; wave.len()];
+        }
+        (0..wave.len())
+            .map(|i| {
+                let position = i as f32 * ratio;
+                let j = position.floor() as usize;
+                let frac = position - j as f32;
+                let a = stretched.get(j).copied().unwrap_or_default();
+                let b = stretched.get(j + 1).copied().unwrap_or_default();
+                a + (b - a) * frac
+            })
+            .collect()
+    }
+}
+
+impl Block<Wave> for PitchShift {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.shift(&input)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("Pitch\n{:+.1}st", self.semitones);
+        visualize_with_window(&text, out, &self.window)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for PitchShift {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[
+            Param::float("semitones", Range::linear(-24f32, 24f32), "st"),
+            Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+            Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+        ];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "semitones" => Some(ParamValue::Float(self.semitones)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("semitones", ParamValue::Float(x)) => self.semitones = *x,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+/// Changes the duration of the input by `factor` keeping its pitch, with WSOLA. Better than
+/// [`TimeStretch`] on speech, worse on polyphonic music.
+#[derive(Debug, Clone, tidy_builder::Builder, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Wsola {
+    /// Output duration over input duration.
+    #[builder(value = 1.5f32)]
+    pub factor: f32,
+    #[builder(value = default)]
+    pub window: WindowSetting,
+    /// Samples, how far frames can be moved to line up.
+    #[builder(value = 256)]
+    pub tolerance: usize,
+}
+
+impl Default for Wsola {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl Block<Wave> for Wsola {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        Some(self)
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        wsola(&input, self.factor, &self.window, self.tolerance)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        let text = format!("WSOLA\n×{:.2}", self.factor);
+        visualize_with_window(&text, out, &self.window)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        let changed = self.window.update(pos, event);
+        control::handled(context, changed)
+    }
+}
+
+impl Parameters for Wsola {
+    fn params(&self) -> &'static [Param] {
+        const PARAMS: &[Param] = &[
+            Param::float("factor", Range::log(0.25f32, 4f32), ""),
+            Param::float("frame_size", Range::log(16f32, 8192f32), "samples"),
+            Param::float("hop_length", Range::log(1f32, 4096f32), "samples"),
+            Param::float("tolerance", Range::log(1f32, 4096f32), "samples"),
+        ];
+        PARAMS
+    }
+
+    fn get(&self, name: &str) -> Option<ParamValue> {
+        match name {
+            "factor" => Some(ParamValue::Float(self.factor)),
+            "tolerance" => Some(ParamValue::Float(self.tolerance as f32)),
+            _ => self.window.get(name),
+        }
+    }
+
+    fn set_checked(&mut self, name: &str, value: ParamValue) {
+        match (name, &value) {
+            ("factor", ParamValue::Float(x)) => self.factor = *x,
+            ("tolerance", ParamValue::Float(x)) => self.tolerance = x.round() as usize,
+            _ => self.window.set_checked(name, value),
+        }
+    }
+}
+
+/// A box with the frame size and hop length sliders.
+fn visualize_with_window(text: &str, out: Wave, window: &WindowSetting) -> (Wave, VisualizeResult) {
+    let window = window.clone();
+    widgets::visualize_with_widgets(text, out, 2, move |d| {
+        let (frame_size, hop_length) = WindowSetting::widgets();
+        frame_size.draw(d, window.frame_size() as f32);
+        hop_length.draw(d, window.hop_length() as f32);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::SR;
+
+    fn sine(freq: f32, len: usize) -> Wave {
+        (0..len)
+            .map(|i| (2f32 * std::f32::consts::PI * freq * i as f32 / SR as f32).sin())
+            .collect()
+    }
+
+    /// Hz, from the rising zero crossings away from both ends of `wave`.
+    fn frequency(wave: &[f32]) -> f32 {
+        let margin = wave.len() / 8;
+        let crossings: Vec<f32> = (margin..wave.len() - margin)
+            .filter(|i| wave[i - 1] < 0f32 && wave[*i] >= 0f32)
+            .map(|i| i as f32 - wave[i] / (wave[i] - wave[i - 1]))
+            .collect();
+        let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+        (crossings.len() - 1) as f32 * SR as f32 / (last - first)
+    }
+
+    fn cents(freq: f32, expected: f32) -> f32 {
+        1200f32 * (freq / expected).log2()
+    }
+
+    #[test]
+    fn stretched_sines_keep_their_frequency() {
+        let input = sine(440f32, SR);
+        let window = WindowSetting::default();
+        for factor in [0.75f32, 1.5f32] {
+            let stretched = [
+                phase_vocoder(&input, factor, &window, false),
+                phase_vocoder(&input, factor, &window, true),
+                wsola(&input, factor, &window, 256),
+            ];
+            for out in stretched {
+                assert_eq!(out.len(), (SR as f32 * factor).round() as usize);
+                let error = cents(frequency(&out), 440f32);
+                assert!(error.abs() < 1f32, "×{factor}: {error} cents");
+            }
+        }
+    }
+
+    #[test]
+    fn pitch_shifted_sines_keep_their_length() {
+        let input = sine(440f32, SR);
+        for semitones in [-5f32, 7f32] {
+            let out = PitchShift::builder()
+                .semitones(semitones)
+                .build()
+                .process(input.clone());
+            assert_eq!(out.len(), input.len());
+            let error = cents(frequency(&out), 440f32) - 100f32 * semitones;
+            assert!(
+                error.abs() < 1f32,
+                "{semitones} semitones: {error} cents off"
+            );
+        }
+    }
+}
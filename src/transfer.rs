@@ -0,0 +1,584 @@
+//! Transfer functions of linear time invariant blocks, in the z-domain.
+//!
+//! https://tttapa.github.io/Pages/Mathematics/Systems-and-Control-Theory/Digital-filters/DTLTI-Systems,-Transfer-Functions,-and-the-Z-transform/Z-Transform.html
+use std::f32::consts::PI;
+
+use anyhow::bail;
+use raylib::color::Color;
+use raylib::math::{Rectangle, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::control::{ControlContext, ControlResult, MouseEvent};
+use crate::dsp::blocks::{Biquad, ConstMultiplier, Delay};
+use crate::dsp::{Wave, SR};
+use crate::graph::{Block, Streaming};
+use crate::layout::Layout;
+use crate::params::Parameters;
+use crate::render::Canvas;
+use crate::spectrum::Complex;
+use crate::vis::{self, VisualizeResult, BOX_SIZE, T};
+
+/// `H(z) = (b[0] + b[1] z^-1 + ...) / (a[0] + a[1] z^-1 + ...)`, with `a[0] == 1`.
+///
+/// As a block it filters with a transposed direct form II, fine for low orders; higher orders
+/// are better split into second order sections.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferFunction {
+    pub b: Vec<f32>,
+    pub a: Vec<f32>,
+}
+
+/// The same transfer function as its roots: `H(z) = gain * Π(z - zeros) / Π(z - poles)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZeroPoleGain {
+    pub zeros: Vec<Complex>,
+    pub poles: Vec<Complex>,
+    pub gain: f32,
+}
+
+impl TransferFunction {
+    /// Normalizes the coefficients so `a[0] == 1`.
+    pub fn new(b: Vec<f32>, a: Vec<f32>) -> anyhow::Result<Self> {
+        let a0 = a.first().copied().unwrap_or_default();
+        if a0 == 0f32 {
+            bail!("the first denominator coefficient can't be zero, got {a:?}");
+        }
+        Ok(Self {
+            b: b.iter().map(|x| x / a0).collect(),
+            a: a.iter().map(|x| x / a0).collect(),
+        })
+    }
+
+    pub fn identity() -> Self {
+        Self::gain(1f32)
+    }
+
+    pub fn gain(gain: f32) -> Self {
+        Self {
+            b: vec![gain],
+            a: vec![1f32],
+        }
+    }
+
+    /// `z^-n`
+    pub fn delay(n: usize) -> Self {
+        let mut b = vec![0f32; n + 1];
+        b[n] = 1f32;
+        Self { b, a: vec![1f32] }
+    }
+
+    pub fn order(&self) -> usize {
+        (self.b.len().max(self.a.len())).saturating_sub(1)
+    }
+
+    /// `H(e^jω)` at `freq` Hz.
+    pub fn response(&self, freq: f32) -> Complex {
+        let w = 2f32 * PI * freq / SR as f32;
+        // powers of z^-1 = e^-jω
+        let eval = |coefficients: &[f32]| {
+            coefficients
+                .iter()
+                .enumerate()
+                .fold(Complex::ZERO, |acc, (k, c)| {
+                    acc + Complex::from_polar(*c, -w * k as f32)
+                })
+        };
+        eval(&self.b) / eval(&self.a)
+    }
+
+    pub fn impulse_response(&self, len: usize) -> Wave {
+        let mut input = vec![0f32; len];
+        if let Some(x) = input.first_mut() {
+            *x = 1f32;
+        }
+        self.clone().process(input)
+    }
+
+    /// Both filters one after the other.
+    pub fn cascade(&self, other: &TransferFunction) -> Self {
+        Self {
+            b: poly_mul(&self.b, &other.b),
+            a: poly_mul(&self.a, &other.a),
+        }
+    }
+
+    /// Both filters on the same input, summed.
+    pub fn parallel(&self, other: &TransferFunction) -> Self {
+        Self {
+            b: poly_add(&poly_mul(&self.b, &other.a), &poly_mul(&other.b, &self.a)),
+            a: poly_mul(&self.a, &other.a),
+        }
+    }
+
+    pub fn to_zpk(&self) -> ZeroPoleGain {
+        // multiplying by z^L, L being the order, gives polynomials in z with the coefficients in
+        // the same order: b[0] z^L + b[1] z^(L-1) + ...
+        let len = self.order() + 1;
+        let pad = |coefficients: &[f32]| {
+            let mut x = coefficients.to_vec();
+            x.resize(len, 0f32);
+            x
+        };
+        let (b, a) = (pad(&self.b), pad(&self.a));
+        let leading = b.iter().position(|x| *x != 0f32);
+        ZeroPoleGain {
+            zeros: leading.map(|i| roots(&b[i..])).unwrap_or_default(),
+            poles: roots(&a),
+            gain: leading.map(|i| b[i] / a[0]).unwrap_or_default(),
+        }
+    }
+
+    pub fn from_zpk(zpk: &ZeroPoleGain) -> anyhow::Result<Self> {
+        if zpk.zeros.len() > zpk.poles.len() {
+            bail!(
+                "{} zeros and {} poles isn't causal",
+                zpk.zeros.len(),
+                zpk.poles.len()
+            );
+        }
+        // dividing by z^P, P the number of poles, delays the numerator by the missing zeros
+        let mut b = vec![0f32; zpk.poles.len() - zpk.zeros.len()];
+        b.extend(poly_from_roots(&zpk.zeros).iter().map(|x| x * zpk.gain));
+        Self::new(b, poly_from_roots(&zpk.poles))
+    }
+
+    pub fn poles(&self) -> Vec<Complex> {
+        roots(&{
+            let mut a = self.a.clone();
+            a.resize(self.order() + 1, 0f32);
+            a
+        })
+    }
+
+    /// All the poles inside the unit circle.
+    pub fn is_stable(&self) -> bool {
+        self.poles().iter().all(|x| x.norm() < 1f32)
+    }
+}
+
+impl ZeroPoleGain {
+    pub fn is_stable(&self) -> bool {
+        self.poles.iter().all(|x| x.norm() < 1f32)
+    }
+}
+
+fn poly_mul(x: &[f32], y: &[f32]) -> Vec<f32> {
+    if x.is_empty() || y.is_empty() {
+        return vec![];
+    }
+    let mut out = vec![0f32; x.len() + y.len() - 1];
+    for (i, a) in x.iter().enumerate() {
+        for (j, b) in y.iter().enumerate() {
+            out[i + j] += a * b;
+        }
+    }
+    out
+}
+
+fn poly_add(x: &[f32], y: &[f32]) -> Vec<f32> {
+    (0..x.len().max(y.len()))
+        .map(|i| x.get(i).unwrap_or(&0f32) + y.get(i).unwrap_or(&0f32))
+        .collect()
+}
+
+/// Coefficients of `Π(z - roots)`, highest power first. Complex roots have to come with their
+/// conjugates for the result to be real.
+pub(crate) fn poly_from_roots(roots: &[Complex]) -> Vec<f32> {
+    let mut poly = vec![Complex::ONE];
+    for root in roots {
+        let mut next = vec![Complex::ZERO; poly.len() + 1];
+        for (i, c) in poly.iter().enumerate() {
+            next[i] += *c;
+            next[i + 1] += -(*c * *root);
+        }
+        poly = next;
+    }
+    poly.into_iter().map(|x| x.re).collect()
+}
+
+/// Roots of a polynomial with the highest power first, with the Durand-Kerner method.
+///
+/// https://en.wikipedia.org/wiki/Durand%E2%80%93Kerner_method
+pub(crate) fn roots(coefficients: &[f32]) -> Vec<Complex> {
+    let leading = match coefficients.iter().position(|x| *x != 0f32) {
+        Some(i) => i,
+        None => return vec![],
+    };
+    // trailing zeros are roots at the origin
+    let trailing = coefficients
+        .iter()
+        .rev()
+        .take_while(|x| **x == 0f32)
+        .count();
+    let monic: Vec<f32> = coefficients[leading..coefficients.len() - trailing]
+        .iter()
+        .map(|x| x / coefficients[leading])
+        .collect();
+    let degree = monic.len() - 1;
+
+    let eval = |z: Complex| {
+        monic
+            .iter()
+            .fold(Complex::ZERO, |acc, c| acc * z + Complex::from(*c))
+    };
+    let mut roots: Vec<Complex> = (0..degree)
+        .map(|k| Complex::from_polar(0.9f32, 2f32 * PI * k as f32 / degree as f32 + 0.4f32))
+        .collect();
+    for _ in 0..500 {
+        let mut change = 0f32;
+        for i in 0..degree {
+            let denominator = (0..degree)
+                .filter(|j| *j != i)
+                .fold(Complex::ONE, |acc, j| acc * (roots[i] - roots[j]));
+            if denominator.norm_sqr() == 0f32 {
+                continue;
+            }
+            let step = eval(roots[i]) / denominator;
+            roots[i] = roots[i] - step;
+            change = change.max(step.norm());
+        }
+        if change < 1e-7 {
+            break;
+        }
+    }
+    // snap almost real roots, so conjugate pairs expand back to real coefficients
+    for root in roots.iter_mut() {
+        if root.im.abs() < 1e-5 {
+            root.im = 0f32;
+        }
+    }
+    roots.extend(std::iter::repeat_n(Complex::ZERO, trailing));
+    roots
+}
+
+impl Block<Wave> for TransferFunction {
+    type Output = Wave;
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.process_chunk(&input, &mut vec![])
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        vis::visualize_simple_box(&format!("H(z)\norder {}", self.order()), out)
+    }
+}
+
+impl Streaming for TransferFunction {
+    /// `state[i]` holds the sum waiting for z^-(i + 1).
+    type State = Vec<f32>;
+
+    fn process_chunk(&mut self, chunk: &[f32], state: &mut Self::State) -> Wave {
+        let order = self.order();
+        let (b, a) = (&self.b, &self.a);
+        let coefficient = |x: &[f32], i: usize| x.get(i).copied().unwrap_or_default();
+
+        // transposed direct form II
+        state.resize(order, 0f32);
+        chunk
+            .iter()
+            .map(|&x| {
+                let y = coefficient(b, 0) * x + state.first().copied().unwrap_or_default();
+                for i in 0..order {
+                    let next = state.get(i + 1).copied().unwrap_or_default();
+                    state[i] = coefficient(b, i + 1) * x - coefficient(a, i + 1) * y + next;
+                }
+                y
+            })
+            .collect()
+    }
+}
+
+/// Blocks that are linear and time invariant, and so completely described by a transfer
+/// function.
+pub trait Lti {
+    fn transfer_function(&self) -> TransferFunction;
+}
+
+impl Lti for TransferFunction {
+    fn transfer_function(&self) -> TransferFunction {
+        self.clone()
+    }
+}
+
+impl Lti for Biquad {
+    fn transfer_function(&self) -> TransferFunction {
+        let ([b0, b1, b2], [a1, a2]) = self.coefficients();
+        TransferFunction {
+            b: vec![b0, b1, b2],
+            a: vec![1f32, a1, a2],
+        }
+    }
+}
+
+impl Lti for ConstMultiplier {
+    fn transfer_function(&self) -> TransferFunction {
+        TransferFunction::gain(self.0)
+    }
+}
+
+impl Lti for Delay {
+    fn transfer_function(&self) -> TransferFunction {
+        TransferFunction::delay(self.0)
+    }
+}
+
+const PLANE_SIZE: f32 = BOX_SIZE * 2f32;
+
+/// Wraps an LTI block and draws its poles (`x`) and zeros (`o`) in the z-plane under it,
+/// with the unit circle.
+#[derive(Debug)]
+pub struct PoleZeroView<B> {
+    pub block: B,
+}
+
+impl<B> PoleZeroView<B> {
+    pub fn new(block: B) -> Self {
+        Self { block }
+    }
+}
+
+fn draw_plane(d: &mut Canvas, zpk: &ZeroPoleGain) {
+    let rec = Rectangle {
+        x: 0f32,
+        y: 0f32,
+        width: PLANE_SIZE,
+        height: PLANE_SIZE,
+    };
+    vis::draw_border(d, rec);
+    let center = Vector2::new(PLANE_SIZE / 2f32, PLANE_SIZE / 2f32);
+    // fit the unit circle and every root, roots at infinity don't exist in z^-1 form
+    let extent = zpk
+        .zeros
+        .iter()
+        .chain(zpk.poles.iter())
+        .map(Complex::norm)
+        .fold(1f32, f32::max)
+        .min(10f32);
+    let scale = (PLANE_SIZE / 2f32 - T * 2f32) / extent;
+    let to_screen = |x: Complex| {
+        Vector2::new(
+            (center.x + x.re.clamp(-extent, extent) * scale).trunc(),
+            (center.y - x.im.clamp(-extent, extent) * scale).trunc(),
+        )
+    };
+
+    d.draw_line_ex(
+        Vector2::new(T, center.y),
+        Vector2::new(PLANE_SIZE - T, center.y),
+        1f32,
+        Color::GRAY,
+    );
+    d.draw_line_ex(
+        Vector2::new(center.x, T),
+        Vector2::new(center.x, PLANE_SIZE - T),
+        1f32,
+        Color::GRAY,
+    );
+    d.draw_circle_lines(center, scale.trunc(), vis::BORDER_COLOR);
+
+    for zero in zpk.zeros.iter() {
+        d.draw_circle_lines(to_screen(*zero), T, vis::LINE_COLORS[1]);
+    }
+    for pole in zpk.poles.iter() {
+        let p = to_screen(*pole);
+        let color = if pole.norm() < 1f32 {
+            vis::LINE_COLORS[2]
+        } else {
+            Color::RED
+        };
+        d.draw_line_ex(p - Vector2::new(T, T), p + Vector2::new(T, T), 1f32, color);
+        d.draw_line_ex(
+            p - Vector2::new(T, -T),
+            p + Vector2::new(T, -T),
+            1f32,
+            color,
+        );
+    }
+
+    let text = if zpk.is_stable() {
+        "stable"
+    } else {
+        "unstable"
+    };
+    d.draw_text(text, T as _, T as _, 1, vis::TEXT_COLOR);
+}
+
+impl<B: Block<Wave, Output = Wave> + Lti> Block<Wave> for PoleZeroView<B> {
+    type Output = Wave;
+
+    fn parameters(&mut self) -> Option<&mut dyn Parameters> {
+        self.block.parameters()
+    }
+
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.block.process(input)
+    }
+
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
+        let (out, result) = self.block.process_and_visualize(input);
+        let zpk = self.block.transfer_function().to_zpk();
+
+        let (block, width, height) = match result {
+            VisualizeResult::Block(layout) => {
+                let (width, height) = (layout.width, layout.height);
+                (Some(layout), width, height)
+            }
+            VisualizeResult::None => (None, 0f32, 0f32),
+        };
+        let (inputs, outputs) = match &block {
+            Some(layout) => (
+                layout.input_connections.clone(),
+                layout.output_connections.clone(),
+            ),
+            None => {
+                let y = (PLANE_SIZE / 2f32).trunc();
+                (
+                    vec![Vector2::new(0f32, y)],
+                    vec![Vector2::new(PLANE_SIZE, y)],
+                )
+            }
+        };
+        let plane_y = (height + T * 2f32).trunc();
+        let layout = Layout::new(width.max(PLANE_SIZE), plane_y + PLANE_SIZE, move |d| {
+            if let Some(block) = block {
+                block.paint(d, Vector2::zero());
+            }
+            d.translated(Vector2::new(0f32, plane_y), |d| draw_plane(d, &zpk));
+        })
+        .with_ports(inputs, outputs);
+        (out, VisualizeResult::Block(layout))
+    }
+
+    fn on_hover(&mut self, pos: Vector2, context: &mut ControlContext) -> ControlResult {
+        self.block.on_hover(pos, context)
+    }
+
+    fn on_unhover(&mut self, context: &mut ControlContext) -> ControlResult {
+        self.block.on_unhover(context)
+    }
+
+    fn on_mouse(
+        &mut self,
+        pos: Vector2,
+        event: MouseEvent,
+        context: &mut ControlContext,
+    ) -> ControlResult {
+        self.block.on_mouse(pos, event, context)
+    }
+
+    fn add_metadata(&mut self, key: &str, value: &str) {
+        self.block.add_metadata(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::blocks::synths::Noise;
+    use crate::dsp::blocks::BiquadType;
+
+    fn assert_close(a: &[f32], b: &[f32], tolerance: f32) {
+        assert_eq!(a.len(), b.len(), "{a:?} != {b:?}");
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < tolerance, "{a:?} != {b:?}");
+        }
+    }
+
+    /// Whether every root of `expected` is found in `roots`, in any order.
+    fn assert_roots(roots: &[Complex], expected: &[Complex]) {
+        assert_eq!(roots.len(), expected.len(), "{roots:?} != {expected:?}");
+        for x in expected {
+            assert!(
+                roots.iter().any(|r| (*r - *x).norm() < 1e-3),
+                "{x:?} not in {roots:?}"
+            );
+        }
+    }
+
+    fn resonance(radius: f32, freq: f32) -> TransferFunction {
+        let w = 2f32 * PI * freq / SR as f32;
+        TransferFunction::new(
+            vec![1f32, 0f32, -1f32],
+            vec![1f32, -2f32 * radius * w.cos(), radius * radius],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn roots_of_known_polynomials() {
+        let real = |x: f32| Complex::from(x);
+        // (z - 1)(z - 2)
+        assert_roots(&roots(&[1f32, -3f32, 2f32]), &[real(1f32), real(2f32)]);
+        // z^2 + 1
+        assert_roots(
+            &roots(&[1f32, 0f32, 1f32]),
+            &[Complex::new(0f32, 1f32), Complex::new(0f32, -1f32)],
+        );
+        // 2z(z + 0.5), the trailing zero is a root at the origin
+        assert_roots(&roots(&[2f32, 1f32, 0f32]), &[real(-0.5f32), real(0f32)]);
+        assert!(roots(&[0f32, 0f32]).is_empty());
+    }
+
+    #[test]
+    fn zpk_round_trips() {
+        for t in BiquadType::ALL {
+            let h = Biquad::builder()
+                .t(t)
+                .cutoff(800f32)
+                .q(2f32)
+                .build()
+                .transfer_function();
+            let back = TransferFunction::from_zpk(&h.to_zpk()).unwrap();
+            assert_close(&back.b, &h.b, 1e-4);
+            assert_close(&back.a, &h.a, 1e-4);
+        }
+        let h = resonance(0.9f32, 3000f32).cascade(&TransferFunction::delay(2));
+        let back = TransferFunction::from_zpk(&h.to_zpk()).unwrap();
+        assert_close(&back.b, &h.b, 1e-4);
+        assert_close(&back.a[..h.a.len()], &h.a, 1e-4);
+    }
+
+    #[test]
+    fn cascades_multiply_and_parallels_add() {
+        let (f, g) = (
+            Biquad::builder().cutoff(500f32).build().transfer_function(),
+            resonance(0.95f32, 2000f32),
+        );
+        let (cascade, parallel) = (f.cascade(&g), f.parallel(&g));
+        // f32 coefficients, the products of polynomials lose a few digits
+        let close = |a: Complex, b: Complex| (a - b).norm() < 1e-2 * b.norm().max(1f32);
+        for freq in [50f32, 500f32, 2000f32, 10000f32] {
+            let (x, y) = (f.response(freq), g.response(freq));
+            assert!(close(cascade.response(freq), x * y), "{freq}Hz");
+            assert!(close(parallel.response(freq), x + y), "{freq}Hz");
+        }
+    }
+
+    #[test]
+    fn stability_follows_the_unit_circle() {
+        assert!(resonance(0.999f32, 1000f32).is_stable());
+        assert!(!resonance(1.001f32, 1000f32).is_stable());
+        assert!(TransferFunction::new(vec![1f32], vec![1f32, -0.999f32])
+            .unwrap()
+            .is_stable());
+        assert!(!TransferFunction::new(vec![1f32], vec![1f32, -1.001f32])
+            .unwrap()
+            .is_stable());
+        assert!(resonance(0.999f32, 1000f32).to_zpk().is_stable());
+        assert!(!resonance(1.001f32, 1000f32).to_zpk().is_stable());
+    }
+
+    #[test]
+    fn chunks_carry_the_state_over() {
+        let mut h = resonance(0.99f32, 440f32).cascade(&TransferFunction::delay(3));
+        let input = Noise::default().samples(1000);
+        let whole = h.process(input.clone());
+        let mut state = Default::default();
+        let chunked: Wave = input
+            .chunks(37)
+            .flat_map(|chunk| h.process_chunk(chunk, &mut state))
+            .collect();
+        assert_close(&chunked, &whole, 1e-5);
+    }
+}
@@ -1,11 +1,18 @@
+use std::cell::Cell;
 use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use crate::dsp;
 use crate::dsp::Wave;
 use crate::graph::{Block, DInto};
+use crate::layout::Layout;
+use crate::render::Canvas;
+use crate::wav::WavWriter;
 use raylib::prelude::*;
 use rodio::Source;
 use rodio::{OutputStream, OutputStreamHandle, Sink};
+use serde::{Deserialize, Serialize};
 
 pub const BOX_SIZE: f32 = 55f32;
 pub const BORDER_COLOR: Color = Color::WHITE;
@@ -20,27 +27,10 @@ pub const LINE_COLORS: [Color; 4] = [
     Color::BROWN,
 ];
 
-pub struct DrawContext<'a, 'b> {
-    pub thread: &'a RaylibThread,
-    pub rl: &'b mut RaylibHandle,
-}
-
-impl<'a, 'b> DrawContext<'a, 'b> {
-    pub fn get_texture(&mut self, width: u32, height: u32) -> RenderTexture2D {
-        self.rl
-            .load_render_texture(self.thread, width, height)
-            .unwrap()
-    }
-}
-
+#[derive(Debug)]
 pub enum VisualizeResult {
     None,
-    SimpleTexture(RenderTexture2D),
-    Block {
-        texture: RenderTexture2D,
-        input_connections: Vec<Vector2>,
-        output_connections: Vec<Vector2>,
-    },
+    Block(Layout),
 }
 
 #[derive(Debug)]
@@ -52,51 +42,33 @@ impl<I> Block<I> for Identity {
         input
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: I,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
+    fn process_and_visualize(&mut self, input: I) -> (Self::Output, VisualizeResult) {
         let out = self.process(input);
 
-        let tx = context.get_texture(BOX_SIZE as _, BOX_SIZE as _);
-
         let center = Vector2::new(BOX_SIZE, BOX_SIZE) / 2f32;
         (
             out,
-            VisualizeResult::Block {
-                texture: tx,
-                input_connections: vec![Vector2::new(center.x, center.y)],
-                output_connections: vec![Vector2::new(center.x, center.y)],
-            },
+            VisualizeResult::Block(
+                Layout::new(BOX_SIZE, BOX_SIZE, |_| {}).with_ports(vec![center], vec![center]),
+            ),
         )
     }
 }
 
 impl VisualizeResult {
-    pub fn as_simple_texture(&self) -> Option<&RenderTexture2D> {
+    /// Paints the arranged diagram into a single canvas.
+    pub fn into_canvas(self) -> Option<Canvas> {
         match self {
             VisualizeResult::None => None,
-            VisualizeResult::SimpleTexture(x) => Some(x),
-            VisualizeResult::Block {
-                texture,
-                input_connections: _,
-                output_connections: _,
-            } => Some(texture),
+            VisualizeResult::Block(layout) => Some(layout.into_canvas()),
         }
     }
 }
 
-pub fn draw_border(d: &mut impl RaylibDraw, rec: Rectangle) {
+pub fn draw_border(d: &mut Canvas, rec: Rectangle) {
     d.draw_rectangle_lines_ex(rec, T / 1.5f32, Color::WHITE);
 }
-pub fn draw_wave(
-    d: &mut impl RaylibDraw,
-    rec: Rectangle,
-    wave_in: &[f32],
-    color: Color,
-    spacing: f32,
-) {
+pub fn draw_wave(d: &mut Canvas, rec: Rectangle, wave_in: &[f32], color: Color, spacing: f32) {
     let n = rec.width.trunc() as usize;
     let step = (wave_in.len() as f64 / n as f64).ceil() as usize;
 
@@ -162,7 +134,8 @@ pub fn draw_wave(
     }
 
     let get_y = |sample: f32| (center_y - (sample / max) * (rec.height / 2.5f32)).trunc();
-    let mut last_point = get_y(wave[0]);
+    let mut points = Vec::with_capacity(n_samples + 1);
+    points.push(Vector2::new(rec.x + offset - spacing, get_y(wave[0])));
     for sample in wave {
         let y = get_y(sample);
 
@@ -176,26 +149,84 @@ pub fn draw_wave(
             );
         }
 
-        // Draw connecting line
-        d.draw_line_ex(
-            Vector2::new(rec.x + offset - spacing, last_point),
-            Vector2::new(rec.x + offset, y),
-            1f32,
-            color,
-        );
-
-        last_point = y;
+        points.push(Vector2::new(rec.x + offset, y));
         offset += spacing;
     }
+
+    // Draw connecting lines
+    d.draw_line_strip(points, 1f32, color);
 }
 
-pub fn draw_wave_box(
-    d: &mut impl RaylibDraw,
+/// Polyline of `values` spread over the width of `rec`, `min` at the bottom and `max` at the
+/// top. Unlike `draw_wave` the values are not normalized.
+pub fn draw_curve(
+    d: &mut Canvas,
     rec: Rectangle,
-    wave_in: &[f32],
+    values: &[f32],
+    min: f32,
+    max: f32,
     color: Color,
-    spacing: f32,
 ) {
+    if values.is_empty() || max <= min {
+        return;
+    }
+    let step = rec.width / (values.len().max(2) - 1) as f32;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let y = (x.clamp(min, max) - min) / (max - min);
+            Vector2::new(
+                (rec.x + step * i as f32).trunc(),
+                (rec.y + rec.height - y * rec.height).trunc(),
+            )
+        })
+        .collect();
+    d.draw_line_strip(points, 1f32, color);
+}
+
+/// Color of `t` in [0, 1] on a black, violet, orange and white scale.
+pub fn heat_color(t: f32) -> Color {
+    const STOPS: [Color; 4] = [Color::BLACK, Color::BLUEVIOLET, Color::ORANGE, Color::WHITE];
+    let x = t.clamp(0f32, 1f32) * (STOPS.len() - 1) as f32;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let frac = x - i as f32;
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * frac).round() as u8;
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    Color::new(lerp(a.r, b.r), lerp(a.g, b.g), lerp(a.b, b.b), 255)
+}
+
+/// Draws `columns` (e.g. one per frame) left to right with their first value at the bottom,
+/// colored from the smallest to the largest value.
+pub fn draw_heatmap(d: &mut Canvas, rec: Rectangle, columns: &[Vec<f32>]) {
+    let rows = columns.iter().map(|x| x.len()).max().unwrap_or_default();
+    if rows == 0 {
+        return;
+    }
+    let (min, max) = columns
+        .iter()
+        .flatten()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+            (min.min(*x), max.max(*x))
+        });
+    let range = (max - min).max(f32::EPSILON);
+    let (width, height) = (rec.width / columns.len() as f32, rec.height / rows as f32);
+    for (i, column) in columns.iter().enumerate() {
+        for (j, x) in column.iter().enumerate() {
+            d.draw_rectangle_rec(
+                Rectangle {
+                    x: rec.x + width * i as f32,
+                    y: rec.y + rec.height - height * (j + 1) as f32,
+                    width: width.max(1f32),
+                    height: height.max(1f32),
+                },
+                heat_color((x - min) / range),
+            );
+        }
+    }
+}
+
+pub fn draw_wave_box(d: &mut Canvas, rec: Rectangle, wave_in: &[f32], color: Color, spacing: f32) {
     // center line
     d.draw_line_ex(
         Vector2::new(0f32, (rec.height / 2f32).trunc()),
@@ -207,7 +238,7 @@ pub fn draw_wave_box(
     draw_border(d, rec);
 }
 
-pub fn draw_simple_bock(d: &mut impl RaylibDraw, text: &str) -> Vector2 {
+pub fn draw_simple_bock(d: &mut Canvas, text: &str) -> Vector2 {
     let center = (BOX_SIZE / 2f32).trunc();
     let h = (BOX_SIZE / 2f32) + T;
     let num_lines = text.split("\n").count();
@@ -232,35 +263,128 @@ pub fn draw_simple_bock(d: &mut impl RaylibDraw, text: &str) -> Vector2 {
 
 // visual/analyze blocks
 
-pub fn visualize_simple_box<O>(
-    context: &mut DrawContext,
-    text: &str,
-    out: O,
-) -> (O, VisualizeResult) {
-    let mut tx = context.get_texture(BOX_SIZE as _, BOX_SIZE as _);
-    tx.set_texture_filter(
-        context.thread,
-        raylib::ffi::TextureFilter::TEXTURE_FILTER_ANISOTROPIC_16X,
-    );
-    let mut d = context.rl.begin_drawing(context.thread);
-    let mut d = d.begin_texture_mode(context.thread, &mut tx);
-
-    let center = draw_simple_bock(&mut d, text);
-    drop(d);
+pub fn visualize_simple_box<O>(text: &str, out: O) -> (O, VisualizeResult) {
+    let text = text.to_string();
+    let center = (BOX_SIZE / 2f32).trunc();
     (
         out,
-        VisualizeResult::Block {
-            texture: tx,
-            input_connections: vec![Vector2::new(0f32, center.y)],
-            output_connections: vec![Vector2::new(BOX_SIZE, center.y)],
-        },
+        VisualizeResult::Block(
+            Layout::new(BOX_SIZE, BOX_SIZE, move |d| {
+                draw_simple_bock(d, &text);
+            })
+            .with_ports(
+                vec![Vector2::new(0f32, center)],
+                vec![Vector2::new(BOX_SIZE, center)],
+            ),
+        ),
     )
 }
 
+/// A red box with the message, drawn in place of a system that failed to run.
+pub fn visualize_error(message: &str) -> VisualizeResult {
+    let message = message.to_string();
+    let longest = message.lines().map(|x| x.len()).max().unwrap_or_default();
+    let width = (longest as f32 * 6f32 + T * 4f32).max(BOX_SIZE);
+    let height = message.lines().count() as f32 * 10f32 + T * 4f32;
+    VisualizeResult::Block(Layout::new(width, height, move |d| {
+        d.draw_text(&message, (T * 2f32) as _, (T * 2f32) as _, 3, Color::RED);
+        d.draw_rectangle_lines_ex(
+            Rectangle {
+                width,
+                height,
+                ..Default::default()
+            },
+            T / 1.5f32,
+            Color::RED,
+        );
+    }))
+}
+
+/// Puts a `width` by `height` plot, drawn by `painter` in the given rectangle, to the right
+/// of a block. The output port moves to the right of the plot.
+pub fn with_plot(
+    result: VisualizeResult,
+    width: f32,
+    height: f32,
+    painter: impl FnOnce(&mut Canvas, Rectangle) + 'static,
+) -> VisualizeResult {
+    let VisualizeResult::Block(block) = result else {
+        return VisualizeResult::None;
+    };
+    let plot_x = (block.width + T * 2f32).trunc();
+    let inputs = block.input_connections.clone();
+    let output_y = block
+        .output_connections
+        .first()
+        .map(|x| x.y)
+        .unwrap_or(BOX_SIZE / 2f32);
+    let layout = Layout::new(plot_x + width, block.height.max(height), move |d| {
+        block.paint(d, Vector2::zero());
+        let rec = Rectangle {
+            x: plot_x,
+            y: 0f32,
+            width,
+            height,
+        };
+        painter(d, rec);
+        draw_border(d, rec);
+    })
+    .with_ports(inputs, vec![Vector2::new(plot_x + width, output_y)]);
+    VisualizeResult::Block(layout)
+}
+
+/// Where the blocks of a setup write their files and play their sound, given when they are
+/// built. In the window [`AudioSink`]s play on the audio device and files go in `./target`, the
+/// `render` command writes them all, sinks included, to its output directory.
+#[derive(Debug, Clone, Default)]
+pub struct OutputConfig {
+    render_dir: Option<PathBuf>,
+    /// Sinks built so far, numbering the files they are rendered to.
+    sinks: Rc<Cell<usize>>,
+}
+
+impl OutputConfig {
+    pub fn render<T: AsRef<Path>>(dir: T) -> Self {
+        Self {
+            render_dir: Some(dir.as_ref().to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the `render` command is processing the diagram, blocks writing files on request
+    /// write them then.
+    pub fn is_rendering(&self) -> bool {
+        self.render_dir.is_some()
+    }
+
+    /// Where blocks write their files: relative paths go in the `render` directory when
+    /// rendering and in `./target` otherwise, absolute ones are kept.
+    pub fn path<T: AsRef<Path>>(&self, path: T) -> PathBuf {
+        let dir = self.render_dir.as_deref().unwrap_or(Path::new("./target"));
+        dir.join(path)
+    }
+
+    /// Plays on the audio device, or writes `sink-<n>.wav` when rendering.
+    pub fn audio_sink(&self) -> anyhow::Result<AudioSink> {
+        let Some(dir) = &self.render_dir else {
+            return AudioSink::try_default();
+        };
+        let n = self.sinks.replace(self.sinks.get() + 1);
+        AudioSink::to_file(dir.join(format!("sink-{n}.wav")))
+    }
+}
+
+enum AudioOutput {
+    Device {
+        stream: OutputStream,
+        stream_handle: OutputStreamHandle,
+        sink: Sink,
+    },
+    File(WavWriter),
+}
+
 pub struct AudioSink {
-    stream: OutputStream,
-    stream_handle: OutputStreamHandle,
-    sink: Sink,
+    output: AudioOutput,
 }
 
 impl Debug for AudioSink {
@@ -275,9 +399,18 @@ impl AudioSink {
         let sink = Sink::try_new(&stream_handle)?;
 
         Ok(Self {
-            stream,
-            stream_handle,
-            sink,
+            output: AudioOutput::Device {
+                stream,
+                stream_handle,
+                sink,
+            },
+        })
+    }
+
+    /// Writes what would be played to a WAV file instead.
+    pub fn to_file<T: AsRef<Path>>(path: T) -> anyhow::Result<Self> {
+        Ok(Self {
+            output: AudioOutput::File(WavWriter::new(path)?),
         })
     }
 }
@@ -286,26 +419,31 @@ impl Block<Wave> for AudioSink {
     type Output = Wave;
 
     fn process(&mut self, input: Wave) -> Self::Output {
+        let sink = match &mut self.output {
+            AudioOutput::Device { sink, .. } => sink,
+            AudioOutput::File(writer) => return writer.process(input),
+        };
+
         let source =
             rodio::buffer::SamplesBuffer::new(1, dsp::SR as u32, input.clone()).repeat_infinite();
 
         // let total_duration = input.len() as f32 / dsp::SR as f32;
         // let current_sound_pos = (sink.get_pos().as_secs_f32() / total_duration) % total_duration;
 
-        self.sink.append(source);
-        self.sink.set_volume(0.02);
-        self.sink.play();
+        sink.append(source);
+        sink.set_volume(0.02);
+        sink.play();
 
         input
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: Wave,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
         let out = self.process(input);
-        visualize_simple_box(context, "Sink", out)
+        let text = match &self.output {
+            AudioOutput::File(writer) => writer.label("Sink"),
+            AudioOutput::Device { .. } => "Sink".to_string(),
+        };
+        visualize_simple_box(&text, out)
     }
 }
 
@@ -328,7 +466,7 @@ impl<const N: usize> WaveView<N> {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum WaveViewType {
     Grow,
     #[default]
@@ -342,11 +480,7 @@ impl<I: DInto<[Wave; N]>, const N: usize> Block<I> for WaveView<N> {
         input
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: I,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
+    fn process_and_visualize(&mut self, input: I) -> (Self::Output, VisualizeResult) {
         let out: [Wave; N] = input.into();
 
         let unit = (if self.is_hovering { 10f32 } else { 2f32 }) * BOX_SIZE;
@@ -377,68 +511,63 @@ impl<I: DInto<[Wave; N]>, const N: usize> Block<I> for WaveView<N> {
             }
         };
 
-        let mut tx = context.get_texture(rec.width as _, rec.height as u32 + stats_height as u32);
-        tx.set_texture_filter(
-            context.thread,
-            raylib::ffi::TextureFilter::TEXTURE_FILTER_ANISOTROPIC_16X,
-        );
-        let mut d = context.rl.begin_drawing(context.thread);
-        let mut d = d.begin_texture_mode(context.thread, &mut tx);
-
-        for (i, wave) in out.iter().enumerate() {
-            d.draw_line_ex(
-                Vector2::new(0f32, (rec.height / 2f32).trunc()),
-                Vector2::new(rec.width, (rec.height / 2f32).trunc()),
-                1f32,
-                Color::GRAY,
-            );
-
-            let inner_box = Rectangle {
-                width: rec.width - 10f32,
-                height: rec.height,
-                x: 5f32,
-                y: 0f32,
-            };
-            // FIXME: draw_wave scales the y values based on y resulting all waveforms being normalized.
-            draw_wave(
-                &mut d,
-                inner_box,
-                &wave,
-                LINE_COLORS[i],
-                if matches!(self.t, WaveViewType::Grow) {
-                    0f32
-                } else {
-                    1f32
-                },
-            );
-        }
-        draw_border(&mut d, rec);
-
-        for i in 0..N {
-            let mean = out[i].iter().fold(0f32, |acc, x| acc + x) / out[i].len() as f32;
-            let std = (out[i]
-                .iter()
-                .map(|x| (*x - mean).powi(2))
-                .fold(0f32, |acc, x| acc + x)
-                / out[i].len() as f32)
-                .sqrt();
-            d.draw_text(
-                format!("u={mean:.3}, s={std:.3}").as_str(),
-                rec.x as _,
-                (rec.height + (stat_font_size * i as f32)) as _,
-                stat_font_size as _,
-                LINE_COLORS[i],
-            );
-        }
+        let spacing = if matches!(self.t, WaveViewType::Grow) {
+            0f32
+        } else {
+            1f32
+        };
+        let waves = out.clone();
+        let painter = move |d: &mut Canvas| {
+            for (i, wave) in waves.iter().enumerate() {
+                d.draw_line_ex(
+                    Vector2::new(0f32, (rec.height / 2f32).trunc()),
+                    Vector2::new(rec.width, (rec.height / 2f32).trunc()),
+                    1f32,
+                    Color::GRAY,
+                );
+
+                let inner_box = Rectangle {
+                    width: rec.width - 10f32,
+                    height: rec.height,
+                    x: 5f32,
+                    y: 0f32,
+                };
+                // FIXME: draw_wave scales the y values based on y resulting all waveforms being normalized.
+                draw_wave(d, inner_box, &wave, LINE_COLORS[i], spacing);
+            }
+            draw_border(d, rec);
+
+            for i in 0..N {
+                let mean = waves[i].iter().fold(0f32, |acc, x| acc + x) / waves[i].len() as f32;
+                let std = (waves[i]
+                    .iter()
+                    .map(|x| (*x - mean).powi(2))
+                    .fold(0f32, |acc, x| acc + x)
+                    / waves[i].len() as f32)
+                    .sqrt();
+                d.draw_text(
+                    format!("u={mean:.3}, s={std:.3}").as_str(),
+                    rec.x as _,
+                    (rec.height + (stat_font_size * i as f32)) as _,
+                    stat_font_size as _,
+                    LINE_COLORS[i],
+                );
+            }
+        };
 
-        drop(d);
         (
             DInto::from(out),
-            VisualizeResult::Block {
-                texture: tx,
-                input_connections: vec![Vector2::new(0f32, rec.height / 2f32)],
-                output_connections: vec![Vector2::new(rec.width, rec.height / 2f32)],
-            },
+            VisualizeResult::Block(
+                Layout::new(
+                    rec.width.trunc(),
+                    (rec.height + stats_height).trunc(),
+                    painter,
+                )
+                .with_ports(
+                    vec![Vector2::new(0f32, rec.height / 2f32)],
+                    vec![Vector2::new(rec.width, rec.height / 2f32)],
+                ),
+            ),
         )
     }
 
@@ -465,3 +594,169 @@ impl<I: DInto<[Wave; N]>, const N: usize> Block<I> for WaveView<N> {
     //     crate::control::ControlResult::Passthrough
     // }
 }
+
+/// Draws a wave with vertical lines wherever the second wave (e.g. the output of an onset
+/// detector) is nonzero. Passes both through.
+#[derive(Debug, Default)]
+pub struct MarkerView;
+
+impl Block<(Wave, Wave)> for MarkerView {
+    type Output = (Wave, Wave);
+
+    fn process(&mut self, input: (Wave, Wave)) -> Self::Output {
+        input
+    }
+
+    fn process_and_visualize(&mut self, input: (Wave, Wave)) -> (Self::Output, VisualizeResult) {
+        let rec = Rectangle {
+            width: BOX_SIZE * 4f32,
+            height: 50f32,
+            x: 0f32,
+            y: 0f32,
+        };
+        let inner_box = Rectangle {
+            width: rec.width - 10f32,
+            height: rec.height,
+            x: 5f32,
+            y: 0f32,
+        };
+
+        let (wave, markers) = input;
+        let len = wave.len().max(markers.len()).max(1);
+        let xs: Vec<f32> = markers
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| **m != 0f32)
+            .map(|(i, _)| (inner_box.x + inner_box.width * i as f32 / len as f32).trunc())
+            .collect();
+        let count = xs.len();
+
+        let signal = wave.clone();
+        let painter = move |d: &mut Canvas| {
+            d.draw_line_ex(
+                Vector2::new(0f32, (rec.height / 2f32).trunc()),
+                Vector2::new(rec.width, (rec.height / 2f32).trunc()),
+                1f32,
+                Color::GRAY,
+            );
+            if !signal.is_empty() {
+                draw_wave(d, inner_box, &signal, LINE_COLORS[0], 0f32);
+            }
+            for x in &xs {
+                d.draw_line_ex(
+                    Vector2::new(*x, 0f32),
+                    Vector2::new(*x, rec.height),
+                    1f32,
+                    LINE_COLORS[2],
+                );
+            }
+            draw_border(d, rec);
+            d.draw_text(
+                format!("{count} markers").as_str(),
+                rec.x as _,
+                rec.height as _,
+                13,
+                LINE_COLORS[2],
+            );
+        };
+
+        (
+            (wave, markers),
+            VisualizeResult::Block(
+                Layout::new(rec.width.trunc(), (rec.height + 13f32).trunc(), painter).with_ports(
+                    vec![Vector2::new(0f32, rec.height / 2f32)],
+                    vec![Vector2::new(rec.width, rec.height / 2f32)],
+                ),
+            ),
+        )
+    }
+}
+
+/// Draws the first wave against the second one as points, e.g. the in-phase and quadrature
+/// parts of symbols as a constellation. Both axes share the same symmetric scale. Passes
+/// both through.
+#[derive(Debug, Default)]
+pub struct XYView;
+
+impl Block<(Wave, Wave)> for XYView {
+    type Output = (Wave, Wave);
+
+    fn process(&mut self, input: (Wave, Wave)) -> Self::Output {
+        input
+    }
+
+    fn process_and_visualize(&mut self, input: (Wave, Wave)) -> (Self::Output, VisualizeResult) {
+        let size = BOX_SIZE * 2f32;
+        let rec = Rectangle {
+            width: size,
+            height: size,
+            x: 0f32,
+            y: 0f32,
+        };
+
+        let (xs, ys) = input;
+        let scale = xs
+            .iter()
+            .chain(ys.iter())
+            .fold(0f32, |max, x| max.max(x.abs()))
+            .max(1e-6);
+        let to_screen = move |x: f32| (size / 2f32 + x / scale * (size / 2f32 - 4f32)).trunc();
+        let points: Vec<Vector2> = xs
+            .iter()
+            .zip(ys.iter())
+            .map(|(x, y)| Vector2::new(to_screen(*x), size - to_screen(*y)))
+            .collect();
+        let count = points.len();
+
+        let painter = move |d: &mut Canvas| {
+            let middle = (size / 2f32).trunc();
+            d.draw_line_ex(
+                Vector2::new(0f32, middle),
+                Vector2::new(size, middle),
+                1f32,
+                Color::GRAY,
+            );
+            d.draw_line_ex(
+                Vector2::new(middle, 0f32),
+                Vector2::new(middle, size),
+                1f32,
+                Color::GRAY,
+            );
+            for point in &points {
+                d.draw_rectangle_rec(
+                    Rectangle {
+                        x: point.x - 1f32,
+                        y: point.y - 1f32,
+                        width: 2f32,
+                        height: 2f32,
+                    },
+                    LINE_COLORS[0],
+                );
+            }
+            draw_border(d, rec);
+            d.draw_text(
+                format!("{count} points").as_str(),
+                rec.x as _,
+                rec.height as _,
+                13,
+                LINE_COLORS[0],
+            );
+        };
+
+        (
+            (xs, ys),
+            VisualizeResult::Block(
+                Layout::new(rec.width.trunc(), (rec.height + 13f32).trunc(), painter).with_ports(
+                    vec![
+                        Vector2::new(0f32, rec.height / 3f32),
+                        Vector2::new(0f32, rec.height * 2f32 / 3f32),
+                    ],
+                    vec![
+                        Vector2::new(rec.width, rec.height / 3f32),
+                        Vector2::new(rec.width, rec.height * 2f32 / 3f32),
+                    ],
+                ),
+            ),
+        )
+    }
+}
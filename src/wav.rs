@@ -1,15 +1,23 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+    fs::File,
+    io::{Seek, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context};
 
 use crate::{
     dsp::{self, Wave},
     graph::Block,
-    vis::{visualize_simple_box, DrawContext, VisualizeResult},
+    vis::{visualize_simple_box, VisualizeResult},
 };
 
 #[derive(Debug)]
 pub struct WavWriter {
     file: File,
     path: String,
+    /// Why the last write failed.
+    error: Option<String>,
 }
 
 impl WavWriter {
@@ -21,50 +29,310 @@ impl WavWriter {
             file: std::fs::OpenOptions::new()
                 .create(true)
                 .write(true)
-                .open(path)?,
+                .truncate(true)
+                .open(&path)
+                .with_context(|| format!("opening {}", path.as_ref().display()))?,
+            error: None,
         })
     }
-}
 
-impl Block<Wave> for WavWriter {
-    type Output = Wave;
+    /// `text` followed by the last write error, if any.
+    pub(crate) fn label(&self, text: &str) -> String {
+        match &self.error {
+            Some(e) => format!("{text}\n{e}"),
+            None => text.to_string(),
+        }
+    }
 
+    /// Writes `wave` as a mono 32-bit float WAV file.
     #[rustfmt::skip]
-    fn process(&mut self, input: Wave) -> Self::Output {
+    pub fn write(&mut self, wave: &[f32]) -> std::io::Result<()> {
+        let mut header = Vec::with_capacity(44);
+
         // RIFF header
-        self.file.write(b"RIFF").unwrap();
-        self.file.write(&((36 + input.len() * 4) as u32).to_le_bytes()).unwrap();
-        self.file.write(b"WAVE").unwrap();
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&((36 + wave.len() * 4) as u32).to_le_bytes());
+        header.extend_from_slice(b"WAVE");
 
         // FMT header
-        self.file.write(b"fmt ").unwrap();
-        self.file.write(&16u32.to_le_bytes()).unwrap();
-        self.file.write(&0x3u16.to_le_bytes()).unwrap(); /* IEEE float PCM  */
-        self.file.write(&1u16.to_le_bytes()).unwrap(); /* mono  */
-        self.file.write(&(dsp::SR as u32).to_le_bytes()).unwrap();
-        self.file.write(&(((1 * dsp::SR * 4) / 8) as i32).to_le_bytes()).unwrap();
-        self.file.write(&(((1 * 4) / 8) as i16).to_le_bytes()).unwrap();
-        self.file.write(&(32i16).to_le_bytes()).unwrap();
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&0x3u16.to_le_bytes()); /* IEEE float PCM  */
+        header.extend_from_slice(&1u16.to_le_bytes()); /* mono  */
+        header.extend_from_slice(&(dsp::SR as u32).to_le_bytes());
+        header.extend_from_slice(&((dsp::SR * 4) as u32).to_le_bytes()); /* bytes per second */
+        header.extend_from_slice(&4u16.to_le_bytes()); /* bytes per frame */
+        header.extend_from_slice(&32u16.to_le_bytes());
 
         // data header
-        self.file.write(b"data").unwrap();
-        self.file.write(&((input.len() * 4) as u32).to_le_bytes()).unwrap();
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&((wave.len() * 4) as u32).to_le_bytes());
 
-        let data: Vec<_> = input.iter().map(|x| x.to_le_bytes()).flatten().collect();
-        self.file.write(data.as_slice()).unwrap();
-        if data.len() % 2 == 1 {
-          self.file.write(b"\0").unwrap();
-        }
+        let data: Vec<_> = wave.iter().flat_map(|x| x.to_le_bytes()).collect();
+        // every process rewrites the whole file
+        self.file.rewind()?;
+        self.file.set_len(0)?;
+        self.file.write_all(&header)?;
+        self.file.write_all(&data)
+    }
+}
+
+impl Block<Wave> for WavWriter {
+    type Output = Wave;
 
+    fn process(&mut self, input: Wave) -> Self::Output {
+        self.error = self
+            .write(&input)
+            .err()
+            .map(|e| format!("writing {}: {e}", self.path));
         input
     }
 
-    fn process_and_visualize(
-        &mut self,
-        input: Wave,
-        context: &mut DrawContext,
-    ) -> (Self::Output, VisualizeResult) {
+    fn process_and_visualize(&mut self, input: Wave) -> (Self::Output, VisualizeResult) {
         let out = self.process(input);
-        visualize_simple_box(context, &self.path, out)
+        visualize_simple_box(&self.label(&self.path), out)
+    }
+}
+
+/// Reads a mono, `dsp::SR` wave from a PCM (8/16/24/32 bit) or IEEE float WAV file. Channels
+/// are averaged and other sample rates are resampled linearly.
+#[derive(Debug)]
+pub struct WavReader {
+    path: String,
+    wave: Wave,
+}
+
+impl WavReader {
+    pub fn new<T: AsRef<Path>>(path: T) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(&path)?;
+        Ok(Self {
+            path: path.as_ref().to_str().unwrap_or_default().to_string(),
+            wave: parse_wav(&bytes)?,
+        })
+    }
+}
+
+fn parse_wav(bytes: &[u8]) -> anyhow::Result<Wave> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        bail!("not a RIFF/WAVE file");
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at =
+        |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+    let mut format = None;
+    let mut data = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let size = u32_at(pos + 4) as usize;
+        let body = pos + 8..(pos + 8 + size).min(bytes.len());
+        match &bytes[pos..pos + 4] {
+            b"fmt " if body.len() >= 16 => {
+                let mut tag = u16_at(body.start);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real tag at the start of the sub-format GUID.
+                if tag == 0xFFFE && body.len() >= 26 {
+                    tag = u16_at(body.start + 24);
+                }
+                format = Some((
+                    tag,
+                    u16_at(body.start + 2) as usize,
+                    u32_at(body.start + 4) as usize,
+                    u16_at(body.start + 14) as usize,
+                ));
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // chunks are padded to an even size
+        pos += 8 + size + size % 2;
+    }
+
+    let (tag, channels, rate, bits) = format.ok_or_else(|| anyhow!("missing fmt chunk"))?;
+    let data = &bytes[data.ok_or_else(|| anyhow!("missing data chunk"))?];
+    if channels == 0 || rate == 0 {
+        bail!("invalid fmt chunk");
+    }
+
+    let width = bits / 8;
+    let sample = |b: &[u8]| -> Option<f32> {
+        Some(match (tag, bits) {
+            (1, 8) => (b[0] as f32 - 128f32) / 128f32,
+            (1, 16) => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768f32,
+            (1, 24) => i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2147483648f32,
+            (1, 32) => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648f32,
+            (3, 32) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            (3, 64) => f64::from_le_bytes(b[0..8].try_into().ok()?) as f32,
+            _ => return None,
+        })
+    };
+    if width == 0 || sample(&[0u8; 8]).is_none() {
+        bail!("unsupported format {tag} with {bits} bits");
+    }
+
+    let frame = width * channels;
+    let mono: Wave = data
+        .chunks_exact(frame)
+        .map(|f| {
+            f.chunks_exact(width)
+                .map(|b| sample(b).unwrap_or_default())
+                .sum::<f32>()
+                / channels as f32
+        })
+        .collect();
+
+    Ok(resample(&mono, rate))
+}
+
+fn resample(wave: &[f32], rate: usize) -> Wave {
+    if rate == dsp::SR || wave.is_empty() {
+        return wave.to_vec();
+    }
+    let ratio = rate as f64 / dsp::SR as f64;
+    let len = wave.len() * dsp::SR / rate;
+    (0..len)
+        .map(|i| {
+            let t = i as f64 * ratio;
+            let j = t as usize;
+            let frac = (t - j as f64) as f32;
+            let next = wave.get(j + 1).copied().unwrap_or(wave[j]);
+            wave[j] + (next - wave[j]) * frac
+        })
+        .collect()
+}
+
+impl Block<()> for WavReader {
+    type Output = Wave;
+
+    fn process(&mut self, _input: ()) -> Self::Output {
+        self.wave.clone()
+    }
+
+    fn process_and_visualize(&mut self, input: ()) -> (Self::Output, VisualizeResult) {
+        let out = self.process(input);
+        visualize_simple_box(&self.path, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::SR;
+
+    #[test]
+    fn written_files_hold_the_last_wave() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("dsp-blocks-wav-writer.wav");
+        let mut writer = WavWriter::new(&path)?;
+        writer.write(&[0.5f32; 100])?;
+        writer.write(&[0.25f32, -0.25f32])?;
+        let wave = WavReader::new(&path)?.process(());
+        assert_eq!(wave, vec![0.25f32, -0.25f32]);
+        Ok(())
+    }
+
+    /// A RIFF/WAVE file with a `fmt ` chunk of `tag`, and `extensible` adds the sub-format
+    /// GUID behind a 0xFFFE tag.
+    fn file(
+        tag: u16,
+        channels: u16,
+        rate: u32,
+        bits: u16,
+        extensible: bool,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let align = channels * bits / 8;
+        let mut fmt = [
+            &(if extensible { 0xFFFE } else { tag }).to_le_bytes()[..],
+            &channels.to_le_bytes(),
+            &rate.to_le_bytes(),
+            &(rate * align as u32).to_le_bytes(),
+            &align.to_le_bytes(),
+            &bits.to_le_bytes(),
+        ]
+        .concat();
+        if extensible {
+            fmt.extend([22u16.to_le_bytes(), bits.to_le_bytes()].concat());
+            fmt.extend(3u32.to_le_bytes());
+            fmt.extend(tag.to_le_bytes());
+            fmt.extend([0u8; 14]);
+        }
+        let chunk = |id: &[u8], body: &[u8]| {
+            let mut chunk = [id, &(body.len() as u32).to_le_bytes()[..], body].concat();
+            if body.len() % 2 == 1 {
+                chunk.push(0);
+            }
+            chunk
+        };
+        // an odd sized chunk in between to check the padding
+        let body = [
+            &b"WAVE"[..],
+            &chunk(b"fmt ", &fmt),
+            &chunk(b"LIST", b"odd"),
+            &chunk(b"data", data),
+        ]
+        .concat();
+        [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat()
+    }
+
+    #[test]
+    fn pcm_channels_are_averaged() -> anyhow::Result<()> {
+        let data: Vec<u8> = [16384i16, 0, -32768, 16384]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let wave = parse_wav(&file(1, 2, SR as u32, 16, false, &data))?;
+        assert_eq!(wave, vec![0.25f32, -0.25f32]);
+
+        let data: Vec<u8> = [0x400000i32, -0x800000]
+            .iter()
+            .flat_map(|x| x.to_le_bytes()[..3].to_vec())
+            .collect();
+        let wave = parse_wav(&file(1, 1, SR as u32, 24, false, &data))?;
+        assert_eq!(wave, vec![0.5f32, -1f32]);
+        Ok(())
+    }
+
+    #[test]
+    fn extensible_files_use_their_sub_format() -> anyhow::Result<()> {
+        let data: Vec<u8> = [0.75f32, -0.125]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let wave = parse_wav(&file(3, 1, SR as u32, 32, true, &data))?;
+        assert_eq!(wave, vec![0.75f32, -0.125]);
+
+        let data: Vec<u8> = [-16384i16].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let wave = parse_wav(&file(1, 1, SR as u32, 16, true, &data))?;
+        assert_eq!(wave, vec![-0.5f32]);
+        Ok(())
+    }
+
+    #[test]
+    fn other_rates_are_resampled() -> anyhow::Result<()> {
+        // a ramp of one second at 48 kHz
+        let data: Vec<u8> = (0..48000)
+            .flat_map(|i| (i as f32 / 48000f32).to_le_bytes())
+            .collect();
+        let wave = parse_wav(&file(3, 1, 48000, 32, false, &data))?;
+        assert_eq!(wave.len(), SR);
+        for (i, x) in wave.iter().enumerate().step_by(1000) {
+            assert!((x - i as f32 / SR as f32).abs() < 1e-4, "{i}: {x}");
+        }
+
+        assert_eq!(resample(&[1f32, 3f32], SR * 2), vec![1f32]);
+        assert_eq!(
+            resample(&[1f32, 3f32], SR / 2),
+            vec![1f32, 2f32, 3f32, 3f32]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        assert!(parse_wav(b"RIFF\0\0\0\0AVI ").is_err());
+        let data = [0u8; 4];
+        assert!(parse_wav(&file(1, 0, SR as u32, 16, false, &data)).is_err());
+        assert!(parse_wav(&file(1, 1, SR as u32, 12, false, &data)).is_err());
+        assert!(parse_wav(&file(2, 1, SR as u32, 16, false, &data)).is_err());
     }
 }
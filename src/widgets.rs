@@ -0,0 +1,274 @@
+//! Controls drawn under blocks to change their parameters from the diagram.
+//!
+//! Widgets don't own the value they control: blocks keep their parameters and hand them to
+//! the widget when drawing it or when routing a mouse event to it. Widget rectangles are
+//! fixed relative to the block, so the same geometry is used for drawing and hit-testing.
+use std::f32::consts::PI;
+
+use raylib::math::{Rectangle, Vector2};
+
+use crate::control::MouseEvent;
+use crate::layout::Layout;
+use crate::render::Canvas;
+use crate::vis::{self, VisualizeResult, BOX_SIZE, T};
+
+pub const WIDGET_SIZE: f32 = T * 5f32;
+pub const ROW_HEIGHT: f32 = WIDGET_SIZE + T * 2f32;
+
+/// Normalized change per pixel dragged.
+const DRAG_SPEED: f32 = 1f32 / 100f32;
+/// Normalized change per wheel step.
+const SCROLL_STEP: f32 = 1f32 / 50f32;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Scale {
+    Linear,
+    Log,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    pub min: f32,
+    pub max: f32,
+    pub scale: Scale,
+}
+
+impl Range {
+    pub const fn linear(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            scale: Scale::Linear,
+        }
+    }
+
+    /// `min` has to be bigger than zero.
+    pub const fn log(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            scale: Scale::Log,
+        }
+    }
+
+    /// Position of `value` in the range, between 0 and 1.
+    pub fn normalize(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        match self.scale {
+            Scale::Linear => (value - self.min) / (self.max - self.min),
+            Scale::Log => (value / self.min).ln() / (self.max / self.min).ln(),
+        }
+    }
+
+    pub fn denormalize(&self, x: f32) -> f32 {
+        let x = x.clamp(0f32, 1f32);
+        match self.scale {
+            Scale::Linear => self.min + x * (self.max - self.min),
+            Scale::Log => self.min * (self.max / self.min).powf(x),
+        }
+    }
+}
+
+/// Cell `i` out of `n` on row `row` under a block.
+pub fn cell(row: usize, i: usize, n: usize) -> Rectangle {
+    let w = (BOX_SIZE - T) / n as f32;
+    Rectangle {
+        x: (T + w * i as f32).trunc(),
+        y: (BOX_SIZE + T + ROW_HEIGHT * row as f32).trunc(),
+        width: (w - T).trunc(),
+        height: WIDGET_SIZE,
+    }
+}
+
+/// Turned by dragging up and down or scrolling.
+#[derive(Debug, Clone, Copy)]
+pub struct Knob {
+    pub rec: Rectangle,
+    pub range: Range,
+}
+
+impl Knob {
+    pub fn new(rec: Rectangle, range: Range) -> Self {
+        Self { rec, range }
+    }
+
+    pub fn draw(&self, d: &mut Canvas, value: f32) {
+        let r = (self.rec.width.min(self.rec.height) / 2f32).trunc();
+        let center = Vector2::new(
+            (self.rec.x + self.rec.width / 2f32).trunc(),
+            (self.rec.y + self.rec.height / 2f32).trunc(),
+        );
+        // from bottom left to bottom right, clockwise
+        let angle = PI * 0.75f32 + self.range.normalize(value) * PI * 1.5f32;
+        d.draw_circle_lines(center, r, vis::BORDER_COLOR);
+        d.draw_line_ex(
+            center,
+            center + Vector2::new(angle.cos(), angle.sin()) * r,
+            1f32,
+            vis::LINE_COLORS[1],
+        );
+    }
+
+    /// Returns true if the event was for this knob and changed the value, clicking alone
+    /// doesn't.
+    pub fn update(&self, value: &mut f32, pos: Vector2, event: MouseEvent) -> bool {
+        if !self.rec.check_collision_point_rec(pos) {
+            return false;
+        }
+        let x = self.range.normalize(*value);
+        let x = match event {
+            MouseEvent::Click => return false,
+            MouseEvent::Drag(delta) => x - delta.y * DRAG_SPEED,
+            MouseEvent::Scroll(wheel) => x + wheel * SCROLL_STEP,
+        };
+        let previous = *value;
+        *value = self.range.denormalize(x);
+        *value != previous
+    }
+}
+
+/// Clicking jumps to the position, dragging sideways or scrolling moves the handle.
+#[derive(Debug, Clone, Copy)]
+pub struct Slider {
+    pub rec: Rectangle,
+    pub range: Range,
+}
+
+impl Slider {
+    pub fn new(rec: Rectangle, range: Range) -> Self {
+        Self { rec, range }
+    }
+
+    pub fn draw(&self, d: &mut Canvas, value: f32) {
+        let y = (self.rec.y + self.rec.height / 2f32).trunc();
+        d.draw_line_ex(
+            Vector2::new(self.rec.x, y),
+            Vector2::new(self.rec.x + self.rec.width, y),
+            1f32,
+            vis::BORDER_COLOR,
+        );
+        let x = (self.rec.x + self.range.normalize(value) * self.rec.width).trunc();
+        d.draw_rectangle_lines_ex(
+            Rectangle {
+                x: x - T,
+                y: self.rec.y,
+                width: T * 2f32,
+                height: self.rec.height,
+            },
+            1f32,
+            vis::LINE_COLORS[1],
+        );
+    }
+
+    /// Returns true if the event was for this slider.
+    pub fn update(&self, value: &mut f32, pos: Vector2, event: MouseEvent) -> bool {
+        if !self.rec.check_collision_point_rec(pos) {
+            return false;
+        }
+        let x = match event {
+            MouseEvent::Click => (pos.x - self.rec.x) / self.rec.width,
+            MouseEvent::Drag(delta) => self.range.normalize(*value) + delta.x / self.rec.width,
+            MouseEvent::Scroll(wheel) => self.range.normalize(*value) + wheel * SCROLL_STEP,
+        };
+        *value = self.range.denormalize(x);
+        true
+    }
+}
+
+/// Cycles between options when clicked or scrolled, a toggle when there are two.
+#[derive(Debug, Clone, Copy)]
+pub struct Toggle {
+    pub rec: Rectangle,
+    pub options: usize,
+}
+
+impl Toggle {
+    pub fn new(rec: Rectangle, options: usize) -> Self {
+        Self { rec, options }
+    }
+
+    pub fn draw(&self, d: &mut Canvas, label: &str) {
+        d.draw_rectangle_lines_ex(self.rec, 1f32, vis::BORDER_COLOR);
+        d.draw_text(
+            label,
+            (self.rec.x + T) as _,
+            (self.rec.y + T) as _,
+            1,
+            vis::TEXT_COLOR,
+        );
+    }
+
+    /// Returns true if the event was for this toggle.
+    pub fn update(&self, index: &mut usize, pos: Vector2, event: MouseEvent) -> bool {
+        if !self.rec.check_collision_point_rec(pos) {
+            return false;
+        }
+        *index = match event {
+            MouseEvent::Click => (*index + 1) % self.options,
+            MouseEvent::Drag(_) => return true,
+            MouseEvent::Scroll(wheel) if wheel > 0f32 => (*index + 1) % self.options,
+            MouseEvent::Scroll(_) => (*index + self.options - 1) % self.options,
+        };
+        true
+    }
+}
+
+/// Asks its block to do something when clicked.
+#[derive(Debug, Clone, Copy)]
+pub struct Button {
+    pub rec: Rectangle,
+}
+
+impl Button {
+    pub fn new(rec: Rectangle) -> Self {
+        Self { rec }
+    }
+
+    pub fn draw(&self, d: &mut Canvas, label: &str) {
+        d.draw_rectangle_lines_ex(self.rec, 1f32, vis::BORDER_COLOR);
+        d.draw_text(
+            label,
+            (self.rec.x + T) as _,
+            (self.rec.y + T) as _,
+            1,
+            vis::TEXT_COLOR,
+        );
+    }
+
+    /// Returns true if the event was a click on this button.
+    pub fn update(&self, pos: Vector2, event: MouseEvent) -> bool {
+        matches!(event, MouseEvent::Click) && self.rec.check_collision_point_rec(pos)
+    }
+}
+
+/// A simple box with `rows` rows of widgets under it, drawn by `draw_widgets`.
+pub fn visualize_with_widgets<O>(
+    text: &str,
+    out: O,
+    rows: usize,
+    draw_widgets: impl FnOnce(&mut Canvas) + 'static,
+) -> (O, VisualizeResult) {
+    let text = text.to_string();
+    let center = (BOX_SIZE / 2f32).trunc();
+    let height = BOX_SIZE + ROW_HEIGHT * rows as f32;
+    let layout = Layout::new(BOX_SIZE, height, move |d| {
+        vis::draw_simple_bock(d, &text);
+        vis::draw_border(
+            d,
+            Rectangle {
+                x: 0f32,
+                y: BOX_SIZE,
+                width: BOX_SIZE,
+                height: height - BOX_SIZE,
+            },
+        );
+        draw_widgets(d);
+    });
+    (
+        out,
+        VisualizeResult::Block(layout.with_ports(
+            vec![Vector2::new(0f32, center)],
+            vec![Vector2::new(BOX_SIZE, center)],
+        )),
+    )
+}